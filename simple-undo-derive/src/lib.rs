@@ -0,0 +1,85 @@
+//! Derive macros for the [`simple-undo`](https://docs.rs/simple-undo) crate.
+//!
+//! They are re-exported by the `derive` feature of `simple-undo`, which documents them.
+#![warn(missing_docs)]
+#![forbid(unsafe_code)]
+#![deny(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+/// Generates a `<Struct>Setters` trait implemented for `Undo<Struct>`,
+/// with a `set_<field>` method recording a labeled update for each field.
+#[proc_macro_derive(Undoable)]
+pub fn derive_undoable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_undoable(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_undoable(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Undoable` cannot be derived for generic structs",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            name,
+            "`Undoable` can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &data.fields,
+            "`Undoable` can only be derived for structs with named fields",
+        ));
+    };
+
+    let vis = &input.vis;
+    let trait_name = format_ident!("{name}Setters");
+    let trait_doc = format!(
+        "Setters of [`{name}`] recording an undoable update, generated by `#[derive(Undoable)]`."
+    );
+    let fields = fields.named.iter().filter_map(|field| {
+        let field_name = field.ident.as_ref()?;
+        let unraw_name = field_name.to_string().trim_start_matches("r#").to_owned();
+        let setter = format_ident!("set_{unraw_name}");
+        let ty = &field.ty;
+        let label = format!("Set {unraw_name}");
+        let doc = format!(
+            "Sets the `{unraw_name}` field, recording the update with the \"{label}\" label."
+        );
+        Some((field_name, setter, ty, label, doc))
+    });
+    let declarations = fields.clone().map(|(_, setter, ty, _, doc)| {
+        quote! {
+            #[doc = #doc]
+            fn #setter(&mut self, value: #ty);
+        }
+    });
+    let implementations = fields.map(|(field_name, setter, ty, label, _)| {
+        quote! {
+            fn #setter(&mut self, value: #ty) {
+                self.update_labeled(#label, move |state: &mut #name| {
+                    state.#field_name = ::core::clone::Clone::clone(&value);
+                });
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[doc = #trait_doc]
+        #vis trait #trait_name {
+            #(#declarations)*
+        }
+
+        impl #trait_name for ::simple_undo::Undo<'_, #name> {
+            #(#implementations)*
+        }
+    })
+}
@@ -0,0 +1,63 @@
+/* C API of simple-undo, built by the `simple-undo-ffi` crate. */
+
+#ifndef SIMPLE_UNDO_H
+#define SIMPLE_UNDO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A history of a state made of bytes. */
+typedef struct SimpleUndo SimpleUndo;
+
+/* The state being updated by a `SimpleUndoUpdateFn`. */
+typedef struct SimpleUndoState SimpleUndoState;
+
+/* A callback updating the state, called once with the `userdata` given to `simple_undo_update`. */
+typedef void (*SimpleUndoUpdateFn)(void *userdata, SimpleUndoState *state);
+
+/* The outcome of `simple_undo_update`. */
+typedef enum SimpleUndoStatus {
+    /* The update was recorded. */
+    SIMPLE_UNDO_OK = 0,
+    /* `undo` or `update_fn` was null, so nothing was done. */
+    SIMPLE_UNDO_NULL_POINTER = 1,
+    /* The update panicked, so the state was restored and nothing was recorded. */
+    SIMPLE_UNDO_PANICKED = 2,
+} SimpleUndoStatus;
+
+/* Wraps a copy of the given bytes, to be freed with `simple_undo_free`. */
+SimpleUndo *simple_undo_new(const uint8_t *data, size_t len);
+
+/* Frees a `SimpleUndo` created with `simple_undo_new`. Does nothing if `undo` is null. */
+void simple_undo_free(SimpleUndo *undo);
+
+/* Updates the state with `update_fn`, recording the changes as a single update. */
+SimpleUndoStatus simple_undo_update(SimpleUndo *undo, SimpleUndoUpdateFn update_fn, void *userdata);
+
+/* Undo the last update. Returns false if there was nothing to undo, or if `undo` is null. */
+bool simple_undo_undo(SimpleUndo *undo);
+
+/* Redo the last undone update. Returns false if there was nothing to redo, or if `undo` is null. */
+bool simple_undo_redo(SimpleUndo *undo);
+
+/* Returns the current state, valid until the next call modifying `undo`, writing its length to `len`.
+ * Returns null and writes a length of 0 if `undo` is null. */
+const uint8_t *simple_undo_get(const SimpleUndo *undo, size_t *len);
+
+/* Returns the state being updated, writing its length to `len`.
+ * Returns null and writes a length of 0 if `state` is null. */
+const uint8_t *simple_undo_state_get(const SimpleUndoState *state, size_t *len);
+
+/* Replaces the state being updated with a copy of the given bytes. Does nothing if `state` is null. */
+void simple_undo_state_set(SimpleUndoState *state, const uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SIMPLE_UNDO_H */
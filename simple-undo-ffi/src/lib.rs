@@ -0,0 +1,286 @@
+//! A minimal C API for the [`simple-undo`](https://docs.rs/simple-undo) crate, to embed its history engine
+//! in C or C++ applications.
+//!
+//! The state is a buffer of bytes, encoded as the application sees fit, declared in `include/simple_undo.h`.
+//! Build a static library with `cargo build --release -p simple-undo-ffi`.
+//!
+//! ```c
+//! void append(void *userdata, SimpleUndoState *state) {
+//!     size_t len;
+//!     const uint8_t *data = simple_undo_state_get(state, &len);
+//!     uint8_t *buffer = malloc(len + 1);
+//!     if (buffer == NULL) {
+//!         return;
+//!     }
+//!     memcpy(buffer, data, len);
+//!     buffer[len] = *(uint8_t *)userdata;
+//!     simple_undo_state_set(state, buffer, len + 1);
+//!     free(buffer);
+//! }
+//!
+//! SimpleUndo *text = simple_undo_new((const uint8_t *)"ab", 2);
+//! uint8_t c = 'c';
+//! if (simple_undo_update(text, append, &c) != SIMPLE_UNDO_OK) {
+//!     /* the update was not recorded */
+//! }
+//! simple_undo_undo(text);
+//! simple_undo_free(text);
+//! ```
+#![warn(missing_docs)]
+#![deny(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
+
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use simple_undo::Undo;
+
+/// An [`Undo`] wrapping a buffer of bytes, created with [`simple_undo_new`] and freed with [`simple_undo_free`].
+pub struct SimpleUndo(Undo<'static, Vec<u8>>);
+
+/// The state being updated by a [`SimpleUndoUpdateFn`], read with [`simple_undo_state_get`]
+/// and replaced with [`simple_undo_state_set`].
+pub struct SimpleUndoState(Vec<u8>);
+
+/// A callback updating the state, called once with the `userdata` given to [`simple_undo_update`].
+pub type SimpleUndoUpdateFn = extern "C" fn(userdata: *mut c_void, state: *mut SimpleUndoState);
+
+/// The outcome of [`simple_undo_update`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimpleUndoStatus {
+    /// The update was recorded.
+    Ok = 0,
+    /// `undo` or `update_fn` was null, so nothing was done.
+    NullPointer = 1,
+    /// The update panicked, so the state was restored and nothing was recorded.
+    Panicked = 2,
+}
+
+/// Returns the given bytes as a slice, an empty one if `data` is null.
+///
+/// # Safety
+/// `data` must be null or point to `len` readable bytes.
+const unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() {
+        &[]
+    } else {
+        // SAFETY: guaranteed by the caller.
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+}
+
+/// Wraps a copy of the given bytes in a new `SimpleUndo`, to be freed with [`simple_undo_free`].
+///
+/// # Safety
+/// `data` must be null or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_new(data: *const u8, len: usize) -> *mut SimpleUndo {
+    // SAFETY: guaranteed by the caller.
+    let state = unsafe { bytes(data, len) }.to_vec();
+    Box::into_raw(Box::new(SimpleUndo(Undo::new(state))))
+}
+
+/// Frees a `SimpleUndo` created with [`simple_undo_new`]. Does nothing if `undo` is null.
+///
+/// # Safety
+/// `undo` must be null or returned by [`simple_undo_new`], and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_free(undo: *mut SimpleUndo) {
+    if !undo.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { Box::from_raw(undo) });
+    }
+}
+
+/// Updates the state with `update_fn`, recording the changes as a single update like [`Undo::modify`].
+///
+/// Returns [`SimpleUndoStatus::NullPointer`] if `undo` or `update_fn` is null,
+/// and [`SimpleUndoStatus::Panicked`] if the update panicked, the state being restored.
+///
+/// # Safety
+/// `undo` must be null or a valid `SimpleUndo`, not used by `update_fn`,
+/// and `userdata` is only passed to `update_fn`.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_update(
+    undo: *mut SimpleUndo,
+    update_fn: Option<SimpleUndoUpdateFn>,
+    userdata: *mut c_void,
+) -> SimpleUndoStatus {
+    // SAFETY: guaranteed by the caller, `undo` being checked for null.
+    let (Some(undo), Some(update_fn)) = (unsafe { undo.as_mut() }, update_fn) else {
+        return SimpleUndoStatus::NullPointer;
+    };
+    // Unwinding out of an `extern "C"` function aborts, so a panic is reported instead.
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = undo.0.modify();
+        let mut state = SimpleUndoState(std::mem::take(&mut *guard));
+        update_fn(userdata, &raw mut state);
+        *guard = state.0;
+    }));
+    match result {
+        Ok(()) => SimpleUndoStatus::Ok,
+        Err(_) => SimpleUndoStatus::Panicked,
+    }
+}
+
+/// Undo the last update, like [`Undo::undo`].
+///
+/// Returns `false` if there was nothing to undo, or if `undo` is null.
+///
+/// # Safety
+/// `undo` must be null or a valid `SimpleUndo`.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_undo(undo: *mut SimpleUndo) -> bool {
+    // SAFETY: guaranteed by the caller, `undo` being checked for null.
+    unsafe { undo.as_mut() }.is_some_and(|undo| undo.0.undo())
+}
+
+/// Redo the last undone update, like [`Undo::redo`].
+///
+/// Returns `false` if there was nothing to redo, or if `undo` is null.
+///
+/// # Safety
+/// `undo` must be null or a valid `SimpleUndo`.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_redo(undo: *mut SimpleUndo) -> bool {
+    // SAFETY: guaranteed by the caller, `undo` being checked for null.
+    unsafe { undo.as_mut() }.is_some_and(|undo| undo.0.redo())
+}
+
+/// Returns the current state, writing its length to `len`.
+///
+/// The returned bytes are only valid until the next call modifying the `SimpleUndo`.
+/// Returns null and writes a length of `0` if `undo` is null.
+///
+/// # Safety
+/// `undo` must be null or a valid `SimpleUndo`, and `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_get(undo: *const SimpleUndo, len: *mut usize) -> *const u8 {
+    // SAFETY: guaranteed by the caller, `undo` being checked for null.
+    let state = unsafe { undo.as_ref() }.map(|undo| undo.0.get().as_slice());
+    // SAFETY: guaranteed by the caller.
+    unsafe { get(state, len) }
+}
+
+/// Returns the state being updated, writing its length to `len`.
+///
+/// Returns null and writes a length of `0` if `state` is null.
+///
+/// # Safety
+/// `state` must be null or the one given to the [`SimpleUndoUpdateFn`], and `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_state_get(
+    state: *const SimpleUndoState,
+    len: *mut usize,
+) -> *const u8 {
+    // SAFETY: guaranteed by the caller, `state` being checked for null.
+    let state = unsafe { state.as_ref() }.map(|state| state.0.as_slice());
+    // SAFETY: guaranteed by the caller.
+    unsafe { get(state, len) }
+}
+
+/// Replaces the state being updated with a copy of the given bytes. Does nothing if `state` is null.
+///
+/// # Safety
+/// `state` must be null or the one given to the [`SimpleUndoUpdateFn`],
+/// and `data` must be null or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn simple_undo_state_set(
+    state: *mut SimpleUndoState,
+    data: *const u8,
+    len: usize,
+) {
+    // SAFETY: guaranteed by the caller, `state` being checked for null.
+    let Some(state) = (unsafe { state.as_mut() }) else {
+        return;
+    };
+    // SAFETY: guaranteed by the caller.
+    let data = unsafe { bytes(data, len) };
+    state.0.clear();
+    state.0.extend_from_slice(data);
+}
+
+/// Returns a pointer to the given bytes, null if there are none, writing their length to `len`.
+///
+/// # Safety
+/// `len` must be null or writable.
+unsafe fn get(bytes: Option<&[u8]>, len: *mut usize) -> *const u8 {
+    if !len.is_null() {
+        // SAFETY: guaranteed by the caller.
+        unsafe { ptr::write(len, bytes.map_or(0, <[u8]>::len)) };
+    }
+    bytes.map_or(ptr::null(), <[u8]>::as_ptr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn append(userdata: *mut c_void, state: *mut SimpleUndoState) {
+        let mut len = 0;
+        // SAFETY: `state` is the one given by `simple_undo_update`, and `len` is writable.
+        let data = unsafe { simple_undo_state_get(state, &raw mut len) };
+        // SAFETY: `data` points to the `len` bytes of the state.
+        let mut text = unsafe { bytes(data, len) }.to_vec();
+        // SAFETY: `userdata` points to the byte given to `simple_undo_update`.
+        text.push(unsafe { *userdata.cast::<u8>() });
+        // SAFETY: `state` is the one given by `simple_undo_update`, and `text` holds `text.len()` bytes.
+        unsafe { simple_undo_state_set(state, text.as_ptr(), text.len()) };
+    }
+
+    #[test]
+    fn it_updates_through_the_c_api() {
+        // SAFETY: `b"ab"` holds 2 readable bytes.
+        let text = unsafe { simple_undo_new(b"ab".as_ptr(), 2) };
+        let mut c = b'c';
+        // SAFETY: `text` is a valid `SimpleUndo`, and `append` reads the byte given as `userdata`.
+        let status =
+            unsafe { simple_undo_update(text, Some(append), ptr::from_mut(&mut c).cast()) };
+        assert_eq!(status, SimpleUndoStatus::Ok);
+
+        let mut len = 0;
+        // SAFETY: `text` is a valid `SimpleUndo`, and `len` is writable.
+        let data = unsafe { simple_undo_get(text, &raw mut len) };
+        // SAFETY: `data` points to the `len` bytes of the current state.
+        assert_eq!(unsafe { bytes(data, len) }, b"abc");
+        // SAFETY: `text` is a valid `SimpleUndo`.
+        unsafe {
+            assert!(simple_undo_undo(text));
+            assert!(!simple_undo_undo(text));
+            assert!(simple_undo_redo(text));
+        }
+        // SAFETY: `text` is a valid `SimpleUndo`, and a null `len` is not written.
+        let data = unsafe { simple_undo_get(text, ptr::null_mut()) };
+        // SAFETY: `data` points to the 3 bytes of the current state.
+        assert_eq!(unsafe { bytes(data, 3) }, b"abc");
+        // SAFETY: `text` was returned by `simple_undo_new` and is not used afterwards.
+        unsafe { simple_undo_free(text) };
+    }
+
+    #[test]
+    fn it_rejects_null_pointers() {
+        let mut len = 1;
+        // SAFETY: null pointers are checked, and `len` is writable.
+        unsafe {
+            assert_eq!(
+                simple_undo_update(ptr::null_mut(), Some(append), ptr::null_mut()),
+                SimpleUndoStatus::NullPointer
+            );
+            assert!(!simple_undo_undo(ptr::null_mut()));
+            assert!(!simple_undo_redo(ptr::null_mut()));
+            assert!(simple_undo_get(ptr::null(), &raw mut len).is_null());
+        }
+        assert_eq!(len, 0);
+
+        // SAFETY: a null pointer with a length of 0 is an empty state.
+        let text = unsafe { simple_undo_new(ptr::null(), 0) };
+        // SAFETY: `text` is a valid `SimpleUndo`, and the null callback is checked.
+        let status = unsafe { simple_undo_update(text, None, ptr::null_mut()) };
+        assert_eq!(status, SimpleUndoStatus::NullPointer);
+        // SAFETY: `text` is a valid `SimpleUndo`.
+        assert!(!unsafe { simple_undo_undo(text) });
+        // SAFETY: `text` was returned by `simple_undo_new` and is not used afterwards.
+        unsafe { simple_undo_free(text) };
+    }
+}
@@ -0,0 +1,274 @@
+//! Reconstruction of a state of the history on a worker thread, so that a long replay never blocks the caller.
+
+#[cfg(feature = "time")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::thread::{self, JoinHandle};
+#[cfg(feature = "time")]
+use std::time::Duration;
+
+use crate::{Action, ChangeKind, ChangeToken, Command, OutOfRange, Snapshot, Undo};
+
+/// A state replayed on a worker thread by [`Undo::spawn_replay`], to move the `Undo` to with [`Undo::finish_replay`].
+#[derive(Debug)]
+pub struct Replayed<TState> {
+    /// The replayed state.
+    state: TState,
+    /// The position of the replayed state in the history.
+    position: usize,
+    /// The position of the checkpoint the state was replayed from.
+    checkpoint: usize,
+    /// The time taken to replay the state, measured with the clock when a replay budget is set.
+    #[cfg(feature = "time")]
+    cost: Option<Duration>,
+    /// The version of the `Undo` when the replay was spawned.
+    token: ChangeToken,
+    /// The length of the history when the replay was spawned.
+    history_len: usize,
+}
+
+impl<TState> Replayed<TState> {
+    /// Returns the replayed state.
+    #[must_use]
+    pub const fn state(&self) -> &TState {
+        &self.state
+    }
+
+    /// Returns the position of the replayed state in the history.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Unwraps the replayed state.
+    pub fn into_state(self) -> TState {
+        self.state
+    }
+}
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Replays the state at the given history position on a new thread, calling `deliver` with the result.
+    ///
+    /// The nearest checkpoint and the commands to replay are cloned beforehand, so the `Undo` can still be used
+    /// during the replay, which requires [`Command`]s that can be cloned and sent to another thread.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if `position` is greater than [`Undo::history_len`], without spawning a thread.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::sync::mpsc;
+    /// let mut counter: Undo<u64, fn(&mut u64)> = Undo::with_commands(0);
+    /// for _ in 0..1000 {
+    ///     counter.apply(|value| *value += 1);
+    /// }
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    /// counter.spawn_replay(10, move |replayed| sender.send(replayed).unwrap())?;
+    /// // ... later, on the thread of the `Undo`
+    /// let replayed = receiver.recv().unwrap();
+    /// assert!(counter.finish_replay(replayed));
+    /// assert_eq!(*counter, 10);
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn spawn_replay(
+        &self,
+        position: usize,
+        deliver: impl FnOnce(Replayed<TState>) + Send + 'static,
+    ) -> Result<JoinHandle<()>, OutOfRange>
+    where
+        TState: Send + 'static,
+        TState::Snapshot: Clone + Send + 'static,
+        TCommand: Clone + Send + 'static,
+    {
+        self.check_position(position)?;
+        // The initial state at position 0 is always found.
+        let index = self
+            .checkpoints
+            .partition_point(|(checkpoint, _)| *checkpoint <= position)
+            - 1;
+        let (start, snapshot) = &self.checkpoints[index];
+        let snapshot = snapshot.clone();
+        let commands: Vec<TCommand> = self.entries[*start..position]
+            .iter()
+            .map(|entry| entry.command.clone())
+            .collect();
+        let checkpoint = *start;
+        let token = self.change_token();
+        let history_len = self.entries.len();
+        #[cfg(feature = "time")]
+        let clock = self.replay_budget.map(|_| Arc::clone(&self.clock));
+        Ok(thread::spawn(move || {
+            #[cfg(feature = "time")]
+            let started = clock.as_ref().map(|clock| clock.now());
+            let mut state = TState::from_snapshot(snapshot);
+            for command in &commands {
+                command.apply(&mut state);
+            }
+            deliver(Replayed {
+                state,
+                position,
+                checkpoint,
+                #[cfg(feature = "time")]
+                cost: clock
+                    .zip(started)
+                    .map(|(clock, started)| clock.now().duration_since(started)),
+                token,
+                history_len,
+            });
+        }))
+    }
+
+    /// Moves to the position of a state replayed by [`Undo::spawn_replay`], as an undo or a redo.
+    ///
+    /// It counts as a replay from a checkpoint in [`Undo::stats`], and takes a checkpoint at this position
+    /// if the replay took longer than the [replay budget](Undo::set_replay_budget), as [`Undo::jump_to`] does.
+    ///
+    /// Returns `false` if the history changed since the replay was spawned, or if a middleware vetoed the move,
+    /// leaving the `Undo` untouched.
+    pub fn finish_replay(&mut self, replayed: Replayed<TState>) -> bool {
+        if self.has_changed_since(replayed.token) || self.entries.len() != replayed.history_len {
+            return false;
+        }
+        let (kind, mut action) = match replayed.position {
+            position if position < self.nb_updates => (
+                ChangeKind::Undo,
+                Action::Undo {
+                    count: self.nb_updates - position,
+                },
+            ),
+            position if position > self.nb_updates => (
+                ChangeKind::Redo,
+                Action::Redo {
+                    count: position - self.nb_updates,
+                },
+            ),
+            _ => return true,
+        };
+        if !self.intercept(&mut action) {
+            return false;
+        }
+        #[cfg(feature = "tracing")]
+        let span = if kind == ChangeKind::Undo {
+            tracing::debug_span!(
+                "undo",
+                count = self.nb_updates - replayed.position,
+                position = replayed.position
+            )
+        } else {
+            tracing::debug_span!("redo", count = replayed.position - self.nb_updates)
+        };
+        #[cfg(feature = "tracing")]
+        let _guard = span.enter();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            checkpoint = replayed.checkpoint,
+            replayed = replayed.position - replayed.checkpoint,
+            "replayed the state on a worker thread"
+        );
+        self.current_state = replayed.state;
+        self.nb_updates = replayed.position;
+        self.stats.replays += 1;
+        self.stats.replayed_updates += (replayed.position - replayed.checkpoint) as u64;
+        #[cfg(feature = "time")]
+        if let Some(cost) = replayed.cost {
+            self.replay_cost = cost;
+            let index = self
+                .checkpoints
+                .partition_point(|(checkpoint, _)| *checkpoint <= replayed.position);
+            if self.is_over_replay_budget() && self.checkpoints[index - 1].0 < replayed.position {
+                self.checkpoints
+                    .insert(index, (replayed.position, self.current_state.snapshot()));
+                self.replay_cost = Duration::ZERO;
+            }
+        }
+        self.notify(kind);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn it_replays_the_state_on_another_thread() {
+        let mut list: Undo<Vec<u32>, fn(&mut Vec<u32>)> = Undo::with_commands(Vec::new());
+        list.apply(|list| list.push(1));
+        list.apply(|list| list.push(2));
+        list.apply(|list| list.push(3));
+
+        let (sender, receiver) = mpsc::channel();
+        list.spawn_replay(1, move |replayed| sender.send(replayed).unwrap())
+            .unwrap()
+            .join()
+            .unwrap();
+        let replayed = receiver.recv().unwrap();
+        assert_eq!((replayed.state(), replayed.position()), (&vec![1], 1));
+        assert!(list.finish_replay(replayed));
+        assert_eq!(*list, [1]);
+        assert!(list.can_redo());
+        assert_eq!(
+            (list.stats().replays, list.stats().replayed_updates),
+            (1, 1)
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        list.spawn_replay(3, move |replayed| sender.send(replayed).unwrap())
+            .unwrap();
+        list.apply(|list| list.push(4));
+        assert!(!list.finish_replay(receiver.recv().unwrap()));
+        assert_eq!(*list, [1, 4]);
+        assert!(list.spawn_replay(3, |_| {}).is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_takes_a_checkpoint_when_the_replay_is_over_the_budget() {
+        use crate::clock::ManualClock;
+
+        #[derive(Clone)]
+        struct SlowIncrement(ManualClock);
+
+        impl Command<u64> for SlowIncrement {
+            fn apply(&self, value: &mut u64) {
+                self.0.advance(Duration::from_millis(2));
+                *value += 1;
+            }
+        }
+
+        let clock = ManualClock::new();
+        let mut counter: Undo<u64, SlowIncrement> = Undo::with_commands(0);
+        counter.set_clock(clock.clone());
+        for _ in 0..6 {
+            counter.apply(SlowIncrement(clock.clone()));
+        }
+        counter.set_replay_budget(Some(Duration::from_millis(3)));
+        assert_eq!(counter.checkpoints.len(), 1);
+
+        let (sender, receiver) = mpsc::channel();
+        counter
+            .spawn_replay(5, move |replayed| sender.send(replayed).unwrap())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(counter.finish_replay(receiver.recv().unwrap()));
+        assert_eq!(*counter, 5);
+        assert!(counter
+            .checkpoints
+            .iter()
+            .any(|(position, _)| *position == 5));
+        assert_eq!(counter.replay_cost, Duration::ZERO);
+        assert_eq!(
+            (counter.stats().replays, counter.stats().replayed_updates),
+            (1, 5)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Integration with the Bevy game engine, enabled by the `bevy` feature, to undo changes made to resources.
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use bevy_app::{App, Plugin, PreUpdate};
+use bevy_ecs::prelude::*;
+
+use crate::{SendUndo, Snapshot, Undo};
+
+/// A resource wrapping a state in a [`SendUndo`], inserted by the [`UndoPlugin`].
+///
+/// Systems update the state through it, and it dereferences to the wrapped `Undo`:
+/// ```
+/// use bevy_ecs::prelude::*;
+/// use simple_undo::bevy::Undoable;
+///
+/// #[derive(Clone, Default)]
+/// struct Score(u32);
+///
+/// fn score_system(mut score: ResMut<Undoable<Score>>) {
+///     score.update(|score| score.0 += 10);
+/// }
+/// ```
+#[derive(Resource)]
+pub struct Undoable<TState: Snapshot + Send + Sync + 'static>(pub SendUndo<'static, TState>)
+where
+    TState::Snapshot: Send + Sync;
+
+impl<TState> Undoable<TState>
+where
+    TState: Snapshot + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    /// Wraps the given state, like [`Undo::new_send`].
+    pub fn new(state: TState) -> Self {
+        Self(Undo::new_send(state))
+    }
+}
+
+impl<TState> Deref for Undoable<TState>
+where
+    TState: Snapshot + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    type Target = SendUndo<'static, TState>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<TState> DerefMut for Undoable<TState>
+where
+    TState: Snapshot + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A message undoing the last update of every [`Undoable`] resource, such as when pressing Ctrl+Z.
+#[derive(Message, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UndoEvent;
+
+/// A message redoing the last undone update of every [`Undoable`] resource, such as when pressing Ctrl+Y.
+#[derive(Message, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RedoEvent;
+
+/// A plugin inserting an [`Undoable`] resource of the default state, unless already inserted,
+/// and undoing or redoing it on each [`UndoEvent`] or [`RedoEvent`] before the `Update` schedule.
+///
+/// Only the number of messages of each kind matters: the undos received in a frame are applied before
+/// its redos, whatever the order in which they were written. Write them in separate frames to keep their order.
+///
+/// # Example
+/// ```
+/// use bevy_app::App;
+/// use simple_undo::bevy::{RedoEvent, UndoEvent, UndoPlugin, Undoable};
+///
+/// #[derive(Clone, Default)]
+/// struct Score(u32);
+///
+/// let mut app = App::new();
+/// app.add_plugins(UndoPlugin::<Score>::default());
+/// app.world_mut().resource_mut::<Undoable<Score>>().update(|score| score.0 += 10);
+///
+/// app.world_mut().write_message(UndoEvent);
+/// app.update();
+/// assert_eq!(app.world().resource::<Undoable<Score>>().get().0, 0);
+/// ```
+pub struct UndoPlugin<TState>(PhantomData<fn() -> TState>);
+
+impl<TState> Default for UndoPlugin<TState> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<TState> Plugin for UndoPlugin<TState>
+where
+    TState: Snapshot + Default + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<Undoable<TState>>() {
+            app.insert_resource(Undoable::new(TState::default()));
+        }
+        app.add_message::<UndoEvent>()
+            .add_message::<RedoEvent>()
+            .add_systems(PreUpdate, undo_redo_system::<TState>);
+    }
+}
+
+/// Undoes the [`Undoable`] resource for each [`UndoEvent`] received, then redoes it for each [`RedoEvent`].
+fn undo_redo_system<TState>(
+    mut undoable: ResMut<Undoable<TState>>,
+    mut undo_events: MessageReader<UndoEvent>,
+    mut redo_events: MessageReader<RedoEvent>,
+) where
+    TState: Snapshot + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    let nb_undos = undo_events.read().count();
+    let nb_redos = redo_events.read().count();
+    if nb_undos > 0 {
+        undoable.undo_n(nb_undos);
+    }
+    if nb_redos > 0 {
+        undoable.redo_n(nb_redos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Selection(Vec<u32>);
+
+    #[test]
+    fn it_undoes_a_resource_on_messages() {
+        let mut app = App::new();
+        app.insert_resource(Undoable::new(Selection(vec![1])));
+        app.add_plugins(UndoPlugin::<Selection>::default());
+        let mut selection = app.world_mut().resource_mut::<Undoable<Selection>>();
+        selection.update(|selection| selection.0.push(2));
+        selection.update(|selection| selection.0.push(3));
+
+        app.world_mut().write_message(UndoEvent);
+        app.world_mut().write_message(UndoEvent);
+        app.update();
+        assert_eq!(
+            **app.world().resource::<Undoable<Selection>>(),
+            Selection(vec![1])
+        );
+
+        app.world_mut().write_message(RedoEvent);
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world().resource::<Undoable<Selection>>().get().0,
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn it_applies_the_undos_before_the_redos_of_a_frame() {
+        let mut app = App::new();
+        app.insert_resource(Undoable::new(Selection(vec![1])));
+        app.add_plugins(UndoPlugin::<Selection>::default());
+        app.world_mut()
+            .resource_mut::<Undoable<Selection>>()
+            .update(|selection| selection.0.push(2));
+
+        app.world_mut().write_message(RedoEvent);
+        app.world_mut().write_message(UndoEvent);
+        app.update();
+        assert_eq!(
+            app.world().resource::<Undoable<Selection>>().get().0,
+            [1, 2]
+        );
+    }
+}
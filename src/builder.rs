@@ -0,0 +1,188 @@
+//! A builder configuring an [`Undo`] before any update, as an alternative to its `set_*` methods.
+
+use core::num::NonZeroUsize;
+#[cfg(feature = "time")]
+use std::time::Duration;
+
+#[cfg(feature = "time")]
+use crate::clock::Clock;
+
+use crate::{
+    Action, ChangeKind, CompactionPolicy, MemoryBudget, RedoPolicy, Snapshot, Undo, Update,
+    Validator,
+};
+
+/// A builder of an [`Undo`], returned by [`Undo::builder`].
+///
+/// # Example
+/// ```
+/// # use simple_undo::Undo;
+/// # use std::num::NonZeroUsize;
+/// let mut text = Undo::builder(String::new())
+///     .limit(100)
+///     .checkpoint_interval(NonZeroUsize::new(10))
+///     .skip_unchanged(PartialEq::eq)
+///     .build();
+/// text.update(|text| text.push_str("Hello"));
+/// assert_eq!(text.limit(), Some(100));
+/// ```
+#[must_use = "the builder does nothing until `build` is called"]
+pub struct UndoBuilder<'state, TState: Snapshot> {
+    /// The `Undo` being configured, without any recorded update.
+    undo: Undo<'state, TState>,
+}
+
+impl<'state, TState: Snapshot + 'state> Undo<'state, TState> {
+    /// Returns a builder wrapping the given state, to configure the `Undo` before building it.
+    pub fn builder(state: TState) -> UndoBuilder<'state, TState> {
+        UndoBuilder {
+            undo: Self::new(state),
+        }
+    }
+}
+
+impl<'state, TState: Snapshot + 'state> UndoBuilder<'state, TState> {
+    /// Reserves room for the given number of updates in the history.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.undo.entries.reserve(capacity);
+        self
+    }
+
+    /// Sets the maximum number of updates kept in the history, see [`Undo::set_limit`].
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.undo.set_limit(Some(limit));
+        self
+    }
+
+    /// Sets the policy compacting the history, see [`Undo::set_compaction_policy`].
+    pub fn compaction_policy(mut self, policy: CompactionPolicy) -> Self {
+        self.undo.set_compaction_policy(policy);
+        self
+    }
+
+    /// Bounds the memory used by the history, see [`Undo::set_memory_budget`].
+    pub fn memory_budget(mut self, budget: MemoryBudget<TState, Update<'state, TState>>) -> Self {
+        self.undo.set_memory_budget(Some(budget));
+        self
+    }
+
+    /// Sets what happens to the undone updates when updating after an undo, see [`Undo::set_redo_policy`].
+    pub fn redo_policy(mut self, policy: RedoPolicy) -> Self {
+        self.undo.set_redo_policy(policy);
+        self
+    }
+
+    /// Sets the number of updates between two snapshots of the state, see [`Undo::set_checkpoint_interval`].
+    pub fn checkpoint_interval(mut self, interval: Option<NonZeroUsize>) -> Self {
+        self.undo.set_checkpoint_interval(interval);
+        self
+    }
+
+    /// Sets the maximum delay between two updates merged into a single entry, see [`Undo::set_grouping_window`].
+    #[cfg(feature = "time")]
+    pub const fn grouping_window(mut self, window: Duration) -> Self {
+        self.undo.set_grouping_window(Some(window));
+        self
+    }
+
+    /// Replaces the clock timestamping the recorded updates, see [`Undo::set_clock`].
+    #[cfg(feature = "time")]
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.undo.set_clock(clock);
+        self
+    }
+
+    /// Takes a snapshot of the state whenever replaying the updates would exceed `budget`,
+    /// see [`Undo::set_replay_budget`].
+    #[cfg(feature = "time")]
+    pub const fn replay_budget(mut self, budget: Duration) -> Self {
+        self.undo.set_replay_budget(Some(budget));
+        self
+    }
+
+    /// Skips recording the updates leaving the state unchanged, see [`Undo::set_skip_unchanged`].
+    pub fn skip_unchanged(mut self, is_unchanged: fn(&TState, &TState) -> bool) -> Self {
+        self.undo.set_skip_unchanged(Some(is_unchanged));
+        self
+    }
+
+    /// Redoes the first undone update when an update repeats it, see [`Undo::set_redo_resurrection`].
+    pub fn redo_resurrection(mut self, is_redone: fn(&TState, &TState) -> bool) -> Self {
+        self.undo.set_redo_resurrection(Some(is_redone));
+        self
+    }
+
+    /// Checks that the updates are deterministic in debug builds, see [`Undo::set_determinism_check`].
+    pub fn determinism_check(mut self, is_replayed: fn(&TState, &TState) -> bool) -> Self {
+        self.undo.set_determinism_check(Some(is_replayed));
+        self
+    }
+
+    /// Checks the state after each update, see [`Undo::set_validator`].
+    pub fn validator(mut self, validator: Validator<TState>) -> Self {
+        self.undo.set_validator(Some(validator));
+        self
+    }
+
+    /// Registers an observer called after each change of the state, see [`Undo::on_change`].
+    pub fn on_change(mut self, observer: impl FnMut(&TState, ChangeKind) + 'state) -> Self {
+        self.undo.on_change(observer);
+        self
+    }
+
+    /// Adds a middleware intercepting each action, see [`Undo::add_middleware`].
+    pub fn middleware(
+        mut self,
+        middleware: impl FnMut(&TState, &mut Action<'_>) -> bool + Send + Sync + 'state,
+    ) -> Self {
+        self.undo.add_middleware(middleware);
+        self
+    }
+
+    /// Sets whether the initial state is marked as saved, as by default, see [`Undo::is_modified`].
+    ///
+    /// A new document that was never saved is modified from the start.
+    pub const fn initially_saved(mut self, is_saved: bool) -> Self {
+        self.undo.saved_position = if is_saved { Some(0) } else { None };
+        self
+    }
+
+    /// Builds the configured `Undo`.
+    #[must_use]
+    pub fn build(self) -> Undo<'state, TState> {
+        self.undo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use core::cell::Cell;
+
+    #[test]
+    fn it_builds_a_configured_undo() {
+        let changes = Cell::new(0);
+        let mut counter = Undo::builder(0)
+            .capacity(10)
+            .limit(2)
+            .validator(|value| {
+                if *value < 10 {
+                    Ok(())
+                } else {
+                    Err(String::from("too large"))
+                }
+            })
+            .on_change(|_, _| changes.set(changes.get() + 1))
+            .initially_saved(false)
+            .build();
+        assert!(counter.is_modified());
+        for _ in 0..3 {
+            counter.update(|value| *value += 1);
+        }
+        counter.update(|value| *value += 10);
+        assert_eq!(*counter, 3);
+        assert_eq!(counter.history_len(), 2);
+        assert_eq!(changes.get(), 3);
+    }
+}
@@ -0,0 +1,377 @@
+//! Undoable cells embedded in a larger state, recording their changes in a shared [`CellRecorder`].
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Ref, RefCell};
+use core::fmt;
+use core::mem;
+
+use crate::{is_panicking, AnyUndo};
+
+/// A recorded change of a cell, undone or redone by swapping the value of the cell with the stored one.
+trait Change {
+    /// Swaps the value of the cell with the stored value.
+    fn swap(&mut self);
+}
+
+/// A change of an [`UndoCell`], storing its other value.
+struct CellChange<T> {
+    cell: Rc<RefCell<T>>,
+    value: T,
+}
+
+impl<T> Change for CellChange<T> {
+    fn swap(&mut self) {
+        mem::swap(&mut *self.cell.borrow_mut(), &mut self.value);
+    }
+}
+
+/// The history shared by a [`CellRecorder`] and its cells.
+#[derive(Default)]
+struct CellHistory {
+    /// All recorded steps, applied or not, each made of the changes of one or several cells.
+    entries: Vec<Vec<Box<dyn Change>>>,
+    /// Number of steps applied to the cells. Undoing reduces this number.
+    nb_updates: usize,
+    /// The changes recorded within [`CellRecorder::group`], if called.
+    group: Option<Vec<Box<dyn Change>>>,
+}
+
+/// A history of the changes made to its [`UndoCell`]s, wherever they live in the state.
+///
+/// Only the cells are undoable: the rest of the state is neither recorded nor cloned.
+/// Cloning the recorder shares the same history.
+///
+/// # Example
+/// ```
+/// use simple_undo::cell::{CellRecorder, UndoCell};
+///
+/// struct Document {
+///     title: UndoCell<String>,
+///     cache: Vec<u8>, // not undoable
+/// }
+///
+/// let recorder = CellRecorder::new();
+/// let mut document = Document {
+///     title: recorder.cell(String::from("Draft")),
+///     cache: Vec::new(),
+/// };
+/// document.title.set(String::from("Report"));
+/// document.cache.push(1);
+///
+/// recorder.undo();
+/// assert_eq!(*document.title.get(), "Draft");
+/// assert_eq!(document.cache, [1]);
+/// ```
+#[derive(Clone, Default)]
+pub struct CellRecorder {
+    history: Rc<RefCell<CellHistory>>,
+}
+
+impl CellRecorder {
+    /// Creates a recorder without any cell.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cell of the given value, recording its changes in this recorder.
+    #[must_use]
+    pub fn cell<T: 'static>(&self, value: T) -> UndoCell<T> {
+        UndoCell {
+            value: Rc::new(RefCell::new(value)),
+            recorder: self.clone(),
+        }
+    }
+
+    /// Calls `group_fn`, recording all the changes made to the cells during the call as a single step.
+    ///
+    /// If `group_fn` panics, the changes made during the call are undone and not recorded.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::cell::CellRecorder;
+    /// let recorder = CellRecorder::new();
+    /// let (mut x, mut y) = (recorder.cell(0), recorder.cell(0));
+    /// recorder.group(|| {
+    ///     x.set(10);
+    ///     y.set(20);
+    /// });
+    /// assert_eq!(recorder.history_len(), 1);
+    /// recorder.undo();
+    /// assert_eq!((*x.get(), *y.get()), (0, 0));
+    /// ```
+    pub fn group<TResult>(&self, group_fn: impl FnOnce() -> TResult) -> TResult {
+        let is_outermost = self.history.borrow().group.is_none();
+        let _guard = is_outermost.then(|| {
+            self.history.borrow_mut().group = Some(Vec::new());
+            GroupGuard(self)
+        });
+        group_fn()
+    }
+
+    /// Returns `true` if there is a step that can be undone with [`CellRecorder::undo`].
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.history.borrow().nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone step that can be redone with [`CellRecorder::redo`].
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        let history = self.history.borrow();
+        history.nb_updates < history.entries.len()
+    }
+
+    /// Returns the number of recorded steps, including the undone ones that can still be redone.
+    #[must_use]
+    pub fn history_len(&self) -> usize {
+        self.history.borrow().entries.len()
+    }
+
+    /// Returns the number of steps currently applied to the cells.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.history.borrow().nb_updates
+    }
+
+    /// Undo the last step, restoring the previous values of its cells.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    ///
+    /// # Panics
+    /// Panics if one of the restored cells is borrowed with [`UndoCell::get`].
+    #[allow(clippy::must_use_candidate)] // like `Undo::undo`, the result can be ignored
+    pub fn undo(&self) -> bool {
+        let mut history = self.history.borrow_mut();
+        if history.nb_updates == 0 {
+            return false;
+        }
+        history.nb_updates -= 1;
+        let position = history.nb_updates;
+        for change in history.entries[position].iter_mut().rev() {
+            change.swap();
+        }
+        true
+    }
+
+    /// Redo the last step that have been undone using [`CellRecorder::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    ///
+    /// # Panics
+    /// Panics if one of the restored cells is borrowed with [`UndoCell::get`].
+    #[allow(clippy::must_use_candidate)] // like `Undo::redo`, the result can be ignored
+    pub fn redo(&self) -> bool {
+        let mut history = self.history.borrow_mut();
+        let position = history.nb_updates;
+        let Some(changes) = history.entries.get_mut(position) else {
+            return false;
+        };
+        for change in changes {
+            change.swap();
+        }
+        history.nb_updates += 1;
+        true
+    }
+
+    /// Records a change made to a cell, in the current group if any.
+    fn record(&self, change: Box<dyn Change>) {
+        let mut history = self.history.borrow_mut();
+        if let Some(group) = &mut history.group {
+            group.push(change);
+        } else {
+            history.push(Vec::from([change]));
+        }
+    }
+}
+
+/// Records the changes of the outermost [`CellRecorder::group`] once dropped, undoing them if it panicked.
+struct GroupGuard<'recorder>(&'recorder CellRecorder);
+
+impl Drop for GroupGuard<'_> {
+    fn drop(&mut self) {
+        let mut history = self.0.history.borrow_mut();
+        let Some(mut changes) = history.group.take() else {
+            return;
+        };
+        if is_panicking() {
+            for change in changes.iter_mut().rev() {
+                change.swap();
+            }
+        } else if !changes.is_empty() {
+            history.push(changes);
+        }
+    }
+}
+
+impl CellHistory {
+    /// Records a new step, discarding the undone ones.
+    fn push(&mut self, changes: Vec<Box<dyn Change>>) {
+        self.entries.truncate(self.nb_updates);
+        self.entries.push(changes);
+        self.nb_updates += 1;
+    }
+}
+
+impl fmt::Debug for CellRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CellRecorder")
+            .field("history_len", &self.history_len())
+            .field("position", &self.position())
+            .finish()
+    }
+}
+
+impl AnyUndo for CellRecorder {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+/// A value whose changes are recorded in the [`CellRecorder`] that created it, with [`CellRecorder::cell`].
+pub struct UndoCell<T> {
+    value: Rc<RefCell<T>>,
+    recorder: CellRecorder,
+}
+
+impl<T: 'static> UndoCell<T> {
+    /// Returns the current value.
+    ///
+    /// # Panics
+    /// Panics if the value is being restored by [`CellRecorder::undo`] or [`CellRecorder::redo`].
+    #[must_use]
+    pub fn get(&self) -> Ref<'_, T> {
+        self.value.borrow()
+    }
+
+    /// Replaces the value, recording the change.
+    pub fn set(&mut self, value: T) {
+        let previous = self.value.replace(value);
+        self.record(previous);
+    }
+
+    /// Replaces the value like [`UndoCell::set`], returning the previous one.
+    pub fn replace(&mut self, value: T) -> T
+    where
+        T: Clone,
+    {
+        let previous = self.value.replace(value);
+        self.record(previous.clone());
+        previous
+    }
+
+    /// Updates the value in place, recording the change.
+    pub fn update(&mut self, update_fn: impl FnOnce(&mut T))
+    where
+        T: Clone,
+    {
+        let previous = self.value.borrow().clone();
+        update_fn(&mut self.value.borrow_mut());
+        self.record(previous);
+    }
+
+    /// Returns the recorder of the cell.
+    #[must_use]
+    pub const fn recorder(&self) -> &CellRecorder {
+        &self.recorder
+    }
+
+    /// Records the change from the previous value.
+    fn record(&self, previous: T) {
+        self.recorder.record(Box::new(CellChange {
+            cell: Rc::clone(&self.value),
+            value: previous,
+        }));
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UndoCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UndoCell")
+            .field(&*self.value.borrow())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    struct Scene {
+        name: UndoCell<String>,
+        positions: Vec<UndoCell<(i32, i32)>>,
+        frame: u64,
+    }
+
+    #[test]
+    fn it_undoes_the_cells_only() {
+        let recorder = CellRecorder::new();
+        let mut scene = Scene {
+            name: recorder.cell(String::from("scene")),
+            positions: vec![recorder.cell((0, 0)), recorder.cell((5, 5))],
+            frame: 0,
+        };
+        scene.name.update(|name| name.push_str(" 1"));
+        recorder.group(|| {
+            for position in &mut scene.positions {
+                position.update(|(x, _)| *x += 1);
+            }
+            scene.frame += 1;
+        });
+        assert_eq!(scene.positions[1].replace((9, 9)), (6, 5));
+        assert_eq!(recorder.history_len(), 3);
+
+        assert!(recorder.undo());
+        assert!(recorder.undo());
+        assert_eq!(*scene.positions[0].get(), (0, 0));
+        assert_eq!(*scene.positions[1].get(), (5, 5));
+        assert_eq!(scene.frame, 1);
+        assert!(recorder.undo());
+        assert!(!recorder.undo());
+        assert_eq!(*scene.name.get(), "scene");
+
+        assert!(recorder.redo());
+        assert!(scene.name.recorder().redo());
+        assert_eq!(*scene.name.get(), "scene 1");
+        assert_eq!(*scene.positions[1].get(), (6, 5));
+        scene.name.set(String::new());
+        assert!(!recorder.can_redo());
+        assert_eq!(recorder.position(), 3);
+    }
+
+    #[test]
+    fn it_undoes_a_panicking_group() {
+        let recorder = CellRecorder::new();
+        let mut x = recorder.cell(0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            recorder.group(|| {
+                x.set(1);
+                panic!("group failed");
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(*x.get(), 0);
+        assert_eq!(recorder.history_len(), 0);
+
+        x.set(2);
+        x.set(3);
+        assert_eq!(recorder.history_len(), 2);
+        assert!(recorder.undo());
+        assert_eq!(*x.get(), 2);
+    }
+}
@@ -0,0 +1,687 @@
+//! Undoable collections recording element-level operations, instead of updates of the whole collection.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use crate::AnyUndo;
+
+/// An operation recorded by an [`UndoVec`], along with what is needed to revert it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VecOp<T> {
+    /// Pushes the value at the end of the list.
+    Push(T),
+    /// Inserts the value at the index.
+    Insert(usize, T),
+    /// Removes the value at the index.
+    Remove(usize, T),
+    /// Swaps the values at both indices.
+    Swap(usize, usize),
+    /// Replaces the old value at the index with the new one.
+    Set {
+        /// The index of the replaced value.
+        index: usize,
+        /// The value before the operation.
+        old: T,
+        /// The value after the operation.
+        new: T,
+    },
+}
+
+impl<T: Clone> VecOp<T> {
+    /// Applies the operation to the list.
+    fn apply(&self, list: &mut Vec<T>) {
+        match self {
+            Self::Push(value) => list.push(value.clone()),
+            Self::Insert(index, value) => list.insert(*index, value.clone()),
+            Self::Remove(index, _) => {
+                list.remove(*index);
+            }
+            Self::Swap(a, b) => list.swap(*a, *b),
+            Self::Set { index, new, .. } => list[*index] = new.clone(),
+        }
+    }
+
+    /// Reverts the operation applied to the list.
+    fn revert(&self, list: &mut Vec<T>) {
+        match self {
+            Self::Push(_) => {
+                list.pop();
+            }
+            Self::Insert(index, _) => {
+                list.remove(*index);
+            }
+            Self::Remove(index, value) => list.insert(*index, value.clone()),
+            Self::Swap(a, b) => list.swap(*a, *b),
+            Self::Set { index, old, .. } => list[*index] = old.clone(),
+        }
+    }
+}
+
+/// A list like [`Undo<Vec<T>>`](crate::Undo) recording each operation as a [`VecOp`] instead of a closure.
+///
+/// Undoing or redoing an operation applies or reverts it, cloning at most one element,
+/// and the recorded operations form a log that is serializable with the `serde` feature.
+///
+/// # Example
+/// ```
+/// use simple_undo::collections::UndoVec;
+///
+/// let mut list = UndoVec::new(vec!['a', 'b']);
+/// list.push('c');
+/// list.swap(0, 2);
+/// assert_eq!(*list, ['c', 'b', 'a']);
+///
+/// list.undo();
+/// assert_eq!(*list, ['a', 'b', 'c']);
+/// list.undo();
+/// assert_eq!(*list, ['a', 'b']);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoVec<T> {
+    /// The current list to update.
+    current_list: Vec<T>,
+    /// All recorded operations, applied or not.
+    ops: Vec<VecOp<T>>,
+    /// Number of operations applied to the current list. Undoing reduces this number.
+    nb_updates: usize,
+}
+
+impl<T: Clone> UndoVec<T> {
+    /// Wraps the given list in an `UndoVec`, which will track all operations and allows undoing or redoing them.
+    #[must_use]
+    pub const fn new(list: Vec<T>) -> Self {
+        Self {
+            current_list: list,
+            ops: Vec::new(),
+            nb_updates: 0,
+        }
+    }
+
+    /// Unwraps the inner list to an owned value, disabling the undo/redo feature.
+    #[must_use]
+    pub fn unwrap(self) -> Vec<T> {
+        self.current_list
+    }
+
+    /// Returns `true` if there is an operation that can be undone with [`UndoVec::undo`].
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone operation that can be redone with [`UndoVec::redo`].
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.ops.len()
+    }
+
+    /// Returns the number of recorded operations, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns the number of operations currently applied to the list.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Appends a value to the end of the list, like [`Vec::push`].
+    ///
+    /// Note that future [`UndoVec::redo`] are reset, as with every operation.
+    pub fn push(&mut self, value: T) {
+        self.record(VecOp::Push(value));
+    }
+
+    /// Inserts a value at the given index, like [`Vec::insert`].
+    ///
+    /// # Panics
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.record(VecOp::Insert(index, value));
+    }
+
+    /// Removes and returns the value at the given index, like [`Vec::remove`].
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        let value = self.current_list[index].clone();
+        self.record(VecOp::Remove(index, value.clone()));
+        value
+    }
+
+    /// Swaps the values at both indices, like [`slice::swap`].
+    ///
+    /// # Panics
+    /// Panics if `a` or `b` are out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.record(VecOp::Swap(a, b));
+    }
+
+    /// Replaces the value at the given index, returning the previous one.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: T) -> T {
+        let old = self.current_list[index].clone();
+        self.record(VecOp::Set {
+            index,
+            old: old.clone(),
+            new: value,
+        });
+        old
+    }
+
+    /// Returns the recorded operations, from the oldest to the most recent, including the undone ones.
+    pub fn ops(&self) -> impl Iterator<Item = &VecOp<T>> {
+        self.ops.iter()
+    }
+
+    /// Undo the last operation by reverting it.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.nb_updates -= 1;
+        self.ops[self.nb_updates].revert(&mut self.current_list);
+        true
+    }
+
+    /// Redo the last operation that have been undone using [`UndoVec::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.nb_updates == self.ops.len() {
+            return false;
+        }
+        self.ops[self.nb_updates].apply(&mut self.current_list);
+        self.nb_updates += 1;
+        true
+    }
+
+    /// Applies the given operation and records it in the history.
+    fn record(&mut self, op: VecOp<T>) {
+        op.apply(&mut self.current_list);
+        // Discard previous operations when updating after an undo.
+        self.ops.truncate(self.nb_updates);
+        self.ops.push(op);
+        self.nb_updates += 1;
+    }
+}
+
+impl<T> Deref for UndoVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_list
+    }
+}
+
+impl<T: Clone> AnyUndo for UndoVec<T> {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+/// An operation recorded by an [`UndoMap`], along with the displaced value needed to revert it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapOp<K, V> {
+    /// Inserts the new value for the key, replacing the old one if any.
+    Insert {
+        /// The key of the inserted value.
+        key: K,
+        /// The value before the operation, if the key was present.
+        old: Option<V>,
+        /// The value after the operation.
+        new: V,
+    },
+    /// Removes the old value of the key.
+    Remove {
+        /// The key of the removed value.
+        key: K,
+        /// The value before the operation.
+        old: V,
+    },
+}
+
+impl<K: Ord + Clone, V: Clone> MapOp<K, V> {
+    /// Applies the operation to the map.
+    fn apply(&self, map: &mut BTreeMap<K, V>) {
+        match self {
+            Self::Insert { key, new, .. } => {
+                map.insert(key.clone(), new.clone());
+            }
+            Self::Remove { key, .. } => {
+                map.remove(key);
+            }
+        }
+    }
+
+    /// Reverts the operation applied to the map.
+    fn revert(&self, map: &mut BTreeMap<K, V>) {
+        match self {
+            Self::Insert { key, old: None, .. } => {
+                map.remove(key);
+            }
+            Self::Insert {
+                key,
+                old: Some(old),
+                ..
+            }
+            | Self::Remove { key, old } => {
+                map.insert(key.clone(), old.clone());
+            }
+        }
+    }
+}
+
+/// A map like [`Undo<BTreeMap<K, V>>`](crate::Undo) recording each operation as a [`MapOp`] instead of a closure.
+///
+/// Each operation records the value it displaced, so that undoing or redoing it only touches one key,
+/// and the recorded operations form a log that is serializable with the `serde` feature.
+///
+/// # Example
+/// ```
+/// use simple_undo::collections::UndoMap;
+///
+/// let mut settings = UndoMap::new();
+/// settings.insert("theme", "light");
+/// settings.insert("theme", "dark");
+/// settings.update(&"theme", |theme| *theme = "solarized");
+/// assert_eq!(settings["theme"], "solarized");
+///
+/// settings.undo();
+/// assert_eq!(settings["theme"], "dark");
+/// settings.undo();
+/// settings.undo();
+/// assert!(settings.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoMap<K, V> {
+    /// The current map to update.
+    current_map: BTreeMap<K, V>,
+    /// All recorded operations, applied or not.
+    ops: Vec<MapOp<K, V>>,
+    /// Number of operations applied to the current map. Undoing reduces this number.
+    nb_updates: usize,
+}
+
+impl<K: Ord + Clone, V: Clone> UndoMap<K, V> {
+    /// Creates an empty `UndoMap`, which will track all operations and allows undoing or redoing them.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::from_map(BTreeMap::new())
+    }
+
+    /// Wraps the given map in an `UndoMap`, like [`UndoMap::new`].
+    #[must_use]
+    pub const fn from_map(map: BTreeMap<K, V>) -> Self {
+        Self {
+            current_map: map,
+            ops: Vec::new(),
+            nb_updates: 0,
+        }
+    }
+
+    /// Unwraps the inner map to an owned value, disabling the undo/redo feature.
+    #[must_use]
+    pub fn unwrap(self) -> BTreeMap<K, V> {
+        self.current_map
+    }
+
+    /// Returns `true` if there is an operation that can be undone with [`UndoMap::undo`].
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone operation that can be redone with [`UndoMap::redo`].
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.ops.len()
+    }
+
+    /// Returns the number of recorded operations, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns the number of operations currently applied to the map.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Inserts a value for the key, returning the previous one, like [`BTreeMap::insert`].
+    ///
+    /// Note that future [`UndoMap::redo`] are reset, as with every operation.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.current_map.get(&key).cloned();
+        self.record(MapOp::Insert {
+            key,
+            old: old.clone(),
+            new: value,
+        });
+        old
+    }
+
+    /// Removes the value of the key and returns it, like [`BTreeMap::remove`].
+    ///
+    /// Nothing is recorded if the key was absent.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.current_map.get(key)?.clone();
+        self.record(MapOp::Remove {
+            key: key.clone(),
+            old: old.clone(),
+        });
+        Some(old)
+    }
+
+    /// Updates the value of the key in place with `update_fn`, recording it as an insertion of the new value.
+    ///
+    /// Returns `false`, recording nothing, if the key was absent.
+    pub fn update(&mut self, key: &K, update_fn: impl FnOnce(&mut V)) -> bool {
+        let Some(old) = self.current_map.get(key) else {
+            return false;
+        };
+        let mut new = old.clone();
+        update_fn(&mut new);
+        self.insert(key.clone(), new);
+        true
+    }
+
+    /// Returns the recorded operations, from the oldest to the most recent, including the undone ones.
+    pub fn ops(&self) -> impl Iterator<Item = &MapOp<K, V>> {
+        self.ops.iter()
+    }
+
+    /// Undo the last operation by reverting it.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.nb_updates -= 1;
+        self.ops[self.nb_updates].revert(&mut self.current_map);
+        true
+    }
+
+    /// Redo the last operation that have been undone using [`UndoMap::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.nb_updates == self.ops.len() {
+            return false;
+        }
+        self.ops[self.nb_updates].apply(&mut self.current_map);
+        self.nb_updates += 1;
+        true
+    }
+
+    /// Applies the given operation and records it in the history.
+    fn record(&mut self, op: MapOp<K, V>) {
+        op.apply(&mut self.current_map);
+        // Discard previous operations when updating after an undo.
+        self.ops.truncate(self.nb_updates);
+        self.ops.push(op);
+        self.nb_updates += 1;
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for UndoMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Deref for UndoMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_map
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> AnyUndo for UndoMap<K, V> {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+/// Serialization of the current collection and all recorded operations, along with the history position.
+#[cfg(feature = "serde")]
+mod serialization {
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{MapOp, UndoMap, UndoVec, VecOp};
+
+    /// The serialized form of an [`UndoVec`].
+    #[derive(Serialize, Deserialize)]
+    struct SerializedUndoVec<TList, TOps> {
+        current_list: TList,
+        ops: TOps,
+        position: usize,
+    }
+
+    /// The serialized form of an [`UndoMap`].
+    #[derive(Serialize, Deserialize)]
+    struct SerializedUndoMap<TMap, TOps> {
+        current_map: TMap,
+        ops: TOps,
+        position: usize,
+    }
+
+    /// Returns an error if the position is after the recorded operations.
+    fn check_position<E: Error>(position: usize, history_len: usize) -> Result<(), E> {
+        if position > history_len {
+            return Err(E::custom(format_args!(
+                "position {position} is out of range for a history of {history_len} operations"
+            )));
+        }
+        Ok(())
+    }
+
+    impl<T: Serialize> Serialize for UndoVec<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SerializedUndoVec {
+                current_list: &self.current_list,
+                ops: &self.ops,
+                position: self.nb_updates,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for UndoVec<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let serialized = SerializedUndoVec::<Vec<T>, Vec<VecOp<T>>>::deserialize(deserializer)?;
+            check_position(serialized.position, serialized.ops.len())?;
+            Ok(Self {
+                current_list: serialized.current_list,
+                ops: serialized.ops,
+                nb_updates: serialized.position,
+            })
+        }
+    }
+
+    impl<K: Serialize, V: Serialize> Serialize for UndoMap<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SerializedUndoMap {
+                current_map: &self.current_map,
+                ops: &self.ops,
+                position: self.nb_updates,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for UndoMap<K, V>
+    where
+        K: Ord + Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let serialized =
+                SerializedUndoMap::<BTreeMap<K, V>, Vec<MapOp<K, V>>>::deserialize(deserializer)?;
+            check_position(serialized.position, serialized.ops.len())?;
+            Ok(Self {
+                current_map: serialized.current_map,
+                ops: serialized.ops,
+                nb_updates: serialized.position,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_undoes_and_redoes_each_operation() {
+        let mut list = UndoVec::new(vec![1, 2, 3]);
+        list.push(4);
+        list.insert(0, 0);
+        assert_eq!(list.remove(2), 2);
+        list.swap(0, 3);
+        assert_eq!(list.set(1, 10), 1);
+        assert_eq!(*list, [4, 10, 3, 0]);
+        assert_eq!(list.history_len(), 5);
+
+        let mut states = Vec::new();
+        while list.undo() {
+            states.push(list.clone().unwrap());
+        }
+        assert_eq!(
+            states,
+            [
+                vec![4, 1, 3, 0],
+                vec![0, 1, 3, 4],
+                vec![0, 1, 2, 3, 4],
+                vec![1, 2, 3, 4],
+                vec![1, 2, 3],
+            ]
+        );
+        while list.redo() {}
+        assert_eq!(*list, [4, 10, 3, 0]);
+
+        list.undo();
+        list.push(5);
+        assert!(!list.can_redo());
+        assert_eq!(list.ops().last(), Some(&VecOp::Push(5)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_the_operations() {
+        use alloc::string::String;
+
+        let mut list = UndoVec::new(Vec::new());
+        list.push(String::from("a"));
+        list.push(String::from("b"));
+        list.undo();
+
+        let json = serde_json::to_string(&list).unwrap();
+        let mut list: UndoVec<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(*list, ["a"]);
+        assert!(list.redo());
+        assert_eq!(*list, ["a", "b"]);
+        while list.undo() {}
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn it_records_the_displaced_values() {
+        let mut scores = UndoMap::new();
+        assert_eq!(scores.insert('a', 1), None);
+        assert_eq!(scores.insert('a', 2), Some(1));
+        assert_eq!(scores.insert('b', 3), None);
+        assert_eq!(scores.remove(&'b'), Some(3));
+        assert_eq!(scores.remove(&'b'), None);
+        assert!(scores.update(&'a', |score| *score *= 10));
+        assert!(!scores.update(&'c', |score| *score *= 10));
+        assert_eq!(scores.history_len(), 5);
+        assert_eq!(
+            scores.ops().nth(1),
+            Some(&MapOp::Insert {
+                key: 'a',
+                old: Some(1),
+                new: 2
+            })
+        );
+
+        let mut states = Vec::new();
+        while scores.undo() {
+            states.push(scores.clone().unwrap().into_iter().collect::<Vec<_>>());
+        }
+        assert_eq!(
+            states,
+            [
+                vec![('a', 2)],
+                vec![('a', 2), ('b', 3)],
+                vec![('a', 2)],
+                vec![('a', 1)],
+                vec![],
+            ]
+        );
+        while scores.redo() {}
+        assert_eq!(scores[&'a'], 20);
+        assert_eq!(scores.len(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_the_map_operations() {
+        let mut scores = UndoMap::new();
+        scores.insert(String::from("a"), 1);
+        scores.remove(&String::from("a"));
+        scores.undo();
+
+        let json = serde_json::to_string(&scores).unwrap();
+        let mut scores: UndoMap<String, i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(scores["a"], 1);
+        assert!(scores.redo());
+        assert!(scores.is_empty());
+        assert!(serde_json::from_str::<UndoMap<String, i32>>(
+            &json.replace("\"position\":1", "\"position\":3")
+        )
+        .is_err());
+    }
+}
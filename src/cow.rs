@@ -0,0 +1,131 @@
+//! A copy-on-write state, shared with the snapshots of its [`Undo`](crate::Undo) until it's modified.
+
+use alloc::rc::Rc;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use crate::Snapshot;
+
+/// A state shared with its snapshots, only cloned when it's modified while a snapshot still refers to it.
+///
+/// Wrapping a state in an [`Undo`](crate::Undo) then doesn't clone it, and undoing to a checkpoint
+/// only restores a pointer to it. The state is cloned once by the first update applied after a snapshot,
+/// through [`DerefMut`].
+///
+/// # Example
+/// ```
+/// use simple_undo::cow::CowState;
+/// use simple_undo::Undo;
+///
+/// let mut list = Undo::new(CowState::new(vec![1, 2, 3])); // not cloned
+/// list.update(|list| list.push(4)); // cloned before being modified
+/// list.undo(); // restores the shared initial state
+///
+/// assert_eq!(list.as_slice(), [1, 2, 3]);
+/// ```
+pub struct CowState<T>(Rc<T>);
+
+impl<T> CowState<T> {
+    /// Wraps the state.
+    pub fn new(state: T) -> Self {
+        Self(Rc::new(state))
+    }
+
+    /// Unwraps the inner state, cloning it if it's shared with a snapshot.
+    #[must_use]
+    pub fn into_inner(self) -> T
+    where
+        T: Clone,
+    {
+        Rc::unwrap_or_clone(self.0)
+    }
+}
+
+impl<T> Deref for CowState<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Clone> DerefMut for CowState<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Rc::make_mut(&mut self.0)
+    }
+}
+
+impl<T> Snapshot for CowState<T> {
+    type Snapshot = Rc<T>;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        Rc::clone(&self.0)
+    }
+
+    fn restore(&mut self, snapshot: &Self::Snapshot) {
+        if !Rc::ptr_eq(&self.0, snapshot) {
+            self.0 = Rc::clone(snapshot);
+        }
+    }
+
+    fn from_snapshot(snapshot: Self::Snapshot) -> Self {
+        Self(snapshot)
+    }
+
+    fn into_snapshot(self) -> Self::Snapshot {
+        self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CowState<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CowState").field(&*self.0).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Undo;
+    use core::cell::Cell;
+    use core::num::NonZeroUsize;
+
+    /// A state counting its clones.
+    struct Tracked<'a> {
+        value: u32,
+        nb_clones: &'a Cell<u32>,
+    }
+
+    impl Clone for Tracked<'_> {
+        fn clone(&self) -> Self {
+            self.nb_clones.set(self.nb_clones.get() + 1);
+            Self {
+                value: self.value,
+                nb_clones: self.nb_clones,
+            }
+        }
+    }
+
+    #[test]
+    fn it_only_clones_the_modified_states() {
+        let nb_clones = Cell::new(0);
+        let mut counter = Undo::new(CowState::new(Tracked {
+            value: 0,
+            nb_clones: &nb_clones,
+        }));
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        assert_eq!(nb_clones.get(), 0);
+        for _ in 0..4 {
+            counter.update(|counter| counter.value += 1);
+        }
+        assert_eq!(nb_clones.get(), 2); // after the initial state and the checkpoint
+
+        counter.undo_n(2);
+        assert_eq!(counter.value, 2);
+        assert_eq!(nb_clones.get(), 2);
+        counter.undo();
+        assert_eq!(counter.value, 1);
+        assert_eq!(nb_clones.get(), 3);
+        assert_eq!(counter.unwrap().into_inner().value, 1);
+    }
+}
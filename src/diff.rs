@@ -0,0 +1,404 @@
+//! History storing the differences between consecutive states, instead of the updates producing them.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use crate::AnyUndo;
+
+/// A structural difference between two states, as recorded by an [`UndoDiff`].
+///
+/// # Example
+/// ```
+/// use simple_undo::diff::Diff;
+///
+/// struct Grid {
+///     cells: Vec<u8>,
+/// }
+///
+/// impl Diff for Grid {
+///     /// The changed cells, with their index, old and new values.
+///     type Patch = Vec<(usize, u8, u8)>;
+///
+///     fn diff(&self, other: &Self) -> Self::Patch {
+///         let cells = self.cells.iter().zip(&other.cells).enumerate();
+///         cells.filter(|(_, (old, new))| old != new).map(|(i, (old, new))| (i, *old, *new)).collect()
+///     }
+///
+///     fn apply_patch(&mut self, patch: &Self::Patch) {
+///         for (i, _, new) in patch {
+///             self.cells[*i] = *new;
+///         }
+///     }
+///
+///     fn revert_patch(&mut self, patch: &Self::Patch) {
+///         for (i, old, _) in patch {
+///             self.cells[*i] = *old;
+///         }
+///     }
+/// }
+///
+/// let grid = Grid { cells: vec![0, 0, 0] };
+/// assert_eq!(grid.diff(&Grid { cells: vec![0, 7, 0] }), [(1, 0, 7)]);
+/// ```
+pub trait Diff {
+    /// The difference between two states.
+    type Patch;
+
+    /// Returns the patch turning this state into `other`.
+    fn diff(&self, other: &Self) -> Self::Patch;
+
+    /// Applies a patch returned by [`Diff::diff`], turning the old state into the new one.
+    fn apply_patch(&mut self, patch: &Self::Patch);
+
+    /// Reverts a patch returned by [`Diff::diff`], turning the new state back into the old one.
+    fn revert_patch(&mut self, patch: &Self::Patch);
+}
+
+/// A patch recorded with its optional label.
+struct DiffEntry<TPatch> {
+    patch: TPatch,
+    label: Option<String>,
+}
+
+/// A wrapper like [`Undo`](crate::Undo) recording the [`Diff`] of each update instead of the update itself.
+///
+/// Undoing or redoing an update only applies its patch, in `O(size of the change)`, and neither regenerates
+/// nor clones the state. The state is only cloned while updating, to compute the patch.
+/// With the `serde` feature, the whole history is serializable as soon as the state and its patches are.
+///
+/// # Example
+/// ```
+/// # use simple_undo::diff::{Diff, UndoDiff};
+/// # #[derive(Clone)]
+/// # struct Grid {
+/// #     cells: Vec<u8>,
+/// # }
+/// # impl Diff for Grid {
+/// #     type Patch = Vec<(usize, u8, u8)>;
+/// #     fn diff(&self, other: &Self) -> Self::Patch {
+/// #         let cells = self.cells.iter().zip(&other.cells).enumerate();
+/// #         cells.filter(|(_, (old, new))| old != new).map(|(i, (old, new))| (i, *old, *new)).collect()
+/// #     }
+/// #     fn apply_patch(&mut self, patch: &Self::Patch) {
+/// #         for (i, _, new) in patch { self.cells[*i] = *new; }
+/// #     }
+/// #     fn revert_patch(&mut self, patch: &Self::Patch) {
+/// #         for (i, old, _) in patch { self.cells[*i] = *old; }
+/// #     }
+/// # }
+/// let mut grid = UndoDiff::new(Grid { cells: vec![0; 1000] });
+/// grid.update(|grid| grid.cells[42] = 1); // records a single changed cell
+/// grid.update(|grid| grid.cells[7] = 2);
+///
+/// grid.undo();
+/// assert_eq!((grid.cells[42], grid.cells[7]), (1, 0));
+/// grid.redo();
+/// assert_eq!(grid.cells[7], 2);
+/// ```
+pub struct UndoDiff<TState: Diff> {
+    /// The current state to update.
+    current_state: TState,
+    /// All recorded patches, applied or not.
+    entries: Vec<DiffEntry<TState::Patch>>,
+    /// Number of patches applied to the current state. Undoing reduces this number.
+    nb_updates: usize,
+}
+
+impl<TState: Diff> UndoDiff<TState> {
+    /// Wraps the given state in an `UndoDiff`, which will track all updates and allows undoing or redoing them.
+    pub const fn new(state: TState) -> Self {
+        Self {
+            current_state: state,
+            entries: Vec::new(),
+            nb_updates: 0,
+        }
+    }
+
+    /// Unwraps the inner state to an owned value, disabling the undo/redo feature.
+    pub fn unwrap(self) -> TState {
+        self.current_state
+    }
+
+    /// Returns `true` if there is an update that can be undone with [`UndoDiff::undo`].
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone update that can be redone with [`UndoDiff::redo`].
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.entries.len()
+    }
+
+    /// Returns the number of recorded updates, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of updates currently applied to the state.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Updates the current state with the given mutating function, recording the patch between
+    /// the state before and after the update.
+    ///
+    /// Note that future [`UndoDiff::redo`] are reset.
+    pub fn update(&mut self, update_fn: impl FnOnce(&mut TState))
+    where
+        TState: Clone,
+    {
+        self.record(None, update_fn);
+    }
+
+    /// Updates the current state like [`UndoDiff::update`], attaching a label to the recorded patch.
+    pub fn update_labeled(&mut self, label: impl Into<String>, update_fn: impl FnOnce(&mut TState))
+    where
+        TState: Clone,
+    {
+        self.record(Some(label.into()), update_fn);
+    }
+
+    /// Returns the recorded patches with their label, from the oldest to the most recent, including the undone ones.
+    pub fn patches(&self) -> impl Iterator<Item = (&TState::Patch, Option<&str>)> {
+        self.entries
+            .iter()
+            .map(|entry| (&entry.patch, entry.label.as_deref()))
+    }
+
+    /// Undo the last update by reverting its patch.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.nb_updates -= 1;
+        self.current_state
+            .revert_patch(&self.entries[self.nb_updates].patch);
+        true
+    }
+
+    /// Redo the last update that have been undone using [`UndoDiff::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.nb_updates == self.entries.len() {
+            return false;
+        }
+        self.current_state
+            .apply_patch(&self.entries[self.nb_updates].patch);
+        self.nb_updates += 1;
+        true
+    }
+
+    /// Applies the given update and records its patch in the history.
+    fn record(&mut self, label: Option<String>, update_fn: impl FnOnce(&mut TState))
+    where
+        TState: Clone,
+    {
+        let previous_state = self.current_state.clone();
+        update_fn(&mut self.current_state);
+        let patch = previous_state.diff(&self.current_state);
+
+        // Discard previous updates when updating after an undo.
+        self.entries.truncate(self.nb_updates);
+        self.entries.push(DiffEntry { patch, label });
+        self.nb_updates += 1;
+    }
+}
+
+impl<TState: Diff> Deref for UndoDiff<TState> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_state
+    }
+}
+
+impl<TState: Diff> AnyUndo for UndoDiff<TState> {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+/// Serialization of the current state and all recorded patches, along with the history position.
+#[cfg(feature = "serde")]
+mod serialization {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Diff, DiffEntry, UndoDiff};
+
+    /// The serialized form of a recorded patch.
+    #[derive(Serialize, Deserialize)]
+    struct SerializedEntry<TPatch> {
+        patch: TPatch,
+        label: Option<String>,
+    }
+
+    /// The serialized form of an [`UndoDiff`].
+    #[derive(Serialize, Deserialize)]
+    struct SerializedUndoDiff<TState, TPatch> {
+        current_state: TState,
+        entries: Vec<SerializedEntry<TPatch>>,
+        position: usize,
+    }
+
+    impl<TState> Serialize for UndoDiff<TState>
+    where
+        TState: Diff + Serialize,
+        TState::Patch: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let entries = self
+                .entries
+                .iter()
+                .map(|entry| SerializedEntry {
+                    patch: &entry.patch,
+                    label: entry.label.clone(),
+                })
+                .collect();
+            SerializedUndoDiff {
+                current_state: &self.current_state,
+                entries,
+                position: self.nb_updates,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, TState> Deserialize<'de> for UndoDiff<TState>
+    where
+        TState: Diff + Deserialize<'de>,
+        TState::Patch: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let serialized =
+                SerializedUndoDiff::<TState, TState::Patch>::deserialize(deserializer)?;
+            let history_len = serialized.entries.len();
+            if serialized.position > history_len {
+                return Err(D::Error::custom(format_args!(
+                    "position {} is out of range for a history of {history_len} updates",
+                    serialized.position
+                )));
+            }
+            Ok(Self {
+                current_state: serialized.current_state,
+                entries: serialized
+                    .entries
+                    .into_iter()
+                    .map(|SerializedEntry { patch, label }| DiffEntry { patch, label })
+                    .collect(),
+                nb_updates: serialized.position,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A text patched by replacing its suffix after the common prefix.
+    #[derive(Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Text(String);
+
+    impl Diff for Text {
+        /// The length of the common prefix, then the old and new suffixes.
+        type Patch = (usize, String, String);
+
+        fn diff(&self, other: &Self) -> Self::Patch {
+            let prefix = self
+                .0
+                .char_indices()
+                .zip(other.0.chars())
+                .find(|((_, old), new)| old != new)
+                .map_or_else(|| self.0.len().min(other.0.len()), |((i, _), _)| i);
+            (
+                prefix,
+                self.0[prefix..].to_string(),
+                other.0[prefix..].to_string(),
+            )
+        }
+
+        fn apply_patch(&mut self, (prefix, _, new): &Self::Patch) {
+            self.0.replace_range(prefix.., new);
+        }
+
+        fn revert_patch(&mut self, (prefix, old, _): &Self::Patch) {
+            self.0.replace_range(prefix.., old);
+        }
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_patches() {
+        let mut text = UndoDiff::new(Text(String::from("Hello")));
+        text.update(|text| text.0.push_str(" world"));
+        text.update_labeled("Shout", |text| text.0 = text.0.replace("world", "WORLD!"));
+        assert_eq!(
+            text.patches().collect::<Vec<_>>(),
+            [
+                (&(5, String::new(), String::from(" world")), None),
+                (
+                    &(6, String::from("world"), String::from("WORLD!")),
+                    Some("Shout")
+                ),
+            ]
+        );
+
+        assert!(text.undo());
+        assert_eq!(text.0, "Hello world");
+        assert!(text.undo());
+        assert!(!text.undo());
+        assert_eq!(text.0, "Hello");
+        assert!(text.redo());
+        assert_eq!(text.0, "Hello world");
+
+        text.update(|text| text.0.truncate(1));
+        assert!(!text.can_redo());
+        assert_eq!((text.position(), text.history_len()), (2, 2));
+        assert!(text.undo());
+        assert_eq!(text.unwrap().0, "Hello world");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_the_patches() {
+        let mut text = UndoDiff::new(Text(String::from("a")));
+        text.update(|text| text.0.push('b'));
+        text.update(|text| text.0.push('c'));
+        text.undo();
+
+        let json = serde_json::to_string(&text).unwrap();
+        let mut text: UndoDiff<Text> = serde_json::from_str(&json).unwrap();
+        assert_eq!(text.0, "ab");
+        assert!(text.redo());
+        assert_eq!(text.0, "abc");
+        while text.undo() {}
+        assert_eq!(text.0, "a");
+
+        let json = r#"{"current_state":"a","entries":[],"position":1}"#;
+        assert!(serde_json::from_str::<UndoDiff<Text>>(json).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Synchronization of an [`Undo`] with a `dioxus` signal, enabled by the `dioxus` feature.
+
+use dioxus_signals::{Writable, WritableExt};
+
+use crate::{Command, Observer, Snapshot, Undo};
+
+/// Sets the signal to the current state, then to the new state after each update, undo or redo of the `Undo`,
+/// re-rendering the components reading it.
+///
+/// # Example
+/// ```no_run
+/// use dioxus_signals::Signal;
+/// use simple_undo::{dioxus::sync_signal, Undo};
+///
+/// // In a component, with the signal created by `use_signal`.
+/// # let value = Signal::new(0);
+/// let mut counter = Undo::new(0);
+/// sync_signal(&mut counter, value);
+/// counter.update(|value| *value += 1);
+/// ```
+pub fn sync_signal<'state, TState, TCommand, TMeta>(
+    undo: &mut Undo<'state, TState, TCommand, Observer<'state, TState>, TMeta>,
+    mut signal: impl Writable<Target = TState> + 'state,
+) where
+    TState: Snapshot + Clone + 'static,
+    TCommand: Command<TState>,
+{
+    signal.set(undo.get().clone());
+    undo.on_change(move |state, _| signal.set(state.clone()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dioxus_core::{ScopeId, VNode, VirtualDom};
+    use dioxus_signals::{ReadableExt, Signal};
+
+    #[test]
+    fn it_sets_the_signal_on_each_change() {
+        let dom = VirtualDom::new(VNode::empty);
+        dom.in_scope(ScopeId::ROOT, || {
+            let items = Signal::new(Vec::new());
+            let mut list = Undo::new(vec![1]);
+            sync_signal(&mut list, items);
+            assert_eq!(items(), [1]);
+
+            list.update(|list| list.push(2));
+            list.update(|list| list.push(3));
+            list.undo_n(2);
+            assert_eq!(items(), [1]);
+            list.redo();
+            assert_eq!(*items.read(), [1, 2]);
+        });
+    }
+}
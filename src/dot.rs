@@ -0,0 +1,204 @@
+//! Export of the history as a [Graphviz](https://graphviz.org) graph in the DOT language, to debug it.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
+
+use crate::tree::UndoTree;
+use crate::{ChangeKind, Command, Snapshot, Undo};
+
+/// The attributes of the node of the current state.
+const CURRENT_STYLE: &str = "style=filled, fillcolor=lightblue";
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Renders the history as a Graphviz graph in the DOT language.
+    ///
+    /// Each position is a node and each update an edge labeled with its label, the undone updates being dashed.
+    /// The current position is filled, and the positions with a checkpoint are circled twice.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update_labeled("Type \"Hi\"", |text| text.push_str("Hi"));
+    /// text.update(|text| text.push('!'));
+    /// text.undo();
+    ///
+    /// assert_eq!(text.to_dot(), r#"digraph history {
+    ///     rankdir=LR;
+    ///     node [shape=circle];
+    ///     0 [peripheries=2];
+    ///     1 [style=filled, fillcolor=lightblue];
+    ///     2;
+    ///     0 -> 1 [label="Type \"Hi\""];
+    ///     1 -> 2 [style=dashed];
+    /// }
+    /// "#);
+    /// ```
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph history {\n    rankdir=LR;\n    node [shape=circle];\n");
+        let mut checkpoints = self
+            .checkpoints
+            .iter()
+            .map(|(position, _)| *position)
+            .peekable();
+        for position in 0..=self.entries.len() {
+            let mut attributes = vec![];
+            if checkpoints.next_if_eq(&position).is_some() {
+                attributes.push("peripheries=2");
+            }
+            if position == self.nb_updates {
+                attributes.push(CURRENT_STYLE);
+            }
+            write_node(&mut dot, position, &attributes.join(", "));
+        }
+        for (index, entry) in self.entries.iter().enumerate() {
+            let style = if index < self.nb_updates {
+                ""
+            } else {
+                "style=dashed"
+            };
+            write_edge(&mut dot, index, index + 1, entry.label.as_deref(), style);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<TState: Snapshot> UndoTree<'_, TState> {
+    /// Renders the tree as a Graphviz graph in the DOT language, like [`Undo::to_dot`].
+    ///
+    /// Each node is numbered in depth-first order, and each edge is labeled with the label of its update.
+    /// The current node is filled.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.update_labeled("Add 1", |value| *value += 1);
+    /// counter.undo();
+    /// counter.update_labeled("Add 2", |value| *value += 2);
+    ///
+    /// assert!(counter.to_dot().contains("0 -> 2 [label=\"Add 2\"];"));
+    /// ```
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph history {\n    node [shape=circle];\n");
+        let mut edges = String::new();
+        let mut number = 0;
+        let mut stack = vec![(self.root(), None)];
+        while let Some((node, parent)) = stack.pop() {
+            let attributes = if node == self.current() {
+                CURRENT_STYLE
+            } else {
+                ""
+            };
+            write_node(&mut dot, number, attributes);
+            if let Some(parent) = parent {
+                write_edge(&mut edges, parent, number, self.label(node), "");
+            }
+            // Pushed in reverse to visit the oldest branch first.
+            for child in self.children(node).iter().rev() {
+                stack.push((*child, Some(number)));
+            }
+            number += 1;
+        }
+        dot.push_str(&edges);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Writes a node with the given attributes, if any.
+fn write_node(dot: &mut String, number: usize, attributes: &str) {
+    if attributes.is_empty() {
+        let _ = writeln!(dot, "    {number};");
+    } else {
+        let _ = writeln!(dot, "    {number} [{attributes}];");
+    }
+}
+
+/// Writes an edge with its label, escaped as a DOT string, and the given style, if any.
+fn write_edge(dot: &mut String, from: usize, to: usize, label: Option<&str>, style: &str) {
+    let _ = write!(dot, "    {from} -> {to}");
+    let mut attributes = vec![];
+    let label = label.map(|label| {
+        let escaped = label.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("label=\"{escaped}\"")
+    });
+    attributes.extend(label.as_deref());
+    if !style.is_empty() {
+        attributes.push(style);
+    }
+    if !attributes.is_empty() {
+        let _ = write!(dot, " [{}]", attributes.join(", "));
+    }
+    dot.push_str(";\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::num::NonZeroUsize;
+
+    #[test]
+    fn it_renders_the_history_as_dot() {
+        let mut list = Undo::new(Vec::new());
+        list.set_checkpoint_interval(NonZeroUsize::new(2));
+        for value in 0..3 {
+            list.update_labeled(format!("Push {value}"), move |list| list.push(value));
+        }
+        list.undo_n(2);
+        assert_eq!(
+            list.to_dot(),
+            "digraph history {
+    rankdir=LR;
+    node [shape=circle];
+    0 [peripheries=2];
+    1 [style=filled, fillcolor=lightblue];
+    2 [peripheries=2];
+    3;
+    0 -> 1 [label=\"Push 0\"];
+    1 -> 2 [label=\"Push 1\", style=dashed];
+    2 -> 3 [label=\"Push 2\", style=dashed];
+}
+"
+        );
+    }
+
+    #[test]
+    fn it_renders_the_tree_as_dot() {
+        let mut text = UndoTree::new(String::new());
+        text.update(|text| text.push('a'));
+        text.update_labeled("b", |text| text.push('b'));
+        text.undo();
+        text.update_labeled("c", |text| text.push('c'));
+        text.undo();
+        text.undo();
+        text.update_labeled("d", |text| text.push('d'));
+        assert_eq!(
+            text.to_dot(),
+            "digraph history {
+    node [shape=circle];
+    0;
+    1;
+    2;
+    3;
+    4 [style=filled, fillcolor=lightblue];
+    0 -> 1;
+    1 -> 2 [label=\"b\"];
+    1 -> 3 [label=\"c\"];
+    0 -> 4 [label=\"d\"];
+}
+"
+        );
+    }
+}
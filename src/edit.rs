@@ -0,0 +1,165 @@
+//! Bridge with the `undo` crate, enabled by the `undo` feature, to record its edits in an [`Undo`](crate::Undo)
+//! and the commands of an `Undo` in its histories.
+
+use core::cell::RefCell;
+
+use crate::{Command, Snapshot};
+
+/// An [`undo::Edit`] recorded as a [`Command`] of an [`Undo`](crate::Undo).
+///
+/// The edit is applied with [`Edit::edit`](undo::Edit::edit) each time the state is regenerated,
+/// so it must always produce the same update. Its own `undo` is never called: the `Undo` restores the
+/// checkpoints of the state instead.
+///
+/// # Example
+/// ```
+/// use simple_undo::edit::EditCommand;
+/// use simple_undo::Undo;
+///
+/// struct Push(char);
+///
+/// impl undo::Edit for Push {
+///     type Target = String;
+///     type Output = ();
+///
+///     fn edit(&mut self, text: &mut String) {
+///         text.push(self.0);
+///     }
+///
+///     fn undo(&mut self, text: &mut String) {
+///         text.pop();
+///     }
+/// }
+///
+/// let mut text = Undo::with_commands(String::new());
+/// text.apply(EditCommand::new(Push('a')));
+/// text.apply(Push('b').into());
+/// text.undo();
+/// assert_eq!(*text, "a");
+/// ```
+#[derive(Debug, Clone)]
+pub struct EditCommand<TEdit>(RefCell<TEdit>);
+
+impl<TEdit> EditCommand<TEdit> {
+    /// Wraps the edit in a command.
+    pub const fn new(edit: TEdit) -> Self {
+        Self(RefCell::new(edit))
+    }
+
+    /// Unwraps the inner edit.
+    pub fn into_inner(self) -> TEdit {
+        self.0.into_inner()
+    }
+}
+
+impl<TEdit> From<TEdit> for EditCommand<TEdit> {
+    fn from(edit: TEdit) -> Self {
+        Self::new(edit)
+    }
+}
+
+impl<TEdit: undo::Edit> Command<TEdit::Target> for EditCommand<TEdit> {
+    fn apply(&self, state: &mut TEdit::Target) {
+        self.0.borrow_mut().edit(state);
+    }
+}
+
+/// A [`Command`] recorded as an [`undo::Edit`], in a `Record` or a `History` of the `undo` crate.
+///
+/// The state is snapshotted before the command is applied, and restored by [`Edit::undo`](undo::Edit::undo).
+///
+/// # Example
+/// ```
+/// use simple_undo::edit::CommandEdit;
+///
+/// let mut record: undo::Record<CommandEdit<i32, fn(&mut i32)>> = undo::Record::new();
+/// let mut counter = 1;
+/// record.edit(&mut counter, CommandEdit::new(|value| *value += 1));
+/// record.edit(&mut counter, CommandEdit::new(|value| *value *= 10));
+/// assert_eq!(counter, 20);
+/// record.undo(&mut counter);
+/// assert_eq!(counter, 2);
+/// ```
+pub struct CommandEdit<TState: Snapshot, TCommand> {
+    /// The wrapped command.
+    command: TCommand,
+    /// The snapshot of the state before the command was applied, or `None` if it's not applied.
+    snapshot: Option<TState::Snapshot>,
+}
+
+impl<TState: Snapshot, TCommand> CommandEdit<TState, TCommand> {
+    /// Wraps the command in an edit.
+    pub const fn new(command: TCommand) -> Self {
+        Self {
+            command,
+            snapshot: None,
+        }
+    }
+
+    /// Unwraps the inner command.
+    pub fn into_inner(self) -> TCommand {
+        self.command
+    }
+}
+
+impl<TState, TCommand> undo::Edit for CommandEdit<TState, TCommand>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+{
+    type Target = TState;
+    type Output = ();
+
+    fn edit(&mut self, state: &mut TState) {
+        self.snapshot = Some(state.snapshot());
+        self.command.apply(state);
+    }
+
+    fn undo(&mut self, state: &mut TState) {
+        if let Some(snapshot) = self.snapshot.take() {
+            state.restore(&snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Undo;
+    use alloc::vec::Vec;
+
+    /// An edit of the `undo` crate, reverted with its inverse.
+    struct Add(i32);
+
+    impl undo::Edit for Add {
+        type Target = Vec<i32>;
+        type Output = ();
+
+        fn edit(&mut self, values: &mut Vec<i32>) {
+            values.push(self.0);
+        }
+
+        fn undo(&mut self, values: &mut Vec<i32>) {
+            values.pop();
+        }
+    }
+
+    #[test]
+    fn it_converts_edits_and_commands() {
+        let mut values = Undo::with_commands(Vec::new());
+        values.apply(EditCommand::new(Add(1)));
+        values.apply(Add(2).into());
+        values.undo();
+        assert_eq!(*values, [1]);
+
+        let mut record = undo::Record::new();
+        let mut values = values.unwrap();
+        for command in [Add(3), Add(4)] {
+            record.edit(&mut values, CommandEdit::new(EditCommand::new(command)));
+        }
+        record.undo(&mut values);
+        assert_eq!(values, [1, 3]);
+        record.redo(&mut values);
+        assert_eq!(values, [1, 3, 4]);
+    }
+}
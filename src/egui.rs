@@ -0,0 +1,182 @@
+//! Widgets for an [`Undo`] in an `egui` user interface, enabled by the `egui` feature.
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use core::ops::Range;
+
+use egui::{Button, Response, RichText, TextEdit, Ui};
+
+use crate::{AnyUndo, ChangeKind, Command, Observer, Snapshot, Undo, Update};
+
+/// Shows "Undo" and "Redo" buttons, disabled when there is nothing to undo or redo.
+///
+/// Returns `true` if clicking one of them changed the state.
+///
+/// # Example
+/// ```
+/// use simple_undo::{egui::undo_redo_buttons, Undo};
+///
+/// fn toolbar(ui: &mut egui::Ui, counter: &mut Undo<i32>) {
+///     undo_redo_buttons(ui, counter);
+///     if ui.button("Increment").clicked() {
+///         counter.update(|value| *value += 1);
+///     }
+/// }
+/// ```
+pub fn undo_redo_buttons(ui: &mut Ui, undo: &mut impl AnyUndo) -> bool {
+    ui.horizontal(|ui| {
+        let mut is_changed = false;
+        if ui
+            .add_enabled(undo.can_undo(), Button::new("Undo"))
+            .clicked()
+        {
+            is_changed |= undo.undo();
+        }
+        if ui
+            .add_enabled(undo.can_redo(), Button::new("Redo"))
+            .clicked()
+        {
+            is_changed |= undo.redo();
+        }
+        is_changed
+    })
+    .inner
+}
+
+/// Shows the history as a list of its labels, starting with the initial state and highlighting the current position.
+///
+/// The undone updates are dimmed and the saved position is marked. Clicking an entry [jumps](Undo::jump_to) to it.
+///
+/// Returns `true` if clicking an entry changed the state.
+pub fn history_list<TState, TCommand, TObserver, TMeta>(
+    ui: &mut Ui,
+    undo: &mut Undo<'_, TState, TCommand, TObserver, TMeta>,
+) -> bool
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    let position = undo.position();
+    let saved_position = undo.saved_position();
+    let mut target = None;
+    let labels = core::iter::once((None, true)).chain(
+        undo.history()
+            .map(|entry| (Some(entry.label.unwrap_or("Update")), entry.is_applied)),
+    );
+    for (index, (label, is_applied)) in labels.enumerate() {
+        let mut text = label.unwrap_or("Initial state").to_owned();
+        if saved_position == Some(index) {
+            text.push_str(" (saved)");
+        }
+        let mut text = RichText::new(text);
+        if !is_applied {
+            text = text.weak();
+        }
+        if ui.selectable_label(index == position, text).clicked() {
+            target = Some(index);
+        }
+    }
+    target.is_some_and(|target| target != position && undo.jump_to(target).is_ok())
+}
+
+/// Shows a multiline [`TextEdit`] of the text, recording the changes as undoable steps.
+///
+/// Consecutive changes are coalesced like [`Undo::update_coalesced`], a new step starting after typing
+/// a whitespace or when the text edit loses the focus. Each step only records the replaced range of the text.
+pub fn text_edit<'state, TMeta>(
+    ui: &mut Ui,
+    undo: &mut Undo<'state, String, Update<'state, String>, Observer<'state, String>, TMeta>,
+) -> Response {
+    let mut text = undo.get().clone();
+    let response = ui.add(TextEdit::multiline(&mut text));
+    let key = format!("egui text edit {:?}", response.id);
+    if response.changed() {
+        record_edit(undo, &key, &text);
+    }
+    if response.lost_focus() {
+        end_step(undo, &key);
+    }
+    response
+}
+
+/// Records the change from the current text to `new_text`, coalesced with the previous changes of the same key.
+fn record_edit<'state, TMeta>(
+    undo: &mut Undo<'state, String, Update<'state, String>, Observer<'state, String>, TMeta>,
+    key: &str,
+    new_text: &str,
+) {
+    let (range, replacement) = changed_range(undo.get(), new_text);
+    let is_end_of_step = replacement.contains(char::is_whitespace);
+    undo.update_coalesced(key, move |text| {
+        text.replace_range(range.clone(), &replacement);
+    });
+    if is_end_of_step {
+        end_step(undo, key);
+    }
+}
+
+/// Prevents the next changes from being coalesced with the last recorded one, if it was made with the given key.
+fn end_step<TState: Snapshot, TMeta>(
+    undo: &mut Undo<'_, TState, Update<'_, TState>, Observer<'_, TState>, TMeta>,
+    key: &str,
+) {
+    if let Some(entry) = undo.entries.last_mut() {
+        if entry.coalesce_key.as_deref() == Some(key) {
+            entry.coalesce_key = None;
+        }
+    }
+}
+
+/// Returns the range of `old` replaced to get `new`, along with its replacement.
+fn changed_range(old: &str, new: &str) -> (Range<usize>, String) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, old_char), new_char)| old_char != new_char)
+        .map_or_else(|| old.len().min(new.len()), |((index, _), _)| index);
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(old_char, new_char)| old_char == new_char)
+        .map(|(old_char, _)| old_char.len_utf8())
+        .sum();
+    (
+        prefix..old.len() - suffix,
+        new[prefix..new.len() - suffix].to_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_changed_range() {
+        assert_eq!(changed_range("hello", "help"), (3..5, String::from("p")));
+        assert_eq!(changed_range("aé", "aéb"), (3..3, String::from("b")));
+        assert_eq!(changed_range("abab", "ab"), (2..4, String::new()));
+        assert_eq!(changed_range("same", "same"), (4..4, String::new()));
+    }
+
+    #[test]
+    fn it_coalesces_typing_into_words() {
+        let mut text = Undo::new(String::new());
+        for typed in [
+            "H", "Hi", "Hi ", "Hi t", "Hi th", "Hi the", "Hi thee", "Hi the",
+        ] {
+            record_edit(&mut text, "key", typed);
+        }
+        assert_eq!(*text, "Hi the");
+        assert_eq!(text.history_len(), 2);
+
+        end_step(&mut text, "key");
+        record_edit(&mut text, "key", "Hi there");
+        text.undo();
+        assert_eq!(*text, "Hi the");
+        text.undo();
+        assert_eq!(*text, "Hi ");
+    }
+}
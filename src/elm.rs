@@ -0,0 +1,116 @@
+//! Adapter for applications following the Elm architecture, like `iced`, where the state changes through messages.
+
+use crate::{ChangeKind, Command, Snapshot, Undo};
+
+/// A message of an application whose state is wrapped in an [`Undo`], handled with [`update`].
+///
+/// The undoable messages are [`Command`]s applied to the state, wrapped with `into()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<TCommand> {
+    /// Applies and records the command.
+    Command(TCommand),
+    /// Undoes the last command.
+    Undo,
+    /// Redoes the last undone command.
+    Redo,
+}
+
+impl<TCommand> From<TCommand> for Message<TCommand> {
+    fn from(command: TCommand) -> Self {
+        Self::Command(command)
+    }
+}
+
+/// Routes a message through the `Undo`, applying its command or undoing or redoing the previous ones.
+///
+/// Returns `true` if the state changed, to be called from the update function of the application.
+///
+/// # Example
+/// ```
+/// use simple_undo::elm::{self, Message};
+/// use simple_undo::{Command, Undo};
+///
+/// #[derive(Debug, Clone)]
+/// enum Edit {
+///     Increment,
+///     Reset,
+/// }
+///
+/// impl Command<i32> for Edit {
+///     fn apply(&self, counter: &mut i32) {
+///         match self {
+///             Edit::Increment => *counter += 1,
+///             Edit::Reset => *counter = 0,
+///         }
+///     }
+/// }
+///
+/// /// The application, with the messages of its view, like an `iced` application.
+/// struct Counter {
+///     value: Undo<'static, i32, Edit>,
+/// }
+///
+/// impl Counter {
+///     fn update(&mut self, message: Message<Edit>) {
+///         elm::update(&mut self.value, message);
+///     }
+/// }
+///
+/// let mut counter = Counter { value: Undo::with_commands(0) };
+/// counter.update(Edit::Increment.into());
+/// counter.update(Edit::Increment.into());
+/// counter.update(Message::Undo);
+/// assert_eq!(*counter.value, 1);
+/// ```
+pub fn update<TState, TCommand, TObserver, TMeta>(
+    undo: &mut Undo<'_, TState, TCommand, TObserver, TMeta>,
+    message: Message<TCommand>,
+) -> bool
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    match message {
+        Message::Command(command) => {
+            undo.apply(command);
+            true
+        }
+        Message::Undo => undo.undo(),
+        Message::Redo => undo.redo(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Push(char);
+
+    impl Command<Vec<char>> for Push {
+        fn apply(&self, list: &mut Vec<char>) {
+            list.push(self.0);
+        }
+    }
+
+    #[test]
+    fn it_routes_the_messages_through_the_undo() {
+        let mut list = Undo::with_commands(Vec::new());
+        let messages = [
+            Push('a').into(),
+            Push('b').into(),
+            Message::Undo,
+            Message::Undo,
+            Message::Undo,
+            Message::Redo,
+        ];
+        let changes: Vec<_> = messages
+            .into_iter()
+            .map(|message| update(&mut list, message))
+            .collect();
+        assert_eq!(changes, [true, true, true, true, false, true]);
+        assert_eq!(*list, ['a']);
+    }
+}
@@ -0,0 +1,362 @@
+//! Focused views over a field of the state, whose updates can be undone separately from the other ones.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::ops::Range;
+
+use crate::{Action, ChangeKind, Snapshot, Undo, Update, UpdateId};
+
+/// A view over a field of the state of an [`Undo`], returned by [`Undo::focus`].
+///
+/// Its updates are recorded in the history of the `Undo`, and can also be undone or redone on their own,
+/// the other updates being kept.
+pub struct Focus<'undo, 'state, TState: Snapshot, TLens, TObserver, TMeta> {
+    /// The focused `Undo`.
+    undo: &'undo mut Undo<'state, TState, Update<'state, TState>, TObserver, TMeta>,
+    /// The key identifying the updates of the focused field.
+    key: String,
+    /// The lens returning the focused field of the state.
+    lens: TLens,
+}
+
+impl<'state, TState, TObserver, TMeta>
+    Undo<'state, TState, Update<'state, TState>, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Returns a view over the field of the state returned by `lens`, identified by `key`.
+    ///
+    /// The updates made through views with the same key are undone together, whatever their lenses.
+    /// They must only change the focused field, so that undoing them separately is the same as never applying them.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// #[derive(Clone)]
+    /// struct App {
+    ///     canvas: Vec<&'static str>,
+    ///     zoom: u32,
+    /// }
+    ///
+    /// let mut app = Undo::new(App { canvas: Vec::new(), zoom: 1 });
+    /// app.focus("canvas", |app: &mut App| &mut app.canvas).update(|canvas| canvas.push("circle"));
+    /// app.focus("zoom", |app: &mut App| &mut app.zoom).update(|zoom| *zoom = 2);
+    ///
+    /// app.focus("canvas", |app: &mut App| &mut app.canvas).undo(); // keeps the zoom
+    /// assert!(app.canvas.is_empty());
+    /// assert_eq!(app.zoom, 2);
+    /// app.undo();
+    /// assert_eq!(app.zoom, 1);
+    /// ```
+    pub fn focus<TField, TLens>(
+        &mut self,
+        key: impl Into<String>,
+        lens: TLens,
+    ) -> Focus<'_, 'state, TState, TLens, TObserver, TMeta>
+    where
+        TLens: Fn(&mut TState) -> &mut TField + Copy + 'state,
+    {
+        Focus {
+            undo: self,
+            key: key.into(),
+            lens,
+        }
+    }
+
+    /// Returns `true` if an entry in the given range was recorded with [`Undo::modify`], so no entry can be moved over it.
+    fn restores_snapshot(&self, range: Range<usize>) -> bool {
+        self.entries[range]
+            .iter()
+            .any(|entry| entry.restores_snapshot)
+    }
+
+    /// Moves the entry at index `from` to index `to`, discarding what depends on the states between them.
+    fn move_entry(&mut self, from: usize, to: usize) {
+        let entry = self.entries.remove(from);
+        self.entries.insert(to, entry);
+        let first_moved = from.min(to);
+        self.checkpoints
+            .retain(|(position, _)| *position <= first_moved);
+        if self
+            .saved_position
+            .is_some_and(|position| position > first_moved)
+        {
+            self.saved_position = None;
+        }
+        self.savepoints
+            .retain(|(_, position)| *position <= first_moved);
+    }
+}
+
+impl<'state, TState, TField, TLens, TObserver, TMeta>
+    Focus<'_, 'state, TState, TLens, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TLens: Fn(&mut TState) -> &mut TField + Copy + 'state,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Updates the focused field like [`Undo::update`].
+    pub fn update(&mut self, update_fn: impl Fn(&mut TField) + 'state) -> UpdateId {
+        self.record(None, update_fn)
+    }
+
+    /// Updates the focused field like [`Focus::update`], attaching a label to the recorded update.
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TField) + 'state,
+    ) -> UpdateId {
+        self.record(Some(label.into()), update_fn)
+    }
+
+    /// Returns `true` if there is an applied update of the focused field that can be undone with [`Focus::undo`].
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.undoable().is_some()
+    }
+
+    /// Returns `true` if there is an undone update of the focused field that can be redone with [`Focus::redo`].
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        self.redoable().is_some()
+    }
+
+    /// Undo the last update of the focused field, keeping the updates made after it.
+    ///
+    /// The update is moved to the top of the history before being undone, like any update with [`Undo::undo`],
+    /// so the saved position and savepoints after it are discarded.
+    ///
+    /// Returns `false` if there was nothing to undo, if an update recorded with [`Undo::modify`] was made after it,
+    /// or if a middleware vetoed it, in which case the history is unchanged.
+    pub fn undo(&mut self) -> bool {
+        let Some(index) = self.undoable() else {
+            return false;
+        };
+        if !self.undo.intercept(&mut Action::Undo { count: 1 }) {
+            return false;
+        }
+        let top = self.undo.nb_updates - 1;
+        if index != top {
+            self.undo.move_entry(index, top);
+        }
+        self.undo.apply_undo(top);
+        true
+    }
+
+    /// Redo the first undone update of the focused field, like [`Focus::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo, if an undone update recorded with [`Undo::modify`]
+    /// comes before it, or if a middleware vetoed it.
+    pub fn redo(&mut self) -> bool {
+        let Some(index) = self.redoable() else {
+            return false;
+        };
+        if !self.undo.intercept(&mut Action::Redo { count: 1 }) {
+            return false;
+        }
+        let top = self.undo.nb_updates;
+        if index != top {
+            self.undo.move_entry(index, top);
+        }
+        self.undo.apply_redo(1);
+        true
+    }
+
+    /// Returns the index of the last applied update of the focused field.
+    fn last_applied(&self) -> Option<usize> {
+        self.undo.entries[..self.undo.nb_updates]
+            .iter()
+            .rposition(|entry| entry.focus_key.as_ref() == Some(&self.key))
+    }
+
+    /// Returns the index of the first undone update of the focused field.
+    fn first_undone(&self) -> Option<usize> {
+        let position = self.undo.nb_updates;
+        self.undo.entries[position..]
+            .iter()
+            .position(|entry| entry.focus_key.as_ref() == Some(&self.key))
+            .map(|index| position + index)
+    }
+
+    /// Returns the index of the last applied update of the focused field, if it can be moved to the top.
+    fn undoable(&self) -> Option<usize> {
+        self.last_applied()
+            .filter(|index| !self.undo.restores_snapshot(index + 1..self.undo.nb_updates))
+    }
+
+    /// Returns the index of the first undone update of the focused field, if it can be moved to the next position.
+    fn redoable(&self) -> Option<usize> {
+        self.first_undone()
+            .filter(|index| !self.undo.restores_snapshot(self.undo.nb_updates..*index))
+    }
+
+    /// Applies the update to the focused field and records it.
+    fn record(
+        &mut self,
+        label: Option<String>,
+        update_fn: impl Fn(&mut TField) + 'state,
+    ) -> UpdateId {
+        let lens = self.lens;
+        let mut entry = self.undo.new_entry(
+            label,
+            Box::new(move |state: &mut TState| update_fn(lens(state))) as Update<'state, TState>,
+        );
+        entry.focus_key = Some(self.key.clone());
+        self.undo.apply_entry(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Editor {
+        shapes: Vec<u32>,
+        settings: Vec<u32>,
+    }
+
+    fn shapes(editor: &mut Editor) -> &mut Vec<u32> {
+        &mut editor.shapes
+    }
+
+    fn settings(editor: &mut Editor) -> &mut Vec<u32> {
+        &mut editor.settings
+    }
+
+    #[test]
+    fn it_undoes_the_updates_of_a_field_separately() {
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        let first = editor
+            .focus("shapes", shapes)
+            .update(|shapes| shapes.push(1));
+        editor
+            .focus("settings", settings)
+            .update(|settings| settings.push(10));
+        editor
+            .focus("shapes", shapes)
+            .update_labeled("Add 2", |shapes| shapes.push(2));
+        editor.update(|editor| editor.settings.push(20));
+        editor
+            .focus("settings", settings)
+            .update(|settings| settings.push(30));
+        assert!(!editor.focus("shapes", shapes).can_redo());
+
+        assert!(editor.focus("shapes", shapes).undo());
+        assert!(editor.focus("shapes", shapes).undo());
+        assert!(!editor.focus("shapes", shapes).undo());
+        assert_eq!(editor.position_of(first), Some(4));
+        assert!(editor.shapes.is_empty());
+        assert_eq!(editor.settings, [10, 20, 30]);
+
+        assert!(editor.focus("shapes", shapes).redo());
+        assert_eq!(editor.shapes, [1]);
+        assert_eq!(editor.undo_description(), None);
+        assert!(editor.focus("settings", settings).undo());
+        assert_eq!(editor.settings, [10, 20]);
+
+        editor.undo_all();
+        assert!(editor.settings.is_empty());
+        editor.redo_all();
+        assert_eq!(
+            *editor,
+            Editor {
+                shapes: vec![1, 2],
+                settings: vec![10, 20, 30],
+            }
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_history_when_a_middleware_vetoes() {
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        let shape = editor
+            .focus("shapes", shapes)
+            .update(|shapes| shapes.push(1));
+        editor
+            .focus("settings", settings)
+            .update(|settings| settings.push(10));
+        editor.mark_saved();
+        editor.add_middleware(|_, action| !matches!(action, Action::Undo { .. }));
+        assert!(!editor.focus("shapes", shapes).undo());
+        assert_eq!(editor.shapes, [1]);
+        assert_eq!(editor.position_of(shape), Some(1));
+        assert_eq!(editor.saved_position(), Some(2));
+
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        editor
+            .focus("shapes", shapes)
+            .update(|shapes| shapes.push(1));
+        let setting = editor
+            .focus("settings", settings)
+            .update(|settings| settings.push(10));
+        editor.undo_all();
+        editor.add_middleware(|_, action| !matches!(action, Action::Redo { .. }));
+        assert!(!editor.focus("settings", settings).redo());
+        assert!(editor.settings.is_empty());
+        assert_eq!(editor.position_of(setting), Some(2));
+    }
+
+    #[test]
+    fn it_identifies_closure_lenses_by_key() {
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        editor
+            .focus("shapes", |editor: &mut Editor| &mut editor.shapes)
+            .update(|shapes| shapes.push(1));
+        editor
+            .focus("settings", |editor: &mut Editor| &mut editor.settings)
+            .update(|settings| settings.push(10));
+
+        let mut focus = editor.focus("shapes", |editor: &mut Editor| &mut editor.shapes);
+        assert!(focus.undo());
+        assert!(!focus.can_undo());
+        assert!(editor.shapes.is_empty());
+        assert_eq!(editor.settings, [10]);
+    }
+
+    #[test]
+    fn it_keeps_the_updates_before_a_modification() {
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        editor
+            .focus("shapes", shapes)
+            .update(|shapes| shapes.push(1));
+        editor.modify().settings.push(10);
+        let mut focus = editor.focus("shapes", shapes);
+        assert!(!focus.can_undo());
+        assert!(!focus.undo());
+        assert!(!editor.can_redo());
+        assert_eq!(editor.shapes, [1]);
+
+        let mut editor = Undo::new(Editor {
+            shapes: Vec::new(),
+            settings: Vec::new(),
+        });
+        editor.modify().settings.push(10);
+        editor
+            .focus("shapes", shapes)
+            .update(|shapes| shapes.push(1));
+        editor.undo_all();
+        let mut focus = editor.focus("shapes", shapes);
+        assert!(!focus.can_redo());
+        assert!(!focus.redo());
+        assert!(editor.shapes.is_empty());
+        assert_eq!(editor.position(), 0);
+    }
+}
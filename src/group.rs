@@ -0,0 +1,447 @@
+//! Coordination of several [`Undo`] instances, to undo the most recent change across all of them,
+//! or to route the undo and redo commands to the focused one.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::{AnyUndo, ChangeKind, Command, Snapshot, Undo};
+
+/// The order in which the members of an [`UndoGroup`] changed, shared with their observers.
+#[derive(Default)]
+struct Log {
+    /// Members that can be undone, the most recent change being last.
+    undo: Vec<usize>,
+    /// Members that can be redone, the most recently undone being last.
+    redo: Vec<usize>,
+}
+
+/// A group of [`Undo`] instances, possibly of different types, sharing a single chronological history.
+///
+/// Each member records its own updates as usual, while [`UndoGroup::undo`] undoes the most recent update
+/// across all members and [`UndoGroup::redo`] redoes the most recently undone one.
+/// Members are shared with the group through `Rc<RefCell<_>>`.
+///
+/// # Example
+/// ```
+/// use simple_undo::group::UndoGroup;
+/// use simple_undo::Undo;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let document = Rc::new(RefCell::new(Undo::new(String::new())));
+/// let zoom = Rc::new(RefCell::new(Undo::new(100)));
+/// let mut group = UndoGroup::new();
+/// group.add(&document);
+/// group.add(&zoom);
+///
+/// document.borrow_mut().update(|text| text.push_str("Hello"));
+/// zoom.borrow_mut().update(|zoom| *zoom = 150);
+///
+/// group.undo();
+/// assert_eq!(**zoom.borrow(), 100);
+/// group.undo();
+/// assert_eq!(**document.borrow(), "");
+/// group.redo();
+/// assert_eq!(**document.borrow(), "Hello");
+/// ```
+#[derive(Default)]
+pub struct UndoGroup<'state> {
+    /// All members of the group, indexed by the order in which they were added.
+    members: Vec<Rc<RefCell<dyn AnyUndo + 'state>>>,
+    /// The order in which the members changed.
+    log: Rc<RefCell<Log>>,
+}
+
+impl<'state> UndoGroup<'state> {
+    /// Creates an empty group.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a member to the group, whose following updates become part of the group history.
+    ///
+    /// Updates recorded before being added can't be undone through the group.
+    pub fn add<TState, TCommand>(&mut self, undo: &Rc<RefCell<Undo<'state, TState, TCommand>>>)
+    where
+        TState: Snapshot + 'state,
+        TCommand: Command<TState> + 'state,
+    {
+        let index = self.members.len();
+        let log = Rc::clone(&self.log);
+        undo.borrow_mut().on_change(move |_, kind| {
+            if kind == ChangeKind::Update {
+                let mut log = log.borrow_mut();
+                log.undo.push(index);
+                log.redo.clear();
+            }
+        });
+        self.members
+            .push(Rc::clone(undo) as Rc<RefCell<dyn AnyUndo + 'state>>);
+    }
+
+    /// Returns `true` if a member has an update that can be undone through the group.
+    ///
+    /// # Panics
+    /// Panics if a member is currently mutably borrowed.
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        let log = self.log.borrow();
+        log.undo
+            .iter()
+            .any(|index| self.members[*index].borrow().can_undo())
+    }
+
+    /// Returns `true` if a member has an update that can be redone through the group.
+    ///
+    /// # Panics
+    /// Panics if a member is currently mutably borrowed.
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        let log = self.log.borrow();
+        log.redo
+            .iter()
+            .any(|index| self.members[*index].borrow().can_redo())
+    }
+
+    /// Undo the most recent update across all members.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    ///
+    /// # Panics
+    /// Panics if a member is currently borrowed.
+    pub fn undo(&mut self) -> bool {
+        loop {
+            let Some(index) = self.log.borrow_mut().undo.pop() else {
+                return false;
+            };
+            // The update may have been undone directly on the member, or folded because of its limit.
+            if self.members[index].borrow_mut().undo() {
+                self.log.borrow_mut().redo.push(index);
+                return true;
+            }
+        }
+    }
+
+    /// Redo the most recently undone update across all members.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    ///
+    /// # Panics
+    /// Panics if a member is currently borrowed.
+    pub fn redo(&mut self) -> bool {
+        loop {
+            let Some(index) = self.log.borrow_mut().redo.pop() else {
+                return false;
+            };
+            if self.members[index].borrow_mut().redo() {
+                self.log.borrow_mut().undo.push(index);
+                return true;
+            }
+        }
+    }
+}
+
+/// A registry of histories keyed by an identifier, such as the documents of a multi-document application,
+/// routing the undo and redo commands to the focused one.
+///
+/// Like the members of an [`UndoGroup`], the histories are shared through `Rc<RefCell<_>>`, and can be of
+/// different types. As an [`AnyUndo`], the registry undoes or redoes the focused history.
+///
+/// # Example
+/// ```
+/// use simple_undo::group::UndoRegistry;
+/// use simple_undo::Undo;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let notes = Rc::new(RefCell::new(Undo::new(String::new())));
+/// let sketch = Rc::new(RefCell::new(Undo::new(vec![(0, 0)])));
+/// let mut registry = UndoRegistry::new();
+/// registry.insert("notes.txt", &notes);
+/// registry.insert("sketch.svg", &sketch);
+///
+/// notes.borrow_mut().update(|text| text.push_str("TODO"));
+/// sketch.borrow_mut().update(|points| points.push((1, 1)));
+///
+/// registry.focus(&"notes.txt");
+/// registry.undo_focused(); // Ctrl+Z in the notes tab
+/// assert_eq!(**notes.borrow(), "");
+/// assert_eq!(sketch.borrow().len(), 2);
+/// registry.undo(&"sketch.svg");
+/// assert_eq!(sketch.borrow().len(), 1);
+/// ```
+pub struct UndoRegistry<'state, TKey> {
+    /// All registered histories, in the order in which they were inserted.
+    entries: Vec<(TKey, Rc<RefCell<dyn AnyUndo + 'state>>)>,
+    /// The index of the focused history in the entries, if any.
+    focused: Option<usize>,
+}
+
+impl<'state, TKey: PartialEq> UndoRegistry<'state, TKey> {
+    /// Creates an empty registry.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            focused: None,
+        }
+    }
+
+    /// Registers the history with the given key, replacing the one previously registered with it, if any.
+    pub fn insert(&mut self, key: TKey, undo: &Rc<RefCell<impl AnyUndo + 'state>>) {
+        let undo = Rc::clone(undo) as Rc<RefCell<dyn AnyUndo + 'state>>;
+        if let Some(index) = self.index_of(&key) {
+            self.entries[index].1 = undo;
+        } else {
+            self.entries.push((key, undo));
+        }
+    }
+
+    /// Unregisters the history with the given key, unfocusing it if it was focused.
+    ///
+    /// Returns `false` if there was no history registered with the key.
+    pub fn remove(&mut self, key: &TKey) -> bool {
+        let Some(index) = self.index_of(key) else {
+            return false;
+        };
+        self.entries.remove(index);
+        self.focused = match self.focused {
+            Some(focused) if focused == index => None,
+            Some(focused) if focused > index => Some(focused - 1),
+            focused => focused,
+        };
+        true
+    }
+
+    /// Returns the history registered with the given key.
+    #[must_use]
+    pub fn get(&self, key: &TKey) -> Option<&Rc<RefCell<dyn AnyUndo + 'state>>> {
+        Some(&self.entries[self.index_of(key)?].1)
+    }
+
+    /// Returns the registered keys and histories, in the order in which they were inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&TKey, &Rc<RefCell<dyn AnyUndo + 'state>>)> {
+        self.entries.iter().map(|(key, undo)| (key, undo))
+    }
+
+    /// Returns the number of registered histories.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there is no registered history.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Focuses the history with the given key, such as when switching to its tab.
+    ///
+    /// Returns `false`, leaving the focus unchanged, if there is no history registered with the key.
+    pub fn focus(&mut self, key: &TKey) -> bool {
+        let index = self.index_of(key);
+        if index.is_some() {
+            self.focused = index;
+        }
+        index.is_some()
+    }
+
+    /// Removes the focus, so that [`UndoRegistry::undo_focused`] does nothing.
+    pub const fn unfocus(&mut self) {
+        self.focused = None;
+    }
+
+    /// Returns the key of the focused history, if any.
+    #[must_use]
+    pub fn focused(&self) -> Option<&TKey> {
+        Some(&self.entries[self.focused?].0)
+    }
+
+    /// Undo the last update of the history with the given key.
+    ///
+    /// Returns `false` if there was nothing to undo or no history registered with the key.
+    ///
+    /// # Panics
+    /// Panics if the history is currently borrowed.
+    pub fn undo(&mut self, key: &TKey) -> bool {
+        self.get(key).is_some_and(|undo| undo.borrow_mut().undo())
+    }
+
+    /// Redo the last undone update of the history with the given key, like [`UndoRegistry::undo`].
+    ///
+    /// # Panics
+    /// Panics if the history is currently borrowed.
+    pub fn redo(&mut self, key: &TKey) -> bool {
+        self.get(key).is_some_and(|undo| undo.borrow_mut().redo())
+    }
+
+    /// Returns `true` if the history with the given key has an update that can be undone.
+    ///
+    /// # Panics
+    /// Panics if the history is currently mutably borrowed.
+    #[must_use]
+    pub fn can_undo(&self, key: &TKey) -> bool {
+        self.get(key).is_some_and(|undo| undo.borrow().can_undo())
+    }
+
+    /// Returns `true` if the history with the given key has an update that can be redone.
+    ///
+    /// # Panics
+    /// Panics if the history is currently mutably borrowed.
+    #[must_use]
+    pub fn can_redo(&self, key: &TKey) -> bool {
+        self.get(key).is_some_and(|undo| undo.borrow().can_redo())
+    }
+
+    /// Undo the last update of the focused history.
+    ///
+    /// Returns `false` if there was nothing to undo or no focused history.
+    ///
+    /// # Panics
+    /// Panics if the history is currently borrowed.
+    pub fn undo_focused(&mut self) -> bool {
+        self.focused_undo()
+            .is_some_and(|undo| undo.borrow_mut().undo())
+    }
+
+    /// Redo the last undone update of the focused history, like [`UndoRegistry::undo_focused`].
+    ///
+    /// # Panics
+    /// Panics if the history is currently borrowed.
+    pub fn redo_focused(&mut self) -> bool {
+        self.focused_undo()
+            .is_some_and(|undo| undo.borrow_mut().redo())
+    }
+
+    /// Returns the index of the history registered with the given key.
+    fn index_of(&self, key: &TKey) -> Option<usize> {
+        self.entries.iter().position(|(other, _)| other == key)
+    }
+
+    /// Returns the focused history.
+    fn focused_undo(&self) -> Option<&Rc<RefCell<dyn AnyUndo + 'state>>> {
+        Some(&self.entries[self.focused?].1)
+    }
+}
+
+impl<TKey: PartialEq> Default for UndoRegistry<'_, TKey> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TKey: PartialEq> AnyUndo for UndoRegistry<'_, TKey> {
+    fn undo(&mut self) -> bool {
+        self.undo_focused()
+    }
+
+    fn redo(&mut self) -> bool {
+        self.redo_focused()
+    }
+
+    fn can_undo(&self) -> bool {
+        self.focused_undo()
+            .is_some_and(|undo| undo.borrow().can_undo())
+    }
+
+    fn can_redo(&self) -> bool {
+        self.focused_undo()
+            .is_some_and(|undo| undo.borrow().can_redo())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_undoes_the_most_recent_update_across_members() {
+        let first = Rc::new(RefCell::new(Undo::new(0)));
+        let second = Rc::new(RefCell::new(Undo::new(0)));
+        let mut group = UndoGroup::new();
+        group.add(&first);
+        group.add(&second);
+        assert!(!group.can_undo());
+
+        first.borrow_mut().update(|value| *value += 1);
+        second.borrow_mut().update(|value| *value += 1);
+        first.borrow_mut().update(|value| *value += 1);
+        assert!(group.can_undo());
+
+        assert!(group.undo());
+        assert_eq!((**first.borrow(), **second.borrow()), (1, 1));
+        assert!(group.undo());
+        assert_eq!((**first.borrow(), **second.borrow()), (1, 0));
+        assert!(group.can_redo());
+        assert!(group.redo());
+        assert_eq!((**first.borrow(), **second.borrow()), (1, 1));
+
+        first.borrow_mut().update(|value| *value += 10); // clears the redo history of the group
+        assert!(!group.can_redo());
+        assert!(!group.redo());
+        assert!(group.undo());
+        assert!(group.undo());
+        assert!(group.undo());
+        assert!(!group.undo());
+        assert_eq!((**first.borrow(), **second.borrow()), (0, 0));
+    }
+
+    #[test]
+    fn it_skips_updates_undone_on_members() {
+        let first = Rc::new(RefCell::new(Undo::new(0)));
+        let second = Rc::new(RefCell::new(Undo::new(0)));
+        let mut group = UndoGroup::new();
+        group.add(&first);
+        group.add(&second);
+
+        first.borrow_mut().update(|value| *value += 1);
+        second.borrow_mut().update(|value| *value += 1);
+        second.borrow_mut().undo();
+
+        assert!(group.undo());
+        assert_eq!(**first.borrow(), 0);
+        assert!(!group.undo());
+    }
+
+    #[test]
+    fn it_routes_to_the_focused_history() {
+        let first = Rc::new(RefCell::new(Undo::new(0)));
+        let second = Rc::new(RefCell::new(crate::inverse::UndoInverse::new(0)));
+        let mut registry = UndoRegistry::new();
+        registry.insert(1, &first);
+        registry.insert(2, &second);
+        first.borrow_mut().update(|value| *value += 1);
+        second
+            .borrow_mut()
+            .update(|value| *value += 1, |value| *value -= 1);
+        assert!(!AnyUndo::can_undo(&registry));
+        assert!(!registry.undo_focused());
+
+        assert!(registry.focus(&2));
+        assert!(!registry.focus(&3));
+        assert_eq!(registry.focused(), Some(&2));
+        assert!(AnyUndo::undo(&mut registry));
+        assert_eq!((**first.borrow(), **second.borrow()), (1, 0));
+        assert!(registry.can_redo(&2));
+        assert!(registry.undo(&1));
+        assert!(!registry.undo(&3));
+        assert_eq!(**first.borrow(), 0);
+
+        assert!(registry.remove(&1));
+        assert!(!registry.remove(&1));
+        assert_eq!(registry.focused(), Some(&2));
+        assert!(registry.redo_focused());
+        assert_eq!(**second.borrow(), 1);
+        registry.insert(2, &first);
+        assert_eq!(registry.len(), 1);
+        assert!(registry.redo(&2));
+        assert_eq!(**first.borrow(), 1);
+        assert_eq!(
+            registry.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            [2]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! History made only of updates with their inverse, which doesn't require the state to be cloneable.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use crate::{AnyUndo, Update};
+
+/// An update recorded with the function reverting it.
+struct InverseEntry<'state, TState> {
+    update_fn: Update<'state, TState>,
+    revert_fn: Update<'state, TState>,
+    label: Option<String>,
+}
+
+/// A wrapper like [`Undo`](crate::Undo) recording each update along with its inverse,
+/// so the state is never cloned nor regenerated and doesn't need to implement `Clone`.
+///
+/// Undoing an update applies its inverse, and redoing it applies the update again.
+///
+/// # Example
+/// ```
+/// use simple_undo::inverse::UndoInverse;
+///
+/// struct Buffer {
+///     data: Vec<u8>, // not `Clone`, imagine a GPU buffer
+/// }
+///
+/// let mut buffer = UndoInverse::new(Buffer { data: vec![1, 2] });
+/// buffer.update(|buffer| buffer.data.push(3), |buffer| { buffer.data.pop(); });
+/// assert_eq!(buffer.data, [1, 2, 3]);
+///
+/// buffer.undo();
+/// assert_eq!(buffer.data, [1, 2]);
+/// buffer.redo();
+/// assert_eq!(buffer.data, [1, 2, 3]);
+/// ```
+pub struct UndoInverse<'state, TState> {
+    /// The current state to update.
+    current_state: TState,
+    /// All recorded updates, applied or not.
+    entries: Vec<InverseEntry<'state, TState>>,
+    /// Number of updates applied to the current state. Undoing reduces this number.
+    nb_updates: usize,
+}
+
+impl<'state, TState> UndoInverse<'state, TState> {
+    /// Wraps the given state in an `UndoInverse`, which will track all updates and allows undoing or redoing them.
+    pub const fn new(state: TState) -> Self {
+        Self {
+            current_state: state,
+            entries: Vec::new(),
+            nb_updates: 0,
+        }
+    }
+
+    /// Unwraps the inner state to an owned value, disabling the undo/redo feature.
+    pub fn unwrap(self) -> TState {
+        self.current_state
+    }
+
+    /// Returns `true` if there is an update that can be undone with [`UndoInverse::undo`].
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone update that can be redone with [`UndoInverse::redo`].
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.entries.len()
+    }
+
+    /// Returns the number of recorded updates, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of updates currently applied to the state.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Updates the current state with `update_fn`, recording `revert_fn` to undo it.
+    ///
+    /// Note that `revert_fn` must exactly undo what `update_fn` did, and that future [`UndoInverse::redo`] are reset.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::inverse::UndoInverse;
+    /// let mut counter = UndoInverse::new(0);
+    /// counter.update(|value| *value += 10, |value| *value -= 10);
+    /// counter.update(|value| *value *= 2, |value| *value /= 2);
+    /// assert_eq!(*counter, 20);
+    /// counter.undo();
+    /// assert_eq!(*counter, 10);
+    /// ```
+    pub fn update(
+        &mut self,
+        update_fn: impl Fn(&mut TState) + 'state,
+        revert_fn: impl Fn(&mut TState) + 'state,
+    ) {
+        self.record(None, Box::new(update_fn), Box::new(revert_fn));
+    }
+
+    /// Updates the current state like [`UndoInverse::update`], attaching a label to the recorded update.
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + 'state,
+        revert_fn: impl Fn(&mut TState) + 'state,
+    ) {
+        self.record(Some(label.into()), Box::new(update_fn), Box::new(revert_fn));
+    }
+
+    /// Returns the labels of the recorded updates, from the oldest to the most recent, including the undone ones.
+    pub fn labels(&self) -> impl Iterator<Item = Option<&str>> {
+        self.entries.iter().map(|entry| entry.label.as_deref())
+    }
+
+    /// Undo the last update by applying its inverse.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.nb_updates -= 1;
+        (self.entries[self.nb_updates].revert_fn)(&mut self.current_state);
+        true
+    }
+
+    /// Redo the last update that have been undone using [`UndoInverse::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.nb_updates == self.entries.len() {
+            return false;
+        }
+        (self.entries[self.nb_updates].update_fn)(&mut self.current_state);
+        self.nb_updates += 1;
+        true
+    }
+
+    /// Applies the given update and records it in the history.
+    fn record(
+        &mut self,
+        label: Option<String>,
+        update_fn: Update<'state, TState>,
+        revert_fn: Update<'state, TState>,
+    ) {
+        // Discard previous updates when updating after an undo.
+        self.entries.truncate(self.nb_updates);
+        update_fn(&mut self.current_state);
+        self.entries.push(InverseEntry {
+            update_fn,
+            revert_fn,
+            label,
+        });
+        self.nb_updates += 1;
+    }
+}
+
+impl<TState> Deref for UndoInverse<'_, TState> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_state
+    }
+}
+
+impl<TState> AnyUndo for UndoInverse<'_, TState> {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state that can't be cloned.
+    struct Resource {
+        values: Vec<u32>,
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_without_cloning() {
+        let mut resource = UndoInverse::new(Resource { values: Vec::new() });
+        resource.update(
+            |r| r.values.push(1),
+            |r| {
+                r.values.pop();
+            },
+        );
+        resource.update_labeled(
+            "push 2",
+            |r| r.values.push(2),
+            |r| {
+                r.values.pop();
+            },
+        );
+        assert_eq!(resource.values, [1, 2]);
+
+        assert!(resource.undo());
+        assert_eq!(resource.values, [1]);
+        assert!(resource.undo());
+        assert!(!resource.undo());
+        assert!(resource.values.is_empty());
+        assert!(resource.redo());
+        assert_eq!(resource.values, [1]);
+
+        resource.update(
+            |r| r.values.push(3),
+            |r| {
+                r.values.pop();
+            },
+        );
+        assert!(!resource.can_redo());
+        assert_eq!((resource.position(), resource.history_len()), (2, 2));
+        assert_eq!(resource.labels().collect::<Vec<_>>(), [None, None]);
+        assert_eq!(resource.unwrap().values, [1, 3]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Reactive signals of an [`Undo`] for `leptos` applications, enabled by the `leptos` feature.
+
+use reactive_graph::signal::{ArcReadSignal, ArcRwSignal};
+use reactive_graph::traits::Set;
+
+use crate::{Command, Observer, Snapshot, Undo};
+
+/// Returns a signal of the current state, set to the new state after each update, undo or redo of the `Undo`.
+///
+/// The signal can be converted into a [`ReadSignal`](reactive_graph::signal::ReadSignal) with `into()`.
+///
+/// # Example
+/// ```
+/// use reactive_graph::traits::GetUntracked;
+/// use simple_undo::{leptos::signal, Undo};
+///
+/// let mut counter = Undo::new(0);
+/// let value = signal(&mut counter);
+///
+/// counter.update(|value| *value += 1);
+/// assert_eq!(value.get_untracked(), 1);
+/// counter.undo();
+/// assert_eq!(value.get_untracked(), 0);
+/// ```
+pub fn signal<'state, TState, TCommand, TMeta>(
+    undo: &mut Undo<'state, TState, TCommand, Observer<'state, TState>, TMeta>,
+) -> ArcReadSignal<TState>
+where
+    TState: Snapshot + Clone + Send + Sync + 'static,
+    TCommand: Command<TState>,
+{
+    let signal = ArcRwSignal::new(undo.get().clone());
+    let read_signal = signal.read_only();
+    undo.on_change(move |state, _| signal.set(state.clone()));
+    read_signal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reactive_graph::traits::GetUntracked;
+
+    #[test]
+    fn it_sets_the_signal_on_each_change() {
+        let mut list = Undo::new(vec![1]);
+        let items = signal(&mut list);
+        assert_eq!(items.get_untracked(), [1]);
+
+        list.update(|list| list.push(2));
+        list.update(|list| list.push(3));
+        list.undo_n(2);
+        assert_eq!(items.get_untracked(), [1]);
+        list.redo();
+        assert_eq!(items.get_untracked(), [1, 2]);
+    }
+}
@@ -2,22 +2,901 @@
 #![warn(missing_docs)]
 #![forbid(unsafe_code)]
 #![deny(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::ops::Deref;
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::error::Error;
+use core::fmt;
+use core::future::Future;
+use core::iter;
+use core::marker::PhantomData;
+use core::mem;
+use core::num::NonZeroUsize;
+use core::ops::{Deref, DerefMut, Range};
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "time")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "time")]
+use clock::{Clock, SystemClock};
+
+#[cfg(feature = "std")]
+pub mod background;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod builder;
+pub mod cell;
+#[cfg(feature = "time")]
+pub mod clock;
+pub mod collections;
+pub mod cow;
+pub mod diff;
+#[cfg(feature = "dioxus")]
+pub mod dioxus;
+mod dot;
+#[cfg(feature = "undo")]
+pub mod edit;
+#[cfg(feature = "egui")]
+pub mod egui;
+pub mod elm;
+pub mod focus;
+pub mod group;
+pub mod inverse;
+#[cfg(feature = "leptos")]
+pub mod leptos;
+pub mod merge;
+#[cfg(feature = "json-patch")]
+pub mod patch;
+#[cfg(feature = "im")]
+pub mod persistent;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "ratatui")]
+pub mod ratatui;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod sync;
+pub mod test_utils;
+#[cfg(feature = "ropey")]
+pub mod text;
+#[cfg(feature = "similar")]
+mod text_diff;
+pub mod tree;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "serde")]
+pub use serialization::Event;
+
+/// Derives a `<Struct>Setters` trait implemented for `Undo<Struct>`, with a `set_<field>` method
+/// recording an update labeled `"Set <field>"` for each field.
+///
+/// Field types must implement `Clone`, as the value is cloned each time the update is re-applied.
+///
+/// # Example
+/// ```
+/// use simple_undo::{Undo, Undoable};
+///
+/// #[derive(Clone, Undoable)]
+/// struct Config {
+///     volume: u8,
+///     theme: String,
+/// }
+///
+/// let mut config = Undo::new(Config { volume: 50, theme: String::from("light") });
+/// config.set_volume(80);
+/// config.set_theme(String::from("dark"));
+/// assert_eq!(config.history().next().unwrap().label, Some("Set volume"));
+///
+/// config.undo();
+/// assert_eq!((config.volume, config.theme.as_str()), (80, "light"));
+/// ```
+#[cfg(feature = "derive")]
+pub use simple_undo_derive::Undoable;
+
+/// A recorded update, re-applied when regenerating the current state.
+///
+/// This is the default [`Command`] type of an [`Undo`].
+pub type Update<'state, TState> = Box<dyn Fn(&mut TState) + 'state>;
+
+/// A recorded update that can be sent to another thread, as recorded by a [`SendUndo`].
+pub type SendUpdate<'state, TState> = Box<dyn Fn(&mut TState) + Send + Sync + 'state>;
+
+/// A recorded update that can be cloned, as recorded by a [`CloneUndo`].
+pub struct SharedUpdate<'state, TState>(Arc<dyn Fn(&mut TState) + Send + Sync + 'state>);
+
+impl<TState> Command<TState> for SharedUpdate<'_, TState> {
+    fn apply(&self, state: &mut TState) {
+        (self.0)(state);
+    }
+}
+
+impl<TState> Clone for SharedUpdate<'_, TState> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+/// A callback registered with [`Undo::on_change`].
+///
+/// This is the default observer type of an [`Undo`].
+pub type Observer<'state, TState> = Box<dyn FnMut(&TState, ChangeKind) + 'state>;
+
+/// A callback that can be sent to another thread, as registered on a [`SendUndo`].
+pub type SendObserver<'state, TState> = Box<dyn FnMut(&TState, ChangeKind) + Send + Sync + 'state>;
+
+/// A middleware added with [`Undo::add_middleware`], returning `false` to veto the given action.
+///
+/// Middlewares are `Send + Sync` so that a [`SendUndo`] can still be sent to other threads.
+pub type Middleware<'state, TState> =
+    Box<dyn FnMut(&TState, &mut Action<'_>) -> bool + Send + Sync + 'state>;
+
+/// A validator set with [`Undo::set_validator`], returning the reason why a state is invalid.
+pub type Validator<TState> = fn(&TState) -> Result<(), String>;
+
+/// An [`Undo`] whose updates and observers are `Send + Sync`, created with [`Undo::new_send`].
+///
+/// It can be moved to or shared with other threads as long as the state and its [`Snapshot`] can.
+pub type SendUndo<'state, TState> =
+    Undo<'state, TState, SendUpdate<'state, TState>, SendObserver<'state, TState>>;
+
+/// An [`Undo`] whose updates can be shared, created with [`Undo::new_cloneable`].
+///
+/// It implements `Clone` as long as the [`Snapshot`] of the state does, the clone sharing the recorded updates.
+pub type CloneUndo<'state, TState> = Undo<'state, TState, SharedUpdate<'state, TState>>;
+
+/// The kind of change notified to the observers registered with [`Undo::on_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A new update has been recorded.
+    Update,
+    /// One or more updates have been undone.
+    Undo,
+    /// One or more updates have been redone.
+    Redo,
+    /// The state has been changed without recording an update, while recording is
+    /// [paused](Undo::pause_recording) or with [`Undo::without_undo`].
+    Unrecorded,
+}
+
+/// A change about to be made, seen by the middlewares added with [`Undo::add_middleware`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action<'a> {
+    /// A new update, already applied to the state, about to be recorded with the given label which can be changed.
+    ///
+    /// For an update merged into the previous entry, the label is the one of this entry.
+    Update {
+        /// The label of the recorded entry.
+        label: &'a mut Option<String>,
+    },
+    /// Undoing the given number of updates.
+    Undo {
+        /// The number of updates to undo.
+        count: usize,
+    },
+    /// Redoing the given number of updates.
+    Redo {
+        /// The number of updates to redo.
+        count: usize,
+    },
+}
+
+/// A command updating a state, recorded in the history of an [`Undo`].
+///
+/// Closures are commands, recorded as [`Update`] by default. Implementing this trait for a custom type,
+/// such as an enum of all possible edits, allows inspecting the recorded history with [`Undo::commands`].
+///
+/// # Example
+/// ```
+/// use simple_undo::{Command, Undo};
+///
+/// enum Edit {
+///     Add(i32),
+///     Multiply(i32),
+/// }
+///
+/// impl Command<i32> for Edit {
+///     fn apply(&self, value: &mut i32) {
+///         match self {
+///             Edit::Add(operand) => *value += operand,
+///             Edit::Multiply(operand) => *value *= operand,
+///         }
+///     }
+/// }
+///
+/// let mut counter = Undo::with_commands(1);
+/// counter.apply(Edit::Add(2));
+/// counter.apply(Edit::Multiply(10));
+/// assert_eq!(*counter, 30);
+/// counter.undo();
+/// assert_eq!(*counter, 3);
+/// ```
+pub trait Command<TState> {
+    /// Applies the command to the given state.
+    ///
+    /// It's called when the command is recorded, then each time the state is regenerated,
+    /// so it must always produce the same update.
+    fn apply(&self, state: &mut TState);
+}
+
+impl<TState, F: Fn(&mut TState) + ?Sized> Command<TState> for F {
+    fn apply(&self, state: &mut TState) {
+        self(state);
+    }
+}
+
+/// A strategy to snapshot a state, used by an [`Undo`] to store the initial state and its checkpoints.
+///
+/// It's implemented for all `Clone` types, snapshots being clones. Implementing it for a state that can't
+/// be cloned allows using a cheaper representation, like sharing the unchanged parts of a large document.
+///
+/// The current state is regenerated in place when undoing, through [`Snapshot::restore`], which is
+/// [`Clone::clone_from`] for `Clone` types. Implementing `clone_from` or `restore` to reuse the allocations
+/// of the current state, as `Vec` and `String` do, avoids allocating a new state on each undo.
+///
+/// # Example
+/// ```
+/// use std::rc::Rc;
+/// use simple_undo::{Snapshot, Undo};
+///
+/// /// A large document, made of chunks that are only copied when modified.
+/// struct Document {
+///     chunks: Vec<Rc<String>>,
+/// }
+///
+/// impl Snapshot for Document {
+///     type Snapshot = Vec<Rc<String>>;
+///
+///     fn snapshot(&self) -> Self::Snapshot {
+///         self.chunks.clone()
+///     }
+///
+///     fn restore(&mut self, snapshot: &Self::Snapshot) {
+///         for (chunk, saved) in self.chunks.iter_mut().zip(snapshot) {
+///             if !Rc::ptr_eq(chunk, saved) {
+///                 *chunk = Rc::clone(saved);
+///             }
+///         }
+///         self.chunks.truncate(snapshot.len());
+///         self.chunks.extend(snapshot[self.chunks.len()..].iter().cloned());
+///     }
+///
+///     fn from_snapshot(snapshot: Self::Snapshot) -> Self {
+///         Document { chunks: snapshot }
+///     }
+/// }
+///
+/// let mut document = Undo::new(Document { chunks: vec![Rc::new(String::from("Hello"))] });
+/// document.update(|document| Rc::make_mut(&mut document.chunks[0]).push_str(" world"));
+/// assert_eq!(*document.chunks[0], "Hello world");
+/// document.undo();
+/// assert_eq!(*document.chunks[0], "Hello");
+/// ```
+pub trait Snapshot: Sized {
+    /// The stored representation of a state.
+    type Snapshot;
+
+    /// Takes a snapshot of the state.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Restores the state to the given snapshot, ideally reusing its allocations.
+    fn restore(&mut self, snapshot: &Self::Snapshot);
+
+    /// Builds a state from the given snapshot, used when folding the oldest updates beyond the limit.
+    fn from_snapshot(snapshot: Self::Snapshot) -> Self;
+
+    /// Converts the state into a snapshot, used when folding the oldest updates beyond the limit.
+    fn into_snapshot(self) -> Self::Snapshot {
+        self.snapshot()
+    }
+}
+
+impl<TState: Clone> Snapshot for TState {
+    type Snapshot = Self;
+
+    fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.clone_from(snapshot);
+    }
+
+    fn from_snapshot(snapshot: Self) -> Self {
+        snapshot
+    }
+
+    fn into_snapshot(self) -> Self {
+        self
+    }
+}
+
+/// Identifier of an update recorded in an [`Undo`], as returned by [`Undo::update`].
+///
+/// Identifiers are never reused, so an identifier remains valid but no longer [in the history](Undo::is_in_history)
+/// once its update is discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UpdateId(u64);
+
+/// A token identifying a version of the state of an [`Undo`], as returned by [`Undo::change_token`].
+///
+/// It can be stored next to a value computed from the state, which is outdated once
+/// [`Undo::has_changed_since`] returns `true`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeToken(u64);
+
+/// An entry of the history, holding a recorded command and its optional label and metadata.
+#[derive(Clone)]
+struct Entry<TCommand, TMeta> {
+    /// Assigned when the entry is recorded, increasing along the history unless moved by [`Undo::focus`].
+    id: UpdateId,
+    command: TCommand,
+    /// The inverse of the command, given with [`Undo::update_with_inverse`].
+    revert_fn: Option<TCommand>,
+    label: Option<String>,
+    /// The metadata given with [`Undo::update_with_meta`] or [`Undo::apply_with_meta`].
+    meta: Option<TMeta>,
+    /// The key given with [`Undo::update_coalesced`], merging consecutive updates with the same key.
+    coalesce_key: Option<String>,
+    /// The key given with [`Undo::focus`], to undo the updates of a field separately.
+    focus_key: Option<String>,
+    /// `true` for the changes recorded by [`Undo::modify`], whose command restores a snapshot of the whole state,
+    /// so the updates before it can't be undone separately.
+    restores_snapshot: bool,
+    /// When the entry was recorded, or last merged with another update.
+    #[cfg(feature = "time")]
+    timestamp: Instant,
+}
+
+/// Undone updates discarded by an update, kept with [`RedoPolicy::Keep`].
+#[derive(Clone)]
+struct Branch<TCommand, TMeta> {
+    /// The identifier of the update after which the branch was discarded, `None` for the initial state.
+    parent: Option<UpdateId>,
+    entries: Vec<Entry<TCommand, TMeta>>,
+}
+
+/// The states captured before applying an update, to check it once applied.
+struct PendingUpdate<TState> {
+    /// The state before the update, compared with [`Undo::set_skip_unchanged`].
+    before: Option<TState>,
+    /// The state after the first undone update, compared with [`Undo::set_redo_resurrection`].
+    redone: Option<TState>,
+}
+
+impl<TCommand, TMeta> Entry<TCommand, TMeta> {
+    /// Returns a view over the entry.
+    fn view(&self, is_applied: bool) -> HistoryEntry<'_, TMeta> {
+        HistoryEntry {
+            id: self.id,
+            label: self.label.as_deref(),
+            meta: self.meta.as_ref(),
+            is_applied,
+            #[cfg(feature = "time")]
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// A view over an entry of the history, as returned by [`Undo::history`].
+///
+/// It's non-exhaustive as the `time` feature adds its timestamp, so patterns matching it must end with `..`.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HistoryEntry<'a, TMeta = ()> {
+    /// The identifier returned when recording the update.
+    pub id: UpdateId,
+    /// The label given with [`Undo::update_labeled`] or [`Undo::apply_labeled`], if any.
+    pub label: Option<&'a str>,
+    /// The metadata given with [`Undo::update_with_meta`] or [`Undo::apply_with_meta`], if any.
+    pub meta: Option<&'a TMeta>,
+    /// `true` if the update is currently applied to the state, `false` if it has been undone.
+    pub is_applied: bool,
+    /// When the update was recorded, or last merged with another update.
+    #[cfg(feature = "time")]
+    pub timestamp: Instant,
+}
+
+// Implemented manually as deriving them would require the metadata to be `Clone` and `Copy`.
+impl<TMeta> Clone for HistoryEntry<'_, TMeta> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TMeta> Copy for HistoryEntry<'_, TMeta> {}
+
+/// A branch of undone updates discarded by an update, as returned by [`Undo::discarded_branches`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct DiscardedBranch<'a, TMeta = ()> {
+    /// The position from which the branch was discarded, where [`Undo::restore_branch`] moves back to.
+    pub position: usize,
+    /// The discarded updates, from the oldest to the most recent.
+    pub entries: Vec<HistoryEntry<'a, TMeta>>,
+}
 
 /// The `Undo` type wrapping a state that tracks updates and allows undoing or redoing them.
-pub struct Undo<'state, TState> {
-    /// The initial state used to regenerate the current one.
-    initial_state: TState,
+///
+/// Updates are recorded as closures by default, see [`Command`] to record custom types instead.
+/// The state is snapshotted with its [`Snapshot`] implementation, which clones it by default.
+/// The next type parameter is the type of the observers registered with [`Undo::on_change`],
+/// and the last one is the type of the metadata attached with [`Undo::update_with_meta`].
+pub struct Undo<
+    'state,
+    TState: Snapshot,
+    TCommand = Update<'state, TState>,
+    TObserver = Observer<'state, TState>,
+    TMeta = (),
+> {
     /// The current state to update.
     current_state: TState,
     /// All recorded updates applied to the current state.
-    updates: Vec<Box<dyn Fn(&mut TState) + 'state>>,
+    entries: Vec<Entry<TCommand, TMeta>>,
     /// Number of updates applied to the current state. Undoing reduces this number.
     nb_updates: usize,
+    /// Maximum number of recorded updates, the oldest ones being folded into the initial state.
+    limit: Option<usize>,
+    /// When to fold the oldest applied updates into the initial state, checked after each update.
+    compaction_policy: CompactionPolicy,
+    /// Maximum memory used by the history, the oldest updates being folded into the initial state.
+    memory_budget: Option<MemoryBudget<TState, TCommand>>,
+    /// Number of updates between two snapshots of the state.
+    checkpoint_interval: Option<NonZeroUsize>,
+    /// Snapshots of the state taken after the given number of updates, sorted by position.
+    /// The first one is always the initial state, used to regenerate the current one.
+    checkpoints: Vec<(usize, TState::Snapshot)>,
+    /// Position of the last saved state, `None` if it's no longer part of the history.
+    saved_position: Option<usize>,
+    /// Named positions set with [`Undo::savepoint`], from the oldest to the most recent.
+    savepoints: Vec<(String, usize)>,
+    /// What happens to the undone updates when updating after an undo.
+    redo_policy: RedoPolicy,
+    /// The branches of undone updates kept with [`RedoPolicy::Keep`], from the oldest to the most recent.
+    discarded: Vec<Branch<TCommand, TMeta>>,
+    /// `false` while recording is paused, updates being folded into the checkpoints instead.
+    is_recording: bool,
+    /// Compares the states before and after an update, which is not recorded if it returns `true`.
+    is_unchanged: Option<fn(&TState, &TState) -> bool>,
+    /// Compares the state after an update made after an undo with the state after the first undone update,
+    /// which is redone instead of being discarded if it returns `true`.
+    is_redone: Option<fn(&TState, &TState) -> bool>,
+    /// Compares the current state with the state replayed from the history after each update, in debug builds.
+    is_replayed: Option<fn(&TState, &TState) -> bool>,
+    /// Checks the state after each update, which is rolled back if it returns an error.
+    validator: Option<Validator<TState>>,
+    /// The identifier of the next recorded update.
+    next_id: u64,
+    /// The number of changes of the current state, incremented by every update, undo and redo.
+    version: u64,
+    /// How the `Undo` has been used, as returned by [`Undo::stats`].
+    stats: UsageStats,
+    /// Callbacks called after each change of the current state.
+    observers: Vec<TObserver>,
+    /// Callbacks intercepting each action, which can veto it.
+    middlewares: Vec<Middleware<'state, TState>>,
+    /// Maximum delay between two updates merged into a single entry.
+    #[cfg(feature = "time")]
+    grouping_window: Option<Duration>,
+    /// Maximum time to replay the updates since the nearest checkpoint, before a new one is taken.
+    #[cfg(feature = "time")]
+    replay_budget: Option<Duration>,
+    /// Estimated time to replay the applied updates since the nearest checkpoint.
+    #[cfg(feature = "time")]
+    replay_cost: Duration,
+    /// The clock timestamping the recorded updates.
+    #[cfg(feature = "time")]
+    clock: Arc<dyn Clock + Send + Sync>,
+    /// The lifetime of the recorded updates, only used by the default command and observer types.
+    lifetime: PhantomData<&'state ()>,
+}
+
+/// The history of an [`Undo`] without its current state, returned by [`Undo::into_parts`].
+///
+/// It owns the recorded updates and the snapshots needed to replay them.
+pub struct History<'state, TState: Snapshot, TCommand = Update<'state, TState>, TMeta = ()> {
+    // The fields of `Undo`, without the current state, the observers and the middlewares.
+    entries: Vec<Entry<TCommand, TMeta>>,
+    nb_updates: usize,
+    limit: Option<usize>,
+    compaction_policy: CompactionPolicy,
+    memory_budget: Option<MemoryBudget<TState, TCommand>>,
+    checkpoint_interval: Option<NonZeroUsize>,
+    checkpoints: Vec<(usize, TState::Snapshot)>,
+    saved_position: Option<usize>,
+    savepoints: Vec<(String, usize)>,
+    redo_policy: RedoPolicy,
+    discarded: Vec<Branch<TCommand, TMeta>>,
+    is_recording: bool,
+    is_unchanged: Option<fn(&TState, &TState) -> bool>,
+    is_redone: Option<fn(&TState, &TState) -> bool>,
+    is_replayed: Option<fn(&TState, &TState) -> bool>,
+    validator: Option<Validator<TState>>,
+    next_id: u64,
+    version: u64,
+    stats: UsageStats,
+    #[cfg(feature = "time")]
+    grouping_window: Option<Duration>,
+    #[cfg(feature = "time")]
+    replay_budget: Option<Duration>,
+    #[cfg(feature = "time")]
+    replay_cost: Duration,
+    #[cfg(feature = "time")]
+    clock: Arc<dyn Clock + Send + Sync>,
+    /// The lifetime of the recorded updates, only used by the default command type.
+    lifetime: PhantomData<&'state ()>,
+}
+
+impl<TState: Snapshot, TCommand: Command<TState>, TMeta> History<'_, TState, TCommand, TMeta> {
+    /// Returns the number of recorded updates, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no update is recorded.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of updates applied to the state the history is at.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Replaces the given state with the one the history is at, by replaying the recorded updates.
+    pub fn replay(&self, state: &mut TState) {
+        // The initial state at position 0 is always found.
+        let index = self
+            .checkpoints
+            .partition_point(|(position, _)| *position <= self.nb_updates)
+            - 1;
+        let (start, snapshot) = &self.checkpoints[index];
+        state.restore(snapshot);
+        for entry in &self.entries[*start..self.nb_updates] {
+            entry.command.apply(state);
+        }
+    }
+}
+
+/// A policy folding the oldest updates of an [`Undo`] into its initial state as the history grows,
+/// set with [`Undo::set_compaction_policy`].
+///
+/// Unlike the [limit](Undo::set_limit), it never discards the undone updates that can still be redone.
+///
+/// Build it with [`CompactionPolicy::new`] and its `with_*` methods, as the `time` feature adds fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CompactionPolicy {
+    /// Maximum number of applied updates kept in the history.
+    pub keep_last: Option<usize>,
+    /// Maximum age of the applied updates kept in the history.
+    ///
+    /// Only the oldest updates are folded, so an expired update moved after a recent one by [`Undo::focus`] is kept.
+    #[cfg(feature = "time")]
+    pub max_age: Option<Duration>,
+}
+
+impl CompactionPolicy {
+    /// Returns a policy keeping all applied updates.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            keep_last: None,
+            #[cfg(feature = "time")]
+            max_age: None,
+        }
+    }
+
+    /// Returns the policy keeping at most the given number of applied updates.
+    #[must_use]
+    pub const fn with_keep_last(mut self, keep_last: usize) -> Self {
+        self.keep_last = Some(keep_last);
+        self
+    }
+
+    /// Returns the policy keeping the applied updates recorded at most `max_age` ago.
+    ///
+    /// The age is checked with the [clock](Undo::set_clock) of the `Undo` after each update.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{CompactionPolicy, Undo};
+    /// # use simple_undo::clock::ManualClock;
+    /// # use std::time::Duration;
+    /// let clock = ManualClock::new();
+    /// let mut counter = Undo::new(0);
+    /// counter.set_clock(clock.clone());
+    /// counter.set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_secs(3600)));
+    /// counter.update(|value| *value += 1);
+    /// clock.advance(Duration::from_secs(2 * 3600));
+    /// counter.update(|value| *value += 1); // folds the update made two hours ago
+    /// assert_eq!(counter.history_len(), 1);
+    /// ```
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub const fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+}
+
+/// A bound on the approximate memory used by the history of an [`Undo`], set with [`Undo::set_memory_budget`].
+///
+/// The memory of an entry or a snapshot is its size, plus the heap memory returned by the given functions.
+pub struct MemoryBudget<TState: Snapshot, TCommand> {
+    /// Maximum number of bytes used by the recorded updates and the snapshots.
+    pub max_bytes: usize,
+    /// Returns the heap memory used by a snapshot of the state, like the capacity of a `Vec`.
+    pub snapshot_size: fn(&TState::Snapshot) -> usize,
+    /// Returns the heap memory used by a recorded command, `0` for closures.
+    pub command_size: fn(&TCommand) -> usize,
+}
+
+impl<TState: Snapshot, TCommand> Clone for MemoryBudget<TState, TCommand> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TState: Snapshot, TCommand> Copy for MemoryBudget<TState, TCommand> {}
+
+impl<TState: Snapshot, TCommand> fmt::Debug for MemoryBudget<TState, TCommand> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What happens to the undone updates when updating after an undo, set with [`Undo::set_redo_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedoPolicy {
+    /// The undone updates are discarded.
+    #[default]
+    Discard,
+    /// The undone updates are kept as a branch, listed by [`Undo::discarded_branches`]
+    /// and recovered with [`Undo::restore_branch`].
+    Keep,
+}
+
+/// A policy saving the state of an [`Undo`] as it changes, set with [`Undo::autosave`].
+///
+/// The state is saved once enough changes are made or enough time has passed since the last save,
+/// or on every change if neither is set.
+///
+/// Build it with [`AutosavePolicy::new`] and its `with_*` methods, as the `time` feature adds fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AutosavePolicy {
+    /// Number of changes, updates as well as undos and redos, after which the state is saved.
+    pub every: Option<NonZeroUsize>,
+    /// Minimum time since the last save after which the state is saved on the next change.
+    #[cfg(feature = "time")]
+    pub interval: Option<Duration>,
+}
+
+impl AutosavePolicy {
+    /// Returns a policy saving the state on every change.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            every: None,
+            #[cfg(feature = "time")]
+            interval: None,
+        }
+    }
+
+    /// Returns the policy saving the state once the given number of changes are made.
+    #[must_use]
+    pub const fn with_every(mut self, every: NonZeroUsize) -> Self {
+        self.every = Some(every);
+        self
+    }
+
+    /// Returns the policy saving the state on the next change once `interval` has passed since the last save.
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub const fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Returns an observer calling `save_fn` with the state when a save is due, measuring the interval with `clock`.
+    fn observer<TState>(
+        self,
+        #[cfg(feature = "time")] clock: Arc<dyn Clock + Send + Sync>,
+        mut save_fn: impl FnMut(&TState),
+    ) -> impl FnMut(&TState, ChangeKind) {
+        let mut nb_changes = 0;
+        #[cfg(feature = "time")]
+        let mut last_save = clock.now();
+        move |state, _| {
+            nb_changes += 1;
+            let is_due = self.every.map(|every| nb_changes >= every.get());
+            #[cfg(feature = "time")]
+            let is_due = self.interval.map_or(is_due, |interval| {
+                Some(is_due == Some(true) || clock.now().duration_since(last_save) >= interval)
+            });
+            if is_due.unwrap_or(true) {
+                save_fn(state);
+                nb_changes = 0;
+                #[cfg(feature = "time")]
+                {
+                    last_save = clock.now();
+                }
+            }
+        }
+    }
+}
+
+/// The memory used by the history of an [`Undo`], as returned by [`Undo::memory_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The number of recorded updates.
+    pub entries: usize,
+    /// The number of snapshots of the state, including the initial state.
+    pub snapshots: usize,
+    /// The approximate number of bytes allocated for the history.
+    ///
+    /// It includes the allocated capacity and the labels, but not what the recorded commands
+    /// and the snapshots themselves point to, like the captures of boxed closures.
+    pub approximate_bytes: usize,
 }
 
-impl<'state, TState: Clone> Undo<'state, TState> {
+/// How an [`Undo`] has been used since its creation or the last [`Undo::reset_stats`], as returned by [`Undo::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageStats {
+    /// The number of updates, including the ones merged into the previous entry.
+    pub updates: u64,
+    /// The number of undos, undoing several updates at once counting as one.
+    pub undos: u64,
+    /// The number of redos, redoing several updates at once counting as one.
+    pub redos: u64,
+    /// The number of times updating after an undo discarded the updates that could have been redone.
+    pub discarded_branches: u64,
+    /// The number of times the state has been regenerated by replaying updates from a checkpoint.
+    pub replays: u64,
+    /// The total number of updates replayed to regenerate the state.
+    pub replayed_updates: u64,
+}
+
+impl UsageStats {
+    /// Returns the average number of updates replayed each time the state is regenerated,
+    /// `None` if it has never been regenerated.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // an approximation is enough
+    pub fn average_replay_len(&self) -> Option<f64> {
+        (self.replays > 0).then(|| self.replayed_updates as f64 / self.replays as f64)
+    }
+}
+
+/// Error returned by [`Undo::jump_to`] when the position is past the end of the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    /// The requested position.
+    pub position: usize,
+    /// The number of recorded updates at the time of the jump.
+    pub history_len: usize,
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "position {} is out of range for a history of {} updates",
+            self.position, self.history_len
+        )
+    }
+}
+
+impl Error for OutOfRange {}
+
+/// The progress of a replay of [`Undo::jump_to_with_progress`], given after each replayed update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// The number of updates replayed so far.
+    pub replayed: usize,
+    /// The number of updates to replay.
+    pub total: usize,
+}
+
+/// A token cancelling a replay of [`Undo::jump_to_with_progress`] once [cancelled](CancellationToken::cancel).
+///
+/// Its clones share the same flag, so it can be cancelled from another thread or a callback.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the replays using this token, which stop before replaying the next update.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Error returned by [`Undo::try_apply`] when the validator set with [`Undo::set_validator`] rejects an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidState {
+    /// The reason returned by the validator.
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid state: {}", self.reason)
+    }
+}
+
+impl Error for InvalidState {}
+
+/// Error returned by [`Undo::undo_entry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoEntryError {
+    /// The entry at the given index is not currently applied to the state.
+    NotApplied {
+        /// The requested index.
+        index: usize,
+        /// The number of applied updates at the time of the undo.
+        position: usize,
+    },
+    /// The updates after the entry could not be rebased without it, the validator rejecting the resulting state
+    /// or one of them panicking.
+    Conflict(InvalidState),
+    /// A middleware vetoed the undo.
+    Vetoed,
+    /// A later update recorded with [`Undo::modify`] restores a snapshot including the changes of the entry,
+    /// so it can't be undone separately.
+    RestoresSnapshot {
+        /// The index of the update restoring a snapshot.
+        index: usize,
+    },
+}
+
+impl fmt::Display for UndoEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotApplied { index, position } => {
+                write!(f, "entry {index} is not applied at position {position}")
+            }
+            Self::Conflict(invalid) => write!(f, "conflicting updates, {invalid}"),
+            Self::Vetoed => write!(f, "undo vetoed by a middleware"),
+            Self::RestoresSnapshot { index } => {
+                write!(
+                    f,
+                    "entry {index} restores a snapshot including the undone changes"
+                )
+            }
+        }
+    }
+}
+
+impl Error for UndoEntryError {}
+
+impl<'state, TState: Snapshot + 'state> Undo<'state, TState> {
     /// Wraps the given state in an `Undo`, which will track all updates and allows undoing or redoing them.
     ///
     /// # Example
@@ -27,33 +906,42 @@ impl<'state, TState: Clone> Undo<'state, TState> {
     /// let mut wrapper = Undo::new(5);
     /// ```
     pub fn new(state: TState) -> Self {
-        Self {
-            current_state: state.clone(),
-            initial_state: state,
-            updates: Vec::new(),
-            nb_updates: 0,
-        }
+        Self::with_commands(state)
     }
 
-    /// Unwraps the inner state to an owned value, disabling the undo/redo feature.
+    /// Wraps the given state like [`Undo::new`], keeping at most `limit` updates in the history.
+    ///
+    /// See [`Undo::set_limit`] for how the oldest updates are discarded.
     ///
     /// # Example
     /// ```
     /// # use simple_undo::Undo;
-    /// let mut message = Undo::new(String::new());
-    /// message.update(|text| text.push_str("Hello "));
-    /// message.update(|text| text.push_str("world !"));
+    /// let mut counter = Undo::with_limit(0, 2);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
     ///
-    /// let result: String = message.unwrap();
-    /// assert_eq!(result, "Hello world !");
+    /// assert_eq!(counter.undo_all(), 2);
+    /// assert_eq!(*counter, 1);
     /// ```
-    pub fn unwrap(self) -> TState {
-        self.current_state
+    pub fn with_limit(state: TState, limit: usize) -> Self {
+        let mut undo = Self::new(state);
+        undo.limit = Some(limit);
+        undo
     }
+}
 
+impl<'state, TState: Snapshot + 'state, TMeta>
+    Undo<'state, TState, Update<'state, TState>, Observer<'state, TState>, TMeta>
+{
     /// Updates the current state with the given mutating function.
     ///
     /// Note that future [`Undo::redo`] are reset.
+    /// If `update_fn` panics, the state is regenerated to what it was before and the update is not recorded.
+    /// If a recorded update panics as well while regenerating the state, the state is restored to the nearest
+    /// checkpoint instead, the updates after it being undone. Without `std`, this second panic can't be caught.
+    ///
+    /// Returns the identifier of the recorded update, or of the previous one if both are grouped together.
     ///
     /// # Example
     /// ```
@@ -64,128 +952,5026 @@ impl<'state, TState: Clone> Undo<'state, TState> {
     /// counter.update(|value| *value += 3);
     /// assert_eq!(*counter, 8);
     /// ```
-    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + 'state) {
-        if self.nb_updates != self.updates.len() {
-            // Discard previous updates when updating after an undo.
-            self.updates.truncate(self.nb_updates);
+    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + 'state) -> UpdateId {
+        #[cfg(feature = "time")]
+        if self.is_within_grouping_window() {
+            let pending = self.prepare_update();
+            self.unwind_safe(|undo| update_fn(&mut undo.current_state));
+            return self
+                .merge_into_previous(pending, update_fn)
+                .unwrap_or_else(|_| self.next_id());
         }
-        update_fn(&mut self.current_state);
-        self.updates.push(Box::new(update_fn));
-        self.nb_updates += 1;
+        self.apply(Box::new(update_fn))
     }
 
-    /// Undo the last update done to the current state.
+    /// Updates the current state like [`Undo::update`], returning the result of `update_fn` when it's first applied.
+    ///
+    /// The results of the next applications, when the state is regenerated, are dropped.
+    ///
+    /// # Panics
+    /// Panics if `update_fn` panics, after regenerating the state like [`Undo::update`].
     ///
     /// # Example
     /// ```
     /// # use simple_undo::Undo;
-    /// let mut counter = Undo::new(0);
-    /// counter.update(|value| *value += 1);
-    /// counter.update(|value| *value += 2);
-    /// assert_eq!(*counter, 3);
+    /// let mut list = Undo::new(vec![1, 5]);
+    /// let index = list.update_ret(|list| {
+    ///     let index = list.partition_point(|value| *value < 3);
+    ///     list.insert(index, 3);
+    ///     index
+    /// });
+    /// assert_eq!(index, 1);
+    /// assert_eq!(*list, [1, 3, 5]);
+    /// ```
+    pub fn update_ret<TResult: 'state>(
+        &mut self,
+        update_fn: impl Fn(&mut TState) -> TResult + 'state,
+    ) -> TResult {
+        let result = Rc::new(Cell::new(None));
+        let first = Cell::new(Some(Rc::clone(&result)));
+        self.update(move |state| {
+            let value = update_fn(state);
+            if let Some(first) = first.take() {
+                first.set(Some(value));
+            }
+        });
+        result
+            .take()
+            .expect("the update is applied once when recorded")
+    }
+
+    /// Updates the current state like [`Undo::update`], attaching a label to the recorded update.
     ///
-    /// counter.undo();
-    /// assert_eq!(*counter, 1);
-    /// counter.undo();
-    /// assert_eq!(*counter, 0);
-    /// counter.undo(); // does nothing
-    /// assert_eq!(*counter, 0);
+    /// Labels can be retrieved with [`Undo::history`], for example to build an "Undo Typing" menu entry.
+    ///
+    /// # Example
     /// ```
-    pub fn undo(&mut self) {
-        if self.nb_updates == 0 {
-            return;
-        }
-        self.nb_updates -= 1;
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update_labeled("Add ten", |value| *value += 10);
+    /// assert_eq!(*counter, 10);
+    /// assert_eq!(counter.history().next().unwrap().label, Some("Add ten"));
+    /// ```
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + 'state,
+    ) -> UpdateId {
+        self.apply_labeled(label, Box::new(update_fn))
+    }
 
-        self.current_state = self.initial_state.clone();
-        for update_fn in self.updates[..self.nb_updates].iter() {
-            update_fn(&mut self.current_state);
-        }
+    /// Updates the current state like [`Undo::update`], attaching metadata to the recorded update.
+    ///
+    /// See [`Undo::with_meta`] to wrap a state with a custom metadata type.
+    pub fn update_with_meta(
+        &mut self,
+        meta: TMeta,
+        update_fn: impl Fn(&mut TState) + 'state,
+    ) -> UpdateId {
+        self.apply_with_meta(meta, Box::new(update_fn))
     }
 
-    /// Redo the last update that have been undone using [`Undo::undo`].
+    /// Updates the current state with the given fallible function, only recording it if it succeeds.
+    ///
+    /// If the function fails, the state is regenerated to what it was before the update and the error is returned.
+    /// If it succeeds but the validator set with [`Undo::set_validator`] rejects the new state, the update is rolled back
+    /// like with [`Undo::update`].
+    /// Note that future [`Undo::redo`] are only reset if the update succeeds.
+    ///
+    /// Returns the identifier of the recorded update when the function succeeds, like [`Undo::update`].
+    ///
+    /// # Errors
+    /// Returns the error of `update_fn`, if any.
     ///
     /// # Example
     /// ```
     /// # use simple_undo::Undo;
-    /// let mut counter = Undo::new(0);
-    /// counter.update(|value| *value += 1); // 1
-    /// counter.update(|value| *value += 2); // 3
-    /// counter.undo(); // 1
-    /// counter.undo(); // 0
-    /// assert_eq!(*counter, 0);
+    /// let mut list = Undo::new(vec![1, 2, 3]);
+    /// let result = list.try_update(|list| {
+    ///     list.clear();
+    ///     Err("cannot update")
+    /// });
+    /// assert_eq!(result, Err("cannot update"));
+    /// assert_eq!(*list, [1, 2, 3]);
+    /// assert!(!list.can_undo());
     ///
-    /// counter.redo();
-    /// assert_eq!(*counter, 1);
-    /// counter.redo();
-    /// assert_eq!(*counter, 3);
-    /// counter.redo(); // does nothing
-    /// assert_eq!(*counter, 3);
+    /// list.try_update(|list| {
+    ///     list.push(4);
+    ///     Ok::<(), &str>(())
+    /// })?;
+    /// assert_eq!(*list, [1, 2, 3, 4]);
+    /// # Ok::<(), &str>(())
     /// ```
-    pub fn redo(&mut self) {
-        if self.nb_updates == self.updates.len() {
-            return;
+    pub fn try_update<TError>(
+        &mut self,
+        update_fn: impl Fn(&mut TState) -> Result<(), TError> + 'state,
+    ) -> Result<UpdateId, TError>
+    where
+        TError: 'state,
+    {
+        let pending = self.prepare_update();
+        if let Err(error) = self.unwind_safe(|undo| update_fn(&mut undo.current_state)) {
+            self.regenerate();
+            return Err(error);
         }
-        self.updates[self.nb_updates](&mut self.current_state);
-        self.nb_updates += 1;
+        let update_fn = move |state: &mut TState| {
+            // The update succeeded once, so it's expected to succeed again while regenerating the state.
+            let _ = update_fn(state);
+        };
+        let entry = self.new_entry(None, Box::new(update_fn));
+        Ok(self
+            .finish_update(pending, entry)
+            .unwrap_or_else(|_| self.next_id()))
     }
-}
-
-impl<'state, TState: Clone> Deref for Undo<'state, TState> {
-    type Target = TState;
 
-    fn deref(&self) -> &Self::Target {
-        &self.current_state
+    /// Awaits `value`, then updates the current state with it like [`Undo::update`].
+    ///
+    /// The resolved value is captured by the recorded update, so that regenerating the state
+    /// re-applies the same value without awaiting again.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// async fn fetch_name() -> String {
+    ///     String::from("Alice")
+    /// }
+    ///
+    /// # pollster::block_on(async {
+    /// let mut names = Undo::new(Vec::new());
+    /// names.update_async(fetch_name(), |names, name| names.push(name.clone())).await;
+    /// names.update(|names| names.push(String::from("Bob")));
+    /// assert_eq!(*names, ["Alice", "Bob"]);
+    ///
+    /// names.undo(); // re-applies the fetched name
+    /// assert_eq!(*names, ["Alice"]);
+    /// # });
+    /// ```
+    #[allow(clippy::future_not_send)] // updates aren't `Send`, see `Undo::new_send`
+    pub async fn update_async<TValue: 'state>(
+        &mut self,
+        value: impl Future<Output = TValue>,
+        update_fn: impl Fn(&mut TState, &TValue) + 'state,
+    ) -> UpdateId {
+        let value = value.await;
+        self.update(move |state| update_fn(state, &value))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[derive(Clone)]
-    struct Counter {
-        count: u64,
+    /// Updates the current state like [`Undo::update`], along with the function reverting this update.
+    ///
+    /// Undoing this update applies `revert_fn` to the current state instead of re-applying all previous updates
+    /// to the initial state. Both kinds of updates can be mixed: the state is only regenerated when undoing
+    /// an update without inverse.
+    ///
+    /// Note that `revert_fn` must exactly undo what `update_fn` did for the history to remain consistent.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1, 2]);
+    /// list.update_with_inverse(|list| list.push(3), |list| { list.pop(); });
+    /// assert_eq!(*list, [1, 2, 3]);
+    ///
+    /// list.undo(); // applies the inverse
+    /// assert_eq!(*list, [1, 2]);
+    /// list.redo();
+    /// assert_eq!(*list, [1, 2, 3]);
+    /// ```
+    pub fn update_with_inverse(
+        &mut self,
+        update_fn: impl Fn(&mut TState) + 'state,
+        revert_fn: impl Fn(&mut TState) + 'state,
+    ) -> UpdateId {
+        let mut entry = self.new_entry(None, Box::new(update_fn) as Update<'state, TState>);
+        entry.revert_fn = Some(Box::new(revert_fn));
+        self.apply_entry(entry)
+    }
+
+    /// Updates the current state like [`Undo::update`], merging this update with the previous one
+    /// if it was also made with `update_coalesced` and the same key.
+    ///
+    /// Consecutive related edits, like typing characters one by one, are then undone as a single step.
+    /// Updates are never merged after an undo, nor with the updates of a different key.
+    ///
+    /// Returns the identifier of the recorded update, or of the previous one if both are merged.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update_coalesced("typing", |text| text.push('H'));
+    /// text.update_coalesced("typing", |text| text.push('i'));
+    /// text.update(|text| text.push(' '));
+    /// text.update_coalesced("typing", |text| text.push('!'));
+    /// assert_eq!(text.history_len(), 3);
+    ///
+    /// text.undo_n(2);
+    /// assert_eq!(*text, "Hi");
+    /// text.undo();
+    /// assert_eq!(*text, "");
+    /// ```
+    pub fn update_coalesced(
+        &mut self,
+        key: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + 'state,
+    ) -> UpdateId {
+        let key = key.into();
+        let pending = self.prepare_update();
+        self.unwind_safe(|undo| update_fn(&mut undo.current_state));
+
+        let can_merge = self.can_merge();
+        let previous = self
+            .entries
+            .last_mut()
+            .filter(|entry| can_merge && entry.coalesce_key.as_ref() == Some(&key));
+        if previous.is_none() {
+            let mut entry = self.new_entry(None, Box::new(update_fn) as Update<'state, TState>);
+            entry.coalesce_key = Some(key);
+            return self
+                .finish_update(pending, entry)
+                .unwrap_or_else(|_| self.next_id());
+        }
+        self.merge_into_previous(pending, update_fn)
+            .unwrap_or_else(|_| self.next_id())
+    }
+
+    /// Returns a [`ModifyGuard`] giving mutable access to the state, recording the changes as a single update once dropped.
+    ///
+    /// The state before and after the changes are snapshotted, so any mutation can be undone without
+    /// writing it as a closure. Replaying this update restores the state after the changes, so the updates
+    /// before it can't be undone separately with [`Undo::focus`] or [`Undo::undo_entry`].
+    /// While recording is [paused](Undo::pause_recording), the changes can't be folded into the earlier states,
+    /// so the whole history is folded into the initial state instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![3, 1, 2]);
+    /// list.modify().sort_unstable();
+    /// {
+    ///     let mut list = list.modify();
+    ///     list.set_label("Append");
+    ///     list.extend([4, 5]);
+    /// }
+    /// assert_eq!(*list, [1, 2, 3, 4, 5]);
+    ///
+    /// list.undo();
+    /// assert_eq!(*list, [1, 2, 3]);
+    /// list.undo();
+    /// assert_eq!(*list, [3, 1, 2]);
+    /// ```
+    pub fn modify(&mut self) -> ModifyGuard<'_, 'state, TState, TMeta>
+    where
+        TState::Snapshot: 'state,
+    {
+        ModifyGuard {
+            before: Some(self.current_state.snapshot()),
+            label: None,
+            meta: None,
+            undo: self,
+        }
+    }
+
+    /// Applies several updates within a [`Transaction`], recording them as a single entry of the history.
+    ///
+    /// Undoing a transaction reverts all of its updates at once. Nothing is recorded if no update was made.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut position = Undo::new((0, 0));
+    /// position.transact(|tx| {
+    ///     tx.set_label("Drag");
+    ///     tx.update(|(x, _)| *x += 5);
+    ///     tx.update(|(_, y)| *y += 3);
+    ///     tx.update(|(x, _)| *x += 2);
+    ///     assert_eq!(**tx, (7, 3));
+    /// });
+    /// assert_eq!(*position, (7, 3));
+    /// assert_eq!(position.history_len(), 1);
+    ///
+    /// position.undo();
+    /// assert_eq!(*position, (0, 0));
+    /// ```
+    pub fn transact(&mut self, transaction_fn: impl FnOnce(&mut Transaction<'_, 'state, TState>)) {
+        let pending = self.prepare_update();
+        let (updates, label) = self.unwind_safe(|undo| {
+            let mut transaction = Transaction {
+                state: &mut undo.current_state,
+                updates: Vec::new(),
+                label: None,
+            };
+            transaction_fn(&mut transaction);
+            (transaction.updates, transaction.label)
+        });
+        if updates.is_empty() {
+            return;
+        }
+        let update_fn = move |state: &mut TState| {
+            for update_fn in &updates {
+                update_fn(state);
+            }
+        };
+        let _ = self.finish_update(pending, self.new_entry(label, Box::new(update_fn)));
+    }
+
+    /// Collapses the updates recorded in the given range of positions into a single entry, leaving the state unchanged.
+    ///
+    /// If only part of the range is applied, the remaining updates of the range are redone first.
+    /// The resulting entry can be reverted without regenerating the state if all squashed updates had an inverse,
+    /// and keeps the metadata of the last squashed update.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if the range ends past the end of the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(Vec::new());
+    /// list.update(|list| list.push(0));
+    /// for i in 1..=100 {
+    ///     list.update(move |list| list.push(i));
+    /// }
+    ///
+    /// list.squash(1..101)?;
+    /// assert_eq!(list.history_len(), 2);
+    /// list.undo();
+    /// assert_eq!(*list, [0]);
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn squash(&mut self, range: Range<usize>) -> Result<(), OutOfRange> {
+        self.squash_entries(range, None)
+    }
+
+    /// Collapses the updates like [`Undo::squash`], attaching a label to the resulting entry.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if the range ends past the end of the history.
+    pub fn squash_labeled(
+        &mut self,
+        range: Range<usize>,
+        label: impl Into<String>,
+    ) -> Result<(), OutOfRange> {
+        self.squash_entries(range, Some(label.into()))
+    }
+
+    /// Returns the maximum delay between two updates merged into a single entry, if enabled.
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub const fn grouping_window(&self) -> Option<Duration> {
+        self.grouping_window
+    }
+
+    /// Merges the updates made with [`Undo::update`] within `window` after the previous one into a single entry,
+    /// or disables it with `None`.
+    ///
+    /// This groups bursts of updates, like typing a word, into a single undo step.
+    /// Updates are never merged after an undo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::time::Duration;
+    /// let mut text = Undo::new(String::new());
+    /// text.set_grouping_window(Some(Duration::from_secs(1)));
+    /// text.update(|text| text.push('H'));
+    /// text.update(|text| text.push('i'));
+    /// assert_eq!(text.history_len(), 1);
+    ///
+    /// text.undo();
+    /// assert_eq!(*text, "");
+    /// ```
+    #[cfg(feature = "time")]
+    pub const fn set_grouping_window(&mut self, window: Option<Duration>) {
+        self.grouping_window = window;
+    }
+
+    /// Returns `true` if an update made now should be merged into the previous one.
+    #[cfg(feature = "time")]
+    fn is_within_grouping_window(&self) -> bool {
+        let Some(window) = self.grouping_window else {
+            return false;
+        };
+        self.can_merge()
+            && self.entries.last().is_some_and(|entry| {
+                // Coalesced and focused updates are only merged with the updates of the same key.
+                entry.coalesce_key.is_none()
+                    && entry.focus_key.is_none()
+                    && self.clock.now().duration_since(entry.timestamp) <= window
+            })
+    }
+
+    /// Replaces the entries in the given range by a single entry applying all of them.
+    fn squash_entries(
+        &mut self,
+        range: Range<usize>,
+        label: Option<String>,
+    ) -> Result<(), OutOfRange> {
+        if range.end > self.entries.len() {
+            return Err(OutOfRange {
+                position: range.end,
+                history_len: self.entries.len(),
+            });
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+        if range.contains(&self.nb_updates) && self.nb_updates > range.start {
+            self.redo_n(range.end - self.nb_updates);
+        }
+
+        let id = self.entries[range.end - 1].id;
+        let meta = self.entries[range.end - 1].meta.take();
+        let restores_snapshot = self.entries[range.clone()]
+            .iter()
+            .any(|entry| entry.restores_snapshot);
+        #[cfg(feature = "time")]
+        let timestamp = self.entries[range.end - 1].timestamp;
+        let (update_fns, revert_fns): (Vec<_>, Vec<_>) = self
+            .entries
+            .drain(range.clone())
+            .map(|entry| (entry.command, entry.revert_fn))
+            .unzip();
+        let mut entry = self.new_entry(
+            label,
+            Box::new(move |state: &mut TState| {
+                for update_fn in &update_fns {
+                    update_fn(state);
+                }
+            }) as Update<'state, TState>,
+        );
+        if let Some(revert_fns) = revert_fns.into_iter().collect::<Option<Vec<_>>>() {
+            entry.revert_fn = Some(Box::new(move |state: &mut TState| {
+                for revert_fn in revert_fns.iter().rev() {
+                    revert_fn(state);
+                }
+            }));
+        }
+        entry.id = id;
+        entry.meta = meta;
+        entry.restores_snapshot = restores_snapshot;
+        #[cfg(feature = "time")]
+        {
+            entry.timestamp = timestamp;
+        }
+        self.entries.insert(range.start, entry);
+
+        // Shift the positions after the range, dropping the ones within it.
+        let nb_removed = range.len() - 1;
+        let shift = |position: usize| {
+            if position <= range.start {
+                Some(position)
+            } else if position >= range.end {
+                Some(position - nb_removed)
+            } else {
+                None
+            }
+        };
+        self.checkpoints = mem::take(&mut self.checkpoints)
+            .into_iter()
+            .filter_map(|(position, snapshot)| Some((shift(position)?, snapshot)))
+            .collect();
+        self.saved_position = self.saved_position.and_then(shift);
+        self.savepoints = mem::take(&mut self.savepoints)
+            .into_iter()
+            .filter_map(|(name, position)| Some((name, shift(position)?)))
+            .collect();
+        // The range is either fully applied or not at all, so the position is never within it.
+        self.nb_updates = shift(self.nb_updates).unwrap_or(range.start);
+        Ok(())
+    }
+
+    /// Merges an update applied to the current state since [`Undo::prepare_update`] into the last recorded update,
+    /// checking it like [`Undo::finish_update`].
+    ///
+    /// The update is rolled back if the validator rejects it.
+    fn merge_into_previous(
+        &mut self,
+        pending: PendingUpdate<TState>,
+        update_fn: impl Fn(&mut TState) + 'state,
+    ) -> Result<UpdateId, InvalidState> {
+        if self.entries.is_empty() {
+            return self.finish_update(pending, self.new_entry(None, Box::new(update_fn)));
+        }
+        self.validate()?;
+        if let Some((before, is_unchanged)) = pending.before.zip(self.is_unchanged) {
+            if is_unchanged(&before, &self.current_state) {
+                return Ok(self.next_id());
+            }
+        }
+        let previous = self.entries.last_mut().expect("checked above");
+        let id = previous.id;
+        let mut label = previous.label.take();
+        let is_accepted = self.intercept(&mut Action::Update { label: &mut label });
+        let previous = self.entries.last_mut().expect("checked above");
+        previous.label = label;
+        if !is_accepted {
+            self.regenerate();
+            return Ok(id);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            position = self.nb_updates,
+            label = previous.label,
+            "update merged into the previous one"
+        );
+        let previous_fn = mem::replace(&mut previous.command, Box::new(|_| {}));
+        previous.command = Box::new(move |state| {
+            previous_fn(state);
+            update_fn(state);
+        });
+        previous.revert_fn = None;
+        #[cfg(feature = "time")]
+        {
+            previous.timestamp = self.clock.now();
+        }
+
+        // The state after the previous update changed, so its checkpoint and saved marker are outdated.
+        let position = self.nb_updates;
+        self.checkpoints
+            .retain(|(checkpoint, _)| *checkpoint < position);
+        self.take_checkpoint();
+        if self.saved_position == Some(position) {
+            self.saved_position = None;
+        }
+        self.enforce_limit();
+        self.compact();
+        self.enforce_memory_budget();
+        self.notify(ChangeKind::Update);
+        Ok(id)
+    }
+}
+
+impl<'state, TState, TCommand, TObserver, TMeta> Undo<'state, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Wraps the given state in an `Undo` without any recorded update nor observer.
+    fn wrap(state: TState) -> Self {
+        Self {
+            checkpoints: vec![(0, state.snapshot())],
+            current_state: state,
+            entries: Vec::new(),
+            nb_updates: 0,
+            limit: None,
+            compaction_policy: CompactionPolicy::default(),
+            memory_budget: None,
+            checkpoint_interval: None,
+            saved_position: Some(0),
+            savepoints: Vec::new(),
+            redo_policy: RedoPolicy::Discard,
+            discarded: Vec::new(),
+            is_recording: true,
+            is_unchanged: None,
+            is_redone: None,
+            is_replayed: None,
+            validator: None,
+            next_id: 0,
+            version: 0,
+            stats: UsageStats::default(),
+            observers: Vec::new(),
+            middlewares: Vec::new(),
+            #[cfg(feature = "time")]
+            grouping_window: None,
+            #[cfg(feature = "time")]
+            replay_budget: None,
+            #[cfg(feature = "time")]
+            replay_cost: Duration::ZERO,
+            #[cfg(feature = "time")]
+            clock: Arc::new(SystemClock),
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Returns the maximum number of updates kept in the history, if any.
+    #[must_use]
+    pub const fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Sets the maximum number of updates kept in the history, or removes it with `None`.
+    ///
+    /// When the limit is exceeded, the oldest updates are applied once to the initial state and dropped,
+    /// so they can no longer be undone. If the history still exceeds the limit because of undone updates,
+    /// the updates that could have been redone last are discarded.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
+    ///
+    /// counter.set_limit(Some(1));
+    /// assert_eq!(counter.history_len(), 1);
+    /// assert_eq!(*counter, 6);
+    /// counter.undo();
+    /// assert_eq!(*counter, 3);
+    /// ```
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        self.enforce_limit();
+    }
+
+    /// Returns the policy compacting the history, see [`Undo::set_compaction_policy`].
+    #[must_use]
+    pub const fn compaction_policy(&self) -> CompactionPolicy {
+        self.compaction_policy
+    }
+
+    /// Sets the policy compacting the history, applied now and then after each update.
+    ///
+    /// The applied updates exceeding the policy are applied once to the initial state and dropped,
+    /// so they can no longer be undone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{CompactionPolicy, Undo};
+    /// let mut counter = Undo::new(0);
+    /// counter.set_compaction_policy(CompactionPolicy::new().with_keep_last(2));
+    /// for _ in 0..5 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    /// assert_eq!(counter.history_len(), 2);
+    /// assert_eq!(counter.undo_all(), 2);
+    /// assert_eq!(*counter, 3);
+    /// ```
+    pub fn set_compaction_policy(&mut self, policy: CompactionPolicy) {
+        self.compaction_policy = policy;
+        self.compact();
+    }
+
+    /// Returns the bound on the memory used by the history, see [`Undo::set_memory_budget`].
+    #[must_use]
+    pub const fn memory_budget(&self) -> Option<MemoryBudget<TState, TCommand>> {
+        self.memory_budget
+    }
+
+    /// Bounds the approximate memory used by the recorded updates and the snapshots, applied now and then
+    /// after each update, or removes the bound with `None`.
+    ///
+    /// The oldest applied updates exceeding the budget are folded into the initial state like with
+    /// [`Undo::set_limit`], then the most recent undone updates are discarded if needed.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{MemoryBudget, Undo};
+    /// # use std::num::NonZeroUsize;
+    /// let mut image = Undo::new(Vec::<u8>::new());
+    /// image.set_checkpoint_interval(NonZeroUsize::new(1));
+    /// image.set_memory_budget(Some(MemoryBudget {
+    ///     max_bytes: 1 << 20,
+    ///     snapshot_size: Vec::capacity,
+    ///     command_size: |_| 0,
+    /// }));
+    /// for _ in 0..100 {
+    ///     image.update(|image| image.extend([0; 100_000]));
+    /// }
+    ///
+    /// assert!(image.history_len() < 10);
+    /// ```
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget<TState, TCommand>>) {
+        self.memory_budget = budget;
+        self.enforce_memory_budget();
+    }
+
+    /// Returns what happens to the undone updates when updating after an undo, see [`Undo::set_redo_policy`].
+    #[must_use]
+    pub const fn redo_policy(&self) -> RedoPolicy {
+        self.redo_policy
+    }
+
+    /// Sets what happens to the undone updates when updating after an undo.
+    ///
+    /// With [`RedoPolicy::Keep`], they are kept as a branch that can be restored with [`Undo::restore_branch`]
+    /// instead of being lost. Setting [`RedoPolicy::Discard`] drops the branches kept so far.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{RedoPolicy, Undo};
+    /// let mut text = Undo::new(String::from("Hello"));
+    /// text.set_redo_policy(RedoPolicy::Keep);
+    /// text.update(|text| text.push_str(" world"));
+    /// text.undo();
+    /// text.update(|text| text.push_str(" there")); // discards " world"
+    ///
+    /// let branch = text.discarded_branches().next().unwrap();
+    /// assert_eq!(branch.position, 0);
+    /// let first = branch.entries[0].id;
+    /// text.restore_branch(first);
+    /// assert_eq!(*text, "Hello");
+    /// text.redo();
+    /// assert_eq!(*text, "Hello world");
+    /// ```
+    pub fn set_redo_policy(&mut self, policy: RedoPolicy) {
+        self.redo_policy = policy;
+        if policy == RedoPolicy::Discard {
+            self.discarded.clear();
+        }
+    }
+
+    /// Returns the branches of undone updates kept with [`RedoPolicy::Keep`] that can be restored,
+    /// from the oldest to the most recently discarded.
+    pub fn discarded_branches(&self) -> impl Iterator<Item = DiscardedBranch<'_, TMeta>> {
+        self.discarded.iter().filter_map(|branch| {
+            Some(DiscardedBranch {
+                position: self.fork_position(branch)?,
+                entries: branch
+                    .entries
+                    .iter()
+                    .map(|entry| entry.view(false))
+                    .collect(),
+            })
+        })
+    }
+
+    /// Restores the discarded branch starting with the update of the given identifier,
+    /// moving back to the position from which it was discarded so that its updates can be redone.
+    ///
+    /// The updates it replaces are kept as a new branch, so that they can be restored as well.
+    /// Returns `false` if there is no such branch, or if it can't be restored anymore because the update
+    /// it follows is no longer in the history.
+    pub fn restore_branch(&mut self, id: UpdateId) -> bool {
+        let Some(index) = self
+            .discarded
+            .iter()
+            .position(|branch| branch.entries[0].id == id)
+        else {
+            return false;
+        };
+        let Some(position) = self.fork_position(&self.discarded[index]) else {
+            return false;
+        };
+        let mut branch = self.discarded.remove(index);
+        if position < self.entries.len() {
+            let entries = self.entries.split_off(position);
+            self.discarded.push(Branch {
+                parent: branch.parent,
+                entries,
+            });
+        }
+        self.truncate(position);
+        self.entries.append(&mut branch.entries);
+        if self.nb_updates > position {
+            self.nb_updates = position;
+            self.regenerate();
+            self.notify(ChangeKind::Undo);
+        }
+        self.enforce_limit();
+        true
+    }
+
+    /// Returns the position from which the given branch was discarded, if it's still in the history.
+    fn fork_position(&self, branch: &Branch<TCommand, TMeta>) -> Option<usize> {
+        branch
+            .parent
+            .map_or(Some(0), |parent| self.position_of(parent))
+    }
+
+    /// Returns the number of updates between two snapshots of the state, if enabled.
+    #[must_use]
+    pub const fn checkpoint_interval(&self) -> Option<NonZeroUsize> {
+        self.checkpoint_interval
+    }
+
+    /// Enables snapshots of the state every `interval` updates, or disables them with `None`.
+    ///
+    /// Undoing then only re-applies the updates recorded since the nearest snapshot instead of the whole history,
+    /// at the cost of keeping a [`Snapshot`] of the state, a clone by default, for every checkpoint.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::num::NonZeroUsize;
+    /// let mut counter = Undo::new(0);
+    /// counter.set_checkpoint_interval(NonZeroUsize::new(100));
+    /// for _ in 0..1000 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    ///
+    /// counter.undo(); // only re-applies 99 updates
+    /// assert_eq!(*counter, 999);
+    /// ```
+    pub fn set_checkpoint_interval(&mut self, interval: Option<NonZeroUsize>) {
+        self.checkpoint_interval = interval;
+        if interval.is_none() {
+            // Only keep the initial state.
+            self.checkpoints.truncate(1);
+        }
+    }
+
+    /// Replaces the clock timestamping the recorded updates, [`SystemClock`] by default.
+    ///
+    /// The timestamps are used by the [grouping window](Undo::set_grouping_window),
+    /// the [maximum age](CompactionPolicy::max_age) and [`Undo::undo_to`], so a [`ManualClock`](clock::ManualClock)
+    /// makes them testable. The [replay budget](Undo::set_replay_budget) is measured with the clock as well,
+    /// and the [autosave interval](AutosavePolicy::interval) with the clock set when calling [`Undo::autosave`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use simple_undo::clock::ManualClock;
+    /// # use std::time::Duration;
+    /// let clock = ManualClock::new();
+    /// let mut text = Undo::new(String::new());
+    /// text.set_clock(clock.clone());
+    /// text.set_grouping_window(Some(Duration::from_secs(1)));
+    /// text.update(|text| text.push('H'));
+    /// clock.advance(Duration::from_secs(2));
+    /// text.update(|text| text.push('i'));
+    /// assert_eq!(text.history_len(), 2);
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn set_clock(&mut self, clock: impl Clock + Send + Sync + 'static) {
+        self.clock = Arc::new(clock);
+    }
+
+    /// Returns the maximum time to replay the updates since the nearest checkpoint, if enabled.
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub const fn replay_budget(&self) -> Option<Duration> {
+        self.replay_budget
+    }
+
+    /// Takes a snapshot of the state whenever replaying the updates since the nearest checkpoint
+    /// would take more than `budget`, or disables it with `None`.
+    ///
+    /// The replay time is estimated from the time taken by each update when it's recorded,
+    /// and measured when the state is regenerated with the [clock](Undo::set_clock) of the `Undo`, giving a predictable undo latency
+    /// without choosing a [checkpoint interval](Undo::set_checkpoint_interval).
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::time::Duration;
+    /// let mut counter = Undo::new(0);
+    /// counter.set_replay_budget(Some(Duration::from_millis(2)));
+    /// for _ in 0..1000 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    ///
+    /// counter.undo(); // re-applies the updates of at most 2 ms
+    /// assert_eq!(*counter, 999);
+    /// ```
+    #[cfg(feature = "time")]
+    pub const fn set_replay_budget(&mut self, budget: Option<Duration>) {
+        self.replay_budget = budget;
+    }
+
+    /// Returns `true` if the replay budget is exceeded by the estimated replay time.
+    #[cfg(feature = "time")]
+    fn is_over_replay_budget(&self) -> bool {
+        self.replay_budget
+            .is_some_and(|budget| self.replay_cost > budget)
+    }
+
+    /// Skips recording the updates leaving the state unchanged according to `is_unchanged`,
+    /// called with the states before and after each update, or records all updates with `None`.
+    ///
+    /// It applies to [`Undo::update`], [`Undo::update_labeled`], [`Undo::update_with_inverse`] and [`Undo::apply`],
+    /// at the cost of a [`Snapshot`] of the state before each of them. The [`UpdateId`] returned
+    /// for a skipped update isn't part of the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut name = Undo::new(String::from("Alice"));
+    /// name.set_skip_unchanged(Some(PartialEq::eq));
+    /// name.update(|name| *name = String::from("Bob"));
+    /// name.update(|name| *name = String::from("Bob")); // not recorded
+    /// assert_eq!(name.history_len(), 1);
+    /// ```
+    pub fn set_skip_unchanged(&mut self, is_unchanged: Option<fn(&TState, &TState) -> bool>) {
+        self.is_unchanged = is_unchanged;
+    }
+
+    /// Redoes the first undone update instead of discarding it when an update made after an undo
+    /// leads to the same state according to `is_redone`, or always discards it with `None`.
+    ///
+    /// Repeating an undone edit by hand then keeps the updates that can be redone after it.
+    /// It applies to the same updates as [`Undo::set_skip_unchanged`], at the cost of a [`Snapshot`] of the state
+    /// before each of them when there is something to redo. The [`UpdateId`] returned is then the one
+    /// of the redone update.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(Vec::new());
+    /// list.set_redo_resurrection(Some(PartialEq::eq));
+    /// list.update(|list| list.push("a"));
+    /// list.update(|list| list.push("b"));
+    /// list.undo_all();
+    ///
+    /// list.update(|list| list.push("a")); // same as the first undone update
+    /// assert!(list.can_redo());
+    /// list.redo();
+    /// assert_eq!(*list, ["a", "b"]);
+    /// ```
+    pub fn set_redo_resurrection(&mut self, is_redone: Option<fn(&TState, &TState) -> bool>) {
+        self.is_redone = is_redone;
+    }
+
+    /// Checks that the updates are deterministic in debug builds, by replaying the whole history
+    /// from the initial state after each recorded update and comparing the result with the current state
+    /// using `is_replayed`, or disables it with `None`.
+    ///
+    /// An update capturing external state, like a random number generator or the current time,
+    /// produces a different state once replayed, which silently corrupts the states after an undo.
+    /// This catches it as soon as it's recorded, at the cost of replaying the whole history each time,
+    /// so it is meant for tests. It does nothing in release builds.
+    ///
+    /// # Panics
+    /// The next updates panic if the replayed state differs from the current state.
+    ///
+    /// # Example
+    /// ```should_panic
+    /// # use simple_undo::Undo;
+    /// # use std::cell::Cell;
+    /// let seed = Cell::new(0);
+    /// let mut list = Undo::new(Vec::new());
+    /// list.set_determinism_check(Some(PartialEq::eq));
+    /// list.update(|list| {
+    ///     seed.set(seed.get() + 1);
+    ///     list.push(seed.get()); // depends on how many times it's called
+    /// });
+    /// ```
+    pub fn set_determinism_check(&mut self, is_replayed: Option<fn(&TState, &TState) -> bool>) {
+        self.is_replayed = is_replayed;
+    }
+
+    /// Checks the state with `validator` after each update, or removes it with `None`.
+    ///
+    /// When the validator returns an error, the update is rolled back and not recorded, so the state never
+    /// ends up invalid. Use [`Undo::try_apply`] to get the error.
+    ///
+    /// It applies to [`Undo::update`], [`Undo::update_labeled`], [`Undo::update_with_inverse`], [`Undo::apply`]
+    /// and [`Undo::modify`]. The [`UpdateId`] returned for a rejected update isn't part of the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1, 2]);
+    /// list.set_validator(Some(|list| if list.is_empty() { Err("empty list".into()) } else { Ok(()) }));
+    /// list.update(|list| list.clear()); // rolled back
+    /// list.update(|list| list.push(3));
+    /// assert_eq!(*list, [1, 2, 3]);
+    /// assert_eq!(list.history_len(), 1);
+    /// ```
+    pub fn set_validator(&mut self, validator: Option<Validator<TState>>) {
+        self.validator = validator;
+    }
+
+    /// Adds a middleware called with the current state and each [`Action`] before it's done,
+    /// returning `false` to veto it. Middlewares are called in the order they were added.
+    ///
+    /// A vetoed update is rolled back and not recorded, and the [`UpdateId`] returned for it isn't part of the history.
+    /// A vetoed undo or redo does nothing, as if there was nothing to undo or redo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{Action, Undo};
+    /// let mut counter = Undo::new(0);
+    /// counter.add_middleware(|_, action| {
+    ///     if let Action::Update { label } = action {
+    ///         label.get_or_insert_with(|| "Edit".to_owned());
+    ///     }
+    ///     true
+    /// });
+    /// counter.add_middleware(|value, action| !matches!(action, Action::Update { .. } if *value < 0));
+    ///
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value -= 10); // vetoed
+    /// assert_eq!(*counter, 1);
+    /// assert_eq!(counter.history().next().unwrap().label, Some("Edit"));
+    /// ```
+    pub fn add_middleware(
+        &mut self,
+        middleware: impl FnMut(&TState, &mut Action<'_>) -> bool + Send + Sync + 'state,
+    ) {
+        self.middlewares.push(Box::new(middleware));
+    }
+
+    /// Unwraps the inner state to an owned value, disabling the undo/redo feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut message = Undo::new(String::new());
+    /// message.update(|text| text.push_str("Hello "));
+    /// message.update(|text| text.push_str("world !"));
+    ///
+    /// let result: String = message.unwrap();
+    /// assert_eq!(result, "Hello world !");
+    /// ```
+    pub fn unwrap(self) -> TState {
+        self.current_state
+    }
+
+    /// Returns a reference to the current state, like dereferencing the `Undo`.
+    ///
+    /// This avoids any ambiguity when the state itself implements `Deref`.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut boxed = Undo::new(Box::new(1));
+    /// boxed.update(|value| **value += 1);
+    /// let value: &Box<i32> = boxed.get();
+    /// assert_eq!(**value, 2);
+    /// ```
+    #[must_use]
+    pub const fn get(&self) -> &TState {
+        &self.current_state
+    }
+
+    /// Calls `read_fn` with the current state and returns its result, like [`SharedUndo::read`](sync::SharedUndo::read).
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let list = Undo::new(vec![1, 2, 3]);
+    /// assert_eq!(list.read(|list| list.iter().sum::<i32>()), 6);
+    /// ```
+    pub fn read<TResult>(&self, read_fn: impl FnOnce(&TState) -> TResult) -> TResult {
+        read_fn(&self.current_state)
+    }
+
+    /// Splits the `Undo` into the current state and its [`History`], which can be stored separately
+    /// and wrapped again with [`Undo::from_parts`].
+    ///
+    /// The observers registered with [`Undo::on_change`] and the middlewares are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// let (state, history) = counter.into_parts();
+    /// assert_eq!((state, history.len()), (1, 1));
+    ///
+    /// let mut counter = Undo::from_parts(state, history);
+    /// counter.undo();
+    /// assert_eq!(*counter, 0);
+    /// ```
+    pub fn into_parts(self) -> (TState, History<'state, TState, TCommand, TMeta>) {
+        let history = History {
+            entries: self.entries,
+            nb_updates: self.nb_updates,
+            limit: self.limit,
+            compaction_policy: self.compaction_policy,
+            memory_budget: self.memory_budget,
+            checkpoint_interval: self.checkpoint_interval,
+            checkpoints: self.checkpoints,
+            saved_position: self.saved_position,
+            savepoints: self.savepoints,
+            redo_policy: self.redo_policy,
+            discarded: self.discarded,
+            is_recording: self.is_recording,
+            is_unchanged: self.is_unchanged,
+            is_redone: self.is_redone,
+            is_replayed: self.is_replayed,
+            validator: self.validator,
+            next_id: self.next_id,
+            version: self.version,
+            stats: self.stats,
+            #[cfg(feature = "time")]
+            grouping_window: self.grouping_window,
+            #[cfg(feature = "time")]
+            replay_budget: self.replay_budget,
+            #[cfg(feature = "time")]
+            replay_cost: self.replay_cost,
+            #[cfg(feature = "time")]
+            clock: self.clock,
+            lifetime: PhantomData,
+        };
+        (self.current_state, history)
+    }
+
+    /// Returns `true` if there is an update that can be undone with [`Undo::undo`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// assert!(!counter.can_undo());
+    /// counter.update(|value| *value += 1);
+    /// assert!(counter.can_undo());
+    /// counter.undo();
+    /// assert!(!counter.can_undo());
+    /// ```
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone update that can be redone with [`Undo::redo`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// assert!(!counter.can_redo());
+    /// counter.undo();
+    /// assert!(counter.can_redo());
+    /// counter.redo();
+    /// assert!(!counter.can_redo());
+    /// ```
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.entries.len()
+    }
+
+    /// Returns the label of the update that [`Undo::undo`] would undo, to build an "Undo ..." menu entry.
+    ///
+    /// Returns `None` if there is nothing to undo or if the update has no label.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut rows = Undo::new(vec![1, 2]);
+    /// rows.update_labeled("Delete Row", |rows| { rows.pop(); });
+    /// assert_eq!(rows.undo_description(), Some("Delete Row"));
+    /// assert_eq!(rows.redo_description(), None);
+    ///
+    /// rows.undo();
+    /// assert_eq!(rows.undo_description(), None);
+    /// assert_eq!(rows.redo_description(), Some("Delete Row"));
+    /// ```
+    #[must_use]
+    pub fn undo_description(&self) -> Option<&str> {
+        let index = self.nb_updates.checked_sub(1)?;
+        self.entries[index].label.as_deref()
+    }
+
+    /// Returns the label of the update that [`Undo::redo`] would redo, to build a "Redo ..." menu entry.
+    ///
+    /// Returns `None` if there is nothing to redo or if the update has no label.
+    #[must_use]
+    pub fn redo_description(&self) -> Option<&str> {
+        self.entries.get(self.nb_updates)?.label.as_deref()
+    }
+
+    /// Returns the number of recorded updates, including the undone ones that can still be redone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.undo();
+    /// assert_eq!(counter.history_len(), 2);
+    /// ```
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of updates currently applied to the state, between `0` and [`Undo::history_len`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
+    /// counter.undo();
+    /// assert_eq!(counter.position(), 2); // "step 2 of 3"
+    /// ```
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Applies the given command to the current state and records it in the history.
+    ///
+    /// This is the equivalent of [`Undo::update`] for custom [`Command`] types.
+    /// Note that future [`Undo::redo`] are reset.
+    /// Returns the identifier of the recorded update.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{Command, Undo};
+    /// struct Push(char);
+    ///
+    /// impl Command<String> for Push {
+    ///     fn apply(&self, text: &mut String) {
+    ///         text.push(self.0);
+    ///     }
+    /// }
+    ///
+    /// let mut text = Undo::with_commands(String::new());
+    /// text.apply(Push('H'));
+    /// text.apply(Push('i'));
+    /// assert_eq!(*text, "Hi");
+    /// ```
+    pub fn apply(&mut self, command: TCommand) -> UpdateId {
+        self.apply_entry(self.new_entry(None, command))
+    }
+
+    /// Applies the given command like [`Undo::apply`], attaching a label to the recorded entry.
+    pub fn apply_labeled(&mut self, label: impl Into<String>, command: TCommand) -> UpdateId {
+        self.apply_entry(self.new_entry(Some(label.into()), command))
+    }
+
+    /// Applies the given command like [`Undo::apply`], attaching metadata to the recorded entry.
+    ///
+    /// The metadata can be retrieved with [`Undo::history`].
+    pub fn apply_with_meta(&mut self, meta: TMeta, command: TCommand) -> UpdateId {
+        let mut entry = self.new_entry(None, command);
+        entry.meta = Some(meta);
+        self.apply_entry(entry)
+    }
+
+    /// Applies the given command like [`Undo::apply`], returning the error of the validator if it rejects the update.
+    ///
+    /// # Errors
+    /// Returns [`InvalidState`] if the validator set with [`Undo::set_validator`] rejected the new state,
+    /// in which case the update is rolled back and not recorded.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut percent = Undo::new(50);
+    /// percent.set_validator(Some(|value| {
+    ///     if *value <= 100 { Ok(()) } else { Err(format!("{value} is above 100")) }
+    /// }));
+    ///
+    /// let error = percent.try_apply(Box::new(|value| *value += 60)).unwrap_err();
+    /// assert_eq!(error.reason, "110 is above 100");
+    /// assert_eq!(*percent, 50);
+    /// assert!(!percent.can_undo());
+    /// ```
+    pub fn try_apply(&mut self, command: TCommand) -> Result<UpdateId, InvalidState> {
+        self.try_apply_entry(self.new_entry(None, command))
+    }
+
+    /// Iterates over the recorded updates, from the oldest to the most recent.
+    ///
+    /// Undone updates that can still be redone are included and marked as not applied.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update_labeled("Typing", |text| text.push_str("Hello"));
+    /// text.update(|text| text.push(' '));
+    /// text.update_labeled("Paste", |text| text.push_str("world"));
+    /// text.undo();
+    ///
+    /// let history: Vec<_> = text.history().map(|entry| (entry.label, entry.is_applied)).collect();
+    /// assert_eq!(history, [(Some("Typing"), true), (None, true), (Some("Paste"), false)]);
+    /// ```
+    pub fn history(&self) -> impl Iterator<Item = HistoryEntry<'_, TMeta>> {
+        (0..self.entries.len()).map(|index| self.history_entry(index))
+    }
+
+    /// Iterates over the recorded commands, from the oldest to the most recent, including the undone ones.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{Command, Undo};
+    /// #[derive(Debug, PartialEq)]
+    /// struct Add(i32);
+    ///
+    /// impl Command<i32> for Add {
+    ///     fn apply(&self, value: &mut i32) {
+    ///         *value += self.0;
+    ///     }
+    /// }
+    ///
+    /// let mut counter = Undo::with_commands(0);
+    /// counter.apply(Add(1));
+    /// counter.apply(Add(2));
+    /// counter.undo();
+    ///
+    /// let commands: Vec<&Add> = counter.commands().collect();
+    /// assert_eq!(commands, [&Add(1), &Add(2)]);
+    /// ```
+    pub fn commands(&self) -> impl Iterator<Item = &TCommand> {
+        self.entries.iter().map(|entry| &entry.command)
+    }
+
+    /// Iterates over every state of the history, from the initial state to the state after the most recent update,
+    /// including the undone ones. The state at index `n` is the state after the first `n` updates.
+    ///
+    /// Each state is rebuilt by replaying the history once, without changing the current state.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update(|text| text.push('a'));
+    /// text.update(|text| text.push('b'));
+    /// text.undo();
+    ///
+    /// let states: Vec<String> = text.states().collect();
+    /// assert_eq!(states, ["", "a", "ab"]);
+    /// ```
+    pub fn states(&self) -> impl Iterator<Item = TState> + '_
+    where
+        TState::Snapshot: Clone,
+    {
+        let initial_state = TState::from_snapshot(self.checkpoints[0].1.clone());
+        let mut entries = self.entries.iter();
+        iter::successors(Some(initial_state), move |previous| {
+            let entry = entries.next()?;
+            let mut state = TState::from_snapshot(previous.snapshot());
+            entry.command.apply(&mut state);
+            Some(state)
+        })
+    }
+
+    /// Iterates over the states before and after each recorded update, including the undone ones,
+    /// to show what each step changed.
+    ///
+    /// The states are rebuilt like [`Undo::states`], see [`Undo::for_each_diff`] to avoid snapshotting
+    /// each state twice.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1]);
+    /// list.update(|list| list.push(2));
+    /// list.update(|list| list.retain(|value| *value != 1));
+    ///
+    /// let diffs: Vec<(Vec<i32>, Vec<i32>)> = list.diffs().collect();
+    /// assert_eq!(diffs, [(vec![1], vec![1, 2]), (vec![1, 2], vec![2])]);
+    /// ```
+    pub fn diffs(&self) -> impl Iterator<Item = (TState, TState)> + '_
+    where
+        TState::Snapshot: Clone,
+    {
+        let mut before = TState::from_snapshot(self.checkpoints[0].1.clone());
+        self.entries.iter().map(move |entry| {
+            let mut after = TState::from_snapshot(before.snapshot());
+            entry.command.apply(&mut after);
+            let next = TState::from_snapshot(after.snapshot());
+            (mem::replace(&mut before, next), after)
+        })
+    }
+
+    /// Calls `diff_fn` with each recorded update, including the undone ones, and the states before and after it.
+    ///
+    /// Unlike [`Undo::diffs`], each state is only rebuilt once.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::from("Hello"));
+    /// text.update_labeled("Append", |text| text.push_str(" world"));
+    ///
+    /// let mut panel = Vec::new();
+    /// text.for_each_diff(|entry, before, after| {
+    ///     panel.push(format!("{}: {before:?} -> {after:?}", entry.label.unwrap_or("Edit")));
+    /// });
+    /// assert_eq!(panel, [r#"Append: "Hello" -> "Hello world""#]);
+    /// ```
+    pub fn for_each_diff<'undo>(
+        &'undo self,
+        mut diff_fn: impl FnMut(HistoryEntry<'undo, TMeta>, &TState, &TState),
+    ) where
+        TState::Snapshot: Clone,
+    {
+        let mut states = self.states();
+        let Some(mut before) = states.next() else {
+            return;
+        };
+        for (index, after) in states.enumerate() {
+            diff_fn(self.history_entry(index), &before, &after);
+            before = after;
+        }
+    }
+
+    /// Returns the states like [`Undo::states`], rebuilding them in parallel with `rayon`.
+    ///
+    /// Each segment between two checkpoints is replayed on its own thread from its checkpoint,
+    /// so the speedup depends on the [checkpoint interval](Undo::set_checkpoint_interval).
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new_send(0);
+    /// counter.set_checkpoint_interval(std::num::NonZeroUsize::new(100));
+    /// for _ in 0..1000 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    /// assert_eq!(counter.par_states(), (0..=1000).collect::<Vec<_>>());
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_states(&self) -> Vec<TState>
+    where
+        TState: Send,
+        TState::Snapshot: Clone + Sync,
+        TCommand: Sync,
+        TMeta: Sync,
+    {
+        use rayon::prelude::*;
+
+        let segments: Vec<_> = self
+            .checkpoints
+            .iter()
+            .zip(
+                self.checkpoints
+                    .iter()
+                    .skip(1)
+                    .map(|(position, _)| *position)
+                    .chain(iter::once(self.entries.len() + 1)),
+            )
+            .collect();
+        let entries = &self.entries;
+        let states: Vec<Vec<TState>> = segments
+            .into_par_iter()
+            .map(|((start, snapshot), end)| {
+                let initial_state = TState::from_snapshot(snapshot.clone());
+                let mut entries = entries[*start..end - 1].iter();
+                iter::successors(Some(initial_state), move |previous| {
+                    let entry = entries.next()?;
+                    let mut state = TState::from_snapshot(previous.snapshot());
+                    entry.command.apply(&mut state);
+                    Some(state)
+                })
+                .collect()
+            })
+            .collect();
+        states.into_iter().flatten().collect()
+    }
+
+    /// Undo the last update done to the current state.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// assert_eq!(*counter, 3);
+    ///
+    /// counter.undo();
+    /// assert_eq!(*counter, 1);
+    /// counter.undo();
+    /// assert_eq!(*counter, 0);
+    /// assert!(!counter.undo()); // does nothing
+    /// assert_eq!(*counter, 0);
+    /// ```
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.rewind_to(self.nb_updates - 1)
+    }
+
+    /// Undo the last `n` updates, regenerating the current state only once.
+    ///
+    /// Returns the number of updates actually undone, which is less than `n` if the history is shorter.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1); // 1
+    /// counter.update(|value| *value += 2); // 3
+    /// counter.update(|value| *value += 3); // 6
+    ///
+    /// assert_eq!(counter.undo_n(2), 2);
+    /// assert_eq!(*counter, 1);
+    /// assert_eq!(counter.undo_n(5), 1);
+    /// assert_eq!(*counter, 0);
+    /// ```
+    pub fn undo_n(&mut self, n: usize) -> usize {
+        let nb_undone = n.min(self.nb_updates);
+        if nb_undone == 0 {
+            return 0;
+        }
+        if !self.rewind_to(self.nb_updates - nb_undone) {
+            return 0;
+        }
+        nb_undone
+    }
+
+    /// Undo all updates, going back to the initial state.
+    ///
+    /// Returns the number of updates undone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    ///
+    /// assert_eq!(counter.undo_all(), 2);
+    /// assert_eq!(*counter, 0);
+    /// ```
+    pub fn undo_all(&mut self) -> usize {
+        self.undo_n(self.nb_updates)
+    }
+
+    /// Redo the last update that have been undone using [`Undo::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1); // 1
+    /// counter.update(|value| *value += 2); // 3
+    /// counter.undo(); // 1
+    /// counter.undo(); // 0
+    /// assert_eq!(*counter, 0);
+    ///
+    /// counter.redo();
+    /// assert_eq!(*counter, 1);
+    /// counter.redo();
+    /// assert_eq!(*counter, 3);
+    /// assert!(!counter.redo()); // does nothing
+    /// assert_eq!(*counter, 3);
+    /// ```
+    pub fn redo(&mut self) -> bool {
+        self.redo_n(1) == 1
+    }
+
+    /// Redo the next `n` updates that have been undone using [`Undo::undo`].
+    ///
+    /// Returns the number of updates actually redone, which is less than `n` if fewer were undone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1); // 1
+    /// counter.update(|value| *value += 2); // 3
+    /// counter.update(|value| *value += 3); // 6
+    /// counter.undo_all();
+    ///
+    /// assert_eq!(counter.redo_n(2), 2);
+    /// assert_eq!(*counter, 3);
+    /// assert_eq!(counter.redo_n(5), 1);
+    /// assert_eq!(*counter, 6);
+    /// ```
+    pub fn redo_n(&mut self, n: usize) -> usize {
+        let nb_redone = n.min(self.entries.len() - self.nb_updates);
+        if nb_redone == 0 || !self.intercept(&mut Action::Redo { count: nb_redone }) {
+            return 0;
+        }
+        self.apply_redo(nb_redone);
+        nb_redone
+    }
+
+    /// Redo all updates that have been undone using [`Undo::undo`].
+    ///
+    /// Returns the number of updates redone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.undo_all();
+    ///
+    /// assert_eq!(counter.redo_all(), 2);
+    /// assert_eq!(*counter, 3);
+    /// ```
+    pub fn redo_all(&mut self) -> usize {
+        self.redo_n(self.entries.len() - self.nb_updates)
+    }
+
+    /// Undo the last update like [`Undo::undo`], returning its metadata, if any.
+    ///
+    /// Storing the view state of an editor as metadata, such as the cursor position or the selection
+    /// when the edit was made, allows restoring it along with the content.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// #[derive(Debug, PartialEq)]
+    /// struct Cursor(usize);
+    ///
+    /// let mut text = Undo::with_meta(String::from("Hello"));
+    /// text.update_with_meta(Cursor(5), |text| text.push_str(" world"));
+    /// text.update_with_meta(Cursor(0), |text| text.insert(0, '>'));
+    ///
+    /// assert_eq!(text.undo_with_view(), Some(&Cursor(0)));
+    /// assert_eq!(text.undo_with_view(), Some(&Cursor(5)));
+    /// assert_eq!(text.undo_with_view(), None); // nothing to undo
+    /// assert_eq!(text.redo_with_view(), Some(&Cursor(5)));
+    /// assert_eq!(*text, "Hello world");
+    /// ```
+    pub fn undo_with_view(&mut self) -> Option<&TMeta> {
+        if !self.undo() {
+            return None;
+        }
+        self.entries[self.nb_updates].meta.as_ref()
+    }
+
+    /// Redo the last undone update like [`Undo::redo`], returning its metadata, if any.
+    ///
+    /// See [`Undo::undo_with_view`].
+    pub fn redo_with_view(&mut self) -> Option<&TMeta> {
+        if !self.redo() {
+            return None;
+        }
+        self.entries[self.nb_updates - 1].meta.as_ref()
+    }
+
+    /// Moves to the given history position, as returned by [`Undo::position`], regenerating the state at most once.
+    ///
+    /// Position `0` is the initial state and [`Undo::history_len`] is the state after all recorded updates.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if `position` is greater than [`Undo::history_len`], leaving the state untouched.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1); // 1
+    /// counter.update(|value| *value += 2); // 3
+    /// counter.update(|value| *value += 3); // 6
+    ///
+    /// counter.jump_to(1)?;
+    /// assert_eq!(*counter, 1);
+    /// counter.jump_to(3)?;
+    /// assert_eq!(*counter, 6);
+    /// assert!(counter.jump_to(4).is_err());
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn jump_to(&mut self, position: usize) -> Result<(), OutOfRange> {
+        self.check_position(position)?;
+        if position < self.nb_updates {
+            let _ = self.rewind_to(position);
+        } else {
+            self.redo_n(position - self.nb_updates);
+        }
+        Ok(())
+    }
+
+    /// Moves to the given history position like [`Undo::jump_to`], calling `progress` after each replayed update
+    /// and stopping before the next one once `token` is cancelled.
+    ///
+    /// A cancelled jump leaves the `Undo` at the position reached so far, between the nearest checkpoint
+    /// and `position` when undoing, with its state consistent with this position.
+    ///
+    /// Returns `Ok(false)` if the jump was cancelled or vetoed by a middleware.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if `position` is greater than [`Undo::history_len`], leaving the state untouched.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{CancellationToken, Undo};
+    /// let mut counter = Undo::new(0);
+    /// for _ in 0..100 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    ///
+    /// let token = CancellationToken::new();
+    /// let cancel = token.clone(); // e.g. moved to a "Cancel" button
+    /// let is_done = counter.jump_to_with_progress(99, |progress| {
+    ///     if progress.replayed == 40 {
+    ///         cancel.cancel();
+    ///     }
+    /// }, &token)?;
+    /// assert!(!is_done);
+    /// assert_eq!(counter.position(), 40);
+    /// assert_eq!(*counter, 40);
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn jump_to_with_progress(
+        &mut self,
+        position: usize,
+        mut progress: impl FnMut(ReplayProgress),
+        token: &CancellationToken,
+    ) -> Result<bool, OutOfRange> {
+        self.check_position(position)?;
+        let kind = if position < self.nb_updates {
+            let count = self.nb_updates - position;
+            if !self.intercept(&mut Action::Undo { count }) {
+                return Ok(false);
+            }
+            if self.entries[position..self.nb_updates]
+                .iter()
+                .all(|entry| entry.revert_fn.is_some())
+            {
+                return Ok(self.revert_with_progress(position, progress, token));
+            }
+            // The initial state at position 0 is always found.
+            let index = self
+                .checkpoints
+                .partition_point(|(checkpoint, _)| *checkpoint <= position)
+                - 1;
+            let (start, snapshot) = &self.checkpoints[index];
+            self.current_state.restore(snapshot);
+            self.nb_updates = *start;
+            self.stats.replays += 1;
+            ChangeKind::Undo
+        } else {
+            let count = position - self.nb_updates;
+            if count == 0 || !self.intercept(&mut Action::Redo { count }) {
+                return Ok(count == 0);
+            }
+            ChangeKind::Redo
+        };
+        let start = self.nb_updates;
+        let total = position - start;
+        for replayed in 1..=total {
+            if token.is_cancelled() {
+                break;
+            }
+            self.unwind_safe(|undo| {
+                undo.entries[undo.nb_updates]
+                    .command
+                    .apply(&mut undo.current_state);
+            });
+            self.nb_updates += 1;
+            progress(ReplayProgress { replayed, total });
+        }
+        if kind == ChangeKind::Undo {
+            self.stats.replayed_updates += (self.nb_updates - start) as u64;
+        }
+        if kind == ChangeKind::Undo || self.nb_updates > start {
+            self.notify(kind);
+        }
+        Ok(self.nb_updates == position)
+    }
+
+    /// Undo the updates until the given position by applying their inverses, like [`Undo::jump_to_with_progress`].
+    fn revert_with_progress(
+        &mut self,
+        position: usize,
+        mut progress: impl FnMut(ReplayProgress),
+        token: &CancellationToken,
+    ) -> bool {
+        let total = self.nb_updates - position;
+        for replayed in 1..=total {
+            if token.is_cancelled() {
+                break;
+            }
+            self.nb_updates -= 1;
+            if let Some(revert) = &self.entries[self.nb_updates].revert_fn {
+                revert.apply(&mut self.current_state);
+            }
+            progress(ReplayProgress { replayed, total });
+        }
+        if self.nb_updates < position + total {
+            self.notify(ChangeKind::Undo);
+        }
+        self.nb_updates == position
+    }
+
+    /// Returns `true` if the update with the given identifier is still recorded, applied or not.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::with_limit(0, 1);
+    /// let first = counter.update(|value| *value += 1);
+    /// assert!(counter.is_in_history(first));
+    ///
+    /// counter.update(|value| *value += 2); // folds the first update
+    /// assert!(!counter.is_in_history(first));
+    /// ```
+    #[must_use]
+    pub fn is_in_history(&self, id: UpdateId) -> bool {
+        self.position_of(id).is_some()
+    }
+
+    /// Returns the position right after the update with the given identifier, as used by [`Undo::jump_to`],
+    /// or `None` if it's no longer recorded.
+    #[must_use]
+    pub fn position_of(&self, id: UpdateId) -> Option<usize> {
+        // Entries moved by `Focus` are no longer sorted by identifier.
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(index + 1)
+    }
+
+    /// Undo the most recent updates until the last applied one matches `predicate`.
+    ///
+    /// Returns the number of updates undone, `0` if no applied update matches.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(Vec::new());
+    /// list.update(|list| list.push(1));
+    /// list.update_labeled("Import", |list| list.extend([2, 3]));
+    /// list.update(|list| list.push(4));
+    /// list.update(|list| list.push(5));
+    ///
+    /// assert_eq!(list.undo_until(|entry| entry.label.is_some()), 2);
+    /// assert_eq!(*list, [1, 2, 3]);
+    /// ```
+    pub fn undo_until(
+        &mut self,
+        mut predicate: impl FnMut(HistoryEntry<'_, TMeta>) -> bool,
+    ) -> usize {
+        let Some(index) = (0..self.nb_updates)
+            .rev()
+            .find(|index| predicate(self.history_entry(*index)))
+        else {
+            return 0;
+        };
+        let nb_undone = self.nb_updates - (index + 1);
+        if nb_undone > 0 && !self.rewind_to(index + 1) {
+            return 0;
+        }
+        nb_undone
+    }
+
+    /// Undo the most recent updates until the last applied one has the given label, like [`Undo::undo_until`].
+    ///
+    /// Returns the number of updates undone, `0` if no applied update has this label.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update_labeled("before-import", |text| text.push_str("Hello"));
+    /// text.update(|text| text.push_str(" world"));
+    ///
+    /// assert_eq!(text.undo_to_label("before-import"), 1);
+    /// assert_eq!(*text, "Hello");
+    /// ```
+    pub fn undo_to_label(&mut self, label: &str) -> usize {
+        self.undo_until(|entry| entry.label == Some(label))
+    }
+
+    /// Undo all updates applied after the update with the given identifier, which becomes the last applied one.
+    ///
+    /// Returns the number of updates undone, `0` if the update is not applied or no longer recorded.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// let id = counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
+    ///
+    /// assert_eq!(counter.undo_to_id(id), 2);
+    /// assert_eq!(*counter, 1);
+    /// ```
+    pub fn undo_to_id(&mut self, id: UpdateId) -> usize {
+        let Some(position) = self.position_of(id) else {
+            return 0;
+        };
+        let nb_undone = self.nb_updates.saturating_sub(position);
+        if nb_undone > 0 && !self.rewind_to(position) {
+            return 0;
+        }
+        nb_undone
+    }
+
+    /// Undo the applied update at the given index of the history, keeping the updates made after it.
+    ///
+    /// The entry is removed from the history and the later updates are replayed without it, so they must not
+    /// depend on its changes. The rebased state is replayed aside and checked with the validator set with
+    /// [`Undo::set_validator`], if any, so the history is left unchanged if it's rejected or if an update panics.
+    /// As the state after the entry changes, the saved position and savepoints after it are discarded.
+    ///
+    /// Unlike [`Undo::undo`], the removed update can't be redone.
+    ///
+    /// # Errors
+    /// Returns [`UndoEntryError::NotApplied`] if the entry at the index is not applied,
+    /// [`UndoEntryError::RestoresSnapshot`] if a later update was recorded with [`Undo::modify`],
+    /// [`UndoEntryError::Vetoed`] if a middleware vetoed it,
+    /// or [`UndoEntryError::Conflict`] if the validator rejects the rebased state or an update panics.
+    /// Without `std`, a panicking update can't be caught and is propagated, the history being left unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(Vec::new());
+    /// list.update(|list| list.push("milk"));
+    /// list.update(|list| list.push("eggs"));
+    /// list.update(|list| list.push("bread"));
+    ///
+    /// list.undo_entry(1)?;
+    /// assert_eq!(*list, ["milk", "bread"]);
+    /// assert_eq!(list.history_len(), 2);
+    /// # Ok::<(), simple_undo::UndoEntryError>(())
+    /// ```
+    pub fn undo_entry(&mut self, index: usize) -> Result<(), UndoEntryError> {
+        if index >= self.nb_updates {
+            return Err(UndoEntryError::NotApplied {
+                index,
+                position: self.nb_updates,
+            });
+        }
+        if let Some(offset) = self.entries[index + 1..self.nb_updates]
+            .iter()
+            .position(|entry| entry.restores_snapshot)
+        {
+            return Err(UndoEntryError::RestoresSnapshot {
+                index: index + 1 + offset,
+            });
+        }
+        if !self.intercept(&mut Action::Undo { count: 1 }) {
+            return Err(UndoEntryError::Vetoed);
+        }
+        // The snapshots after the entry include its changes, so the rebased state is replayed from the one before it.
+        let checkpoint = self
+            .checkpoints
+            .partition_point(|(position, _)| *position <= index)
+            - 1;
+        let (start, snapshot) = &self.checkpoints[checkpoint];
+        let start = *start;
+        let replay = || {
+            let mut state = TState::from_snapshot(self.current_state.snapshot());
+            state.restore(snapshot);
+            for (_, entry) in self.entries[start..self.nb_updates]
+                .iter()
+                .enumerate()
+                .filter(|(offset, _)| start + offset != index)
+            {
+                entry.command.apply(&mut state);
+            }
+            state
+        };
+        #[cfg(any(feature = "std", test))]
+        let rebased =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(replay)).map_err(|_| {
+                InvalidState {
+                    reason: String::from("an update panicked while being rebased"),
+                }
+            });
+        #[cfg(not(any(feature = "std", test)))]
+        let rebased = Ok(replay());
+        let rebased = rebased.map_err(UndoEntryError::Conflict)?;
+        if let Some(Err(reason)) = self.validator.map(|validator| validator(&rebased)) {
+            return Err(UndoEntryError::Conflict(InvalidState { reason }));
+        }
+        self.current_state = rebased;
+        self.stats.replays += 1;
+        self.stats.replayed_updates += (self.nb_updates - start - 1) as u64;
+
+        self.checkpoints.truncate(checkpoint + 1);
+        self.entries.remove(index);
+        self.nb_updates -= 1;
+        self.saved_position = self.saved_position.filter(|position| *position <= index);
+        self.savepoints.retain(|(_, position)| *position <= index);
+        self.notify(ChangeKind::Undo);
+        Ok(())
+    }
+
+    /// Creates a new branch of the history, starting from the current state and sharing the applied updates.
+    ///
+    /// Unlike [`Undo::clone`](Clone::clone), the undone updates are not kept in the new branch.
+    /// The recorded commands are cloned, which only clones an [`Arc`] for the [`SharedUpdate`]s of a [`CloneUndo`].
+    /// The observers and the middlewares are not kept either.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new_cloneable(String::from("Hello"));
+    /// text.update(|text| text.push_str(" world"));
+    /// text.update(|text| text.push('!'));
+    /// text.undo();
+    ///
+    /// let mut speculative = text.fork();
+    /// assert!(!speculative.can_redo());
+    /// speculative.update(|text| text.push('?'));
+    /// assert_eq!(*speculative, "Hello world?");
+    /// speculative.undo_all();
+    /// assert_eq!(*speculative, "Hello");
+    /// assert_eq!(*text, "Hello world");
+    /// ```
+    #[must_use]
+    pub fn fork(&self) -> Self
+    where
+        TState::Snapshot: Clone,
+        TCommand: Clone,
+        TMeta: Clone,
+    {
+        self.clone_prefix(self.nb_updates)
+    }
+
+    /// Returns the state as it would be after applying `update_fn`, without touching the current state nor the history.
+    ///
+    /// The update is applied to a [`Snapshot`] of the current state, a clone by default.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::from("draft"));
+    /// let preview = text.dry_run(|text| text.make_ascii_uppercase());
+    /// assert_eq!(preview, "DRAFT");
+    /// assert_eq!(*text, "draft");
+    /// assert!(!text.can_undo());
+    /// ```
+    pub fn dry_run(&self, update_fn: impl FnOnce(&mut TState)) -> TState {
+        let mut state = TState::from_snapshot(self.current_state.snapshot());
+        update_fn(&mut state);
+        state
+    }
+
+    /// Returns the state as it would be after [`Undo::undo`], without undoing anything.
+    ///
+    /// Returns `None` if there is nothing to undo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    ///
+    /// assert_eq!(counter.peek_undo(), Some(1));
+    /// assert_eq!(*counter, 3);
+    /// ```
+    #[must_use]
+    pub fn peek_undo(&self) -> Option<TState>
+    where
+        TState::Snapshot: Clone,
+    {
+        let last = self.entries[..self.nb_updates].last()?;
+        if let Some(revert) = &last.revert_fn {
+            let mut state = TState::from_snapshot(self.current_state.snapshot());
+            revert.apply(&mut state);
+            return Some(state);
+        }
+        Some(self.state_at(self.nb_updates - 1))
+    }
+
+    /// Returns the state as it would be after [`Undo::redo`], without redoing anything.
+    ///
+    /// Returns `None` if there is nothing to redo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.undo();
+    ///
+    /// assert_eq!(counter.peek_redo(), Some(1));
+    /// assert_eq!(*counter, 0);
+    /// ```
+    #[must_use]
+    pub fn peek_redo(&self) -> Option<TState> {
+        let next = self.entries.get(self.nb_updates)?;
+        let mut state = TState::from_snapshot(self.current_state.snapshot());
+        next.command.apply(&mut state);
+        Some(state)
+    }
+
+    /// Mutates the state without recording it in the history, for changes that shouldn't be undone
+    /// like refreshing a cache or moving a cursor.
+    ///
+    /// The mutation is also applied to the initial state and to every checkpoint, so that it survives
+    /// regenerating the state. It must then be independent of the recorded updates: if an update changes
+    /// the same data, undoing it can produce a state that never existed. Observers are notified with
+    /// [`ChangeKind::Unrecorded`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut document = Undo::new((String::new(), 0)); // (text, views)
+    /// document.update(|(text, _)| text.push_str("Hello"));
+    /// document.without_undo(|(_, views)| *views += 1);
+    ///
+    /// document.undo();
+    /// assert_eq!(*document, (String::new(), 1));
+    /// assert!(!document.can_undo());
+    /// ```
+    pub fn without_undo(&mut self, mut mutate_fn: impl FnMut(&mut TState)) {
+        self.unwind_safe(|undo| mutate_fn(&mut undo.current_state));
+        self.apply_to_checkpoints(mutate_fn);
+        self.notify(ChangeKind::Unrecorded);
+    }
+
+    /// Returns `false` if recording is paused with [`Undo::pause_recording`].
+    #[must_use]
+    pub const fn is_recording(&self) -> bool {
+        self.is_recording
+    }
+
+    /// Pauses the recording of updates until [`Undo::resume_recording`] is called.
+    ///
+    /// Updates made meanwhile, like applying changes from a remote sync, are folded into the initial state
+    /// and every checkpoint as with [`Undo::without_undo`], so they are kept when undoing the other updates
+    /// and never appear in the history. Changes made with [`Undo::modify`] fold the whole history instead.
+    /// Observers are notified of them with [`ChangeKind::Unrecorded`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1]);
+    /// list.update(|list| list.push(2));
+    ///
+    /// list.pause_recording();
+    /// list.update(|list| list.insert(0, 0)); // synced from a remote
+    /// list.resume_recording();
+    /// assert_eq!(list.history_len(), 1);
+    ///
+    /// list.undo();
+    /// assert_eq!(*list, [0, 1]);
+    /// ```
+    pub const fn pause_recording(&mut self) {
+        self.is_recording = false;
+    }
+
+    /// Resumes the recording of updates paused with [`Undo::pause_recording`].
+    pub const fn resume_recording(&mut self) {
+        self.is_recording = true;
+    }
+
+    /// Returns how the `Undo` has been used since its creation or the last [`Undo::reset_stats`],
+    /// to learn how users interact with undo.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.undo();
+    /// counter.update(|value| *value += 3);
+    ///
+    /// let stats = counter.stats();
+    /// assert_eq!((stats.updates, stats.undos, stats.redos), (3, 1, 0));
+    /// assert_eq!(stats.discarded_branches, 1);
+    /// assert_eq!(stats.average_replay_len(), Some(1.0));
+    /// ```
+    #[must_use]
+    pub const fn stats(&self) -> UsageStats {
+        self.stats
+    }
+
+    /// Resets the statistics returned by [`Undo::stats`].
+    pub fn reset_stats(&mut self) {
+        self.stats = UsageStats::default();
+    }
+
+    /// Returns the number of recorded updates and snapshots, along with the approximate memory they use.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update_labeled("Add", |value| *value += 1);
+    ///
+    /// let stats = counter.memory_usage();
+    /// assert_eq!((stats.entries, stats.snapshots), (1, 1));
+    /// assert!(stats.approximate_bytes > 0);
+    /// ```
+    #[must_use]
+    pub fn memory_usage(&self) -> MemoryStats {
+        let strings: usize = self
+            .entries
+            .iter()
+            .flat_map(|entry| [&entry.label, &entry.coalesce_key])
+            .flatten()
+            .chain(self.savepoints.iter().map(|(name, _)| name))
+            .map(String::capacity)
+            .sum();
+        MemoryStats {
+            entries: self.entries.len(),
+            snapshots: self.checkpoints.len(),
+            approximate_bytes: self.entries.capacity() * mem::size_of::<Entry<TCommand, TMeta>>()
+                + self.checkpoints.capacity() * mem::size_of::<(usize, TState::Snapshot)>()
+                + self.savepoints.capacity() * mem::size_of::<(String, usize)>()
+                + strings,
+        }
+    }
+
+    /// Shrinks the capacity of the history as much as possible, for example after discarding many updates.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// for _ in 0..100 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    /// counter.undo_all();
+    /// counter.update(|value| *value = 1);
+    ///
+    /// let before = counter.memory_usage().approximate_bytes;
+    /// counter.shrink_to_fit();
+    /// assert!(counter.memory_usage().approximate_bytes < before);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.checkpoints.shrink_to_fit();
+        self.savepoints.shrink_to_fit();
+        self.observers.shrink_to_fit();
+        self.middlewares.shrink_to_fit();
+    }
+
+    /// Discards the whole history, the current state becoming the new initial state.
+    ///
+    /// The saved marker is kept if the current state is the saved one.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    ///
+    /// counter.clear();
+    /// assert_eq!(*counter, 3);
+    /// assert!(!counter.can_undo());
+    /// ```
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.discarded.clear();
+        self.checkpoints.clear();
+        self.checkpoints.push((0, self.current_state.snapshot()));
+        self.saved_position = self
+            .saved_position
+            .filter(|position| *position == self.nb_updates)
+            .map(|_| 0);
+        let position = self.nb_updates;
+        self.savepoints.retain_mut(|(_, savepoint)| {
+            let is_current = *savepoint == position;
+            *savepoint = 0;
+            is_current
+        });
+        self.nb_updates = 0;
+    }
+
+    /// Discards the whole history and goes back to the initial state, which includes the updates
+    /// folded by the [limit](Undo::set_limit).
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.undo();
+    ///
+    /// counter.reset();
+    /// assert_eq!(*counter, 0);
+    /// assert!(!counter.can_redo());
+    /// ```
+    pub fn reset(&mut self) {
+        let changed = self.nb_updates > 0;
+        self.nb_updates = 0;
+        self.truncate(0);
+        self.discarded.clear();
+        if changed {
+            self.regenerate();
+            self.notify(ChangeKind::Undo);
+        }
+    }
+
+    /// Undo the most recent updates until the last applied one was recorded at or before the given time,
+    /// regenerating the state only once.
+    ///
+    /// The updates are usually recorded in chronological order, but the ones moved by [`Undo::focus`] can be
+    /// more recent than the updates after them. The time must come from the clock of the `Undo`,
+    /// see [`Undo::set_clock`].
+    ///
+    /// Returns the number of updates undone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::time::Instant;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// let time = Instant::now();
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
+    ///
+    /// assert_eq!(counter.undo_to(time), 2);
+    /// assert_eq!(*counter, 1);
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn undo_to(&mut self, time: Instant) -> usize {
+        let position = self.entries[..self.nb_updates]
+            .iter()
+            .rposition(|entry| entry.timestamp <= time)
+            .map_or(0, |index| index + 1);
+        self.undo_n(self.nb_updates - position)
+    }
+
+    /// Marks the current state as saved, see [`Undo::is_modified`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.mark_saved();
+    /// assert_eq!(counter.saved_position(), Some(1));
+    /// ```
+    pub const fn mark_saved(&mut self) {
+        self.saved_position = Some(self.nb_updates);
+    }
+
+    /// Returns the position of the state marked as saved with [`Undo::mark_saved`], if it's still in the history.
+    ///
+    /// The initial state is considered saved until [`Undo::mark_saved`] is called.
+    /// Returns `None` once the saved state has been discarded, for example by updating after undoing past it.
+    #[must_use]
+    pub const fn saved_position(&self) -> Option<usize> {
+        self.saved_position
+    }
+
+    /// Returns `true` if the current state differs from the one marked as saved with [`Undo::mark_saved`].
+    ///
+    /// Undoing or redoing back to the saved state makes it unmodified again.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// assert!(!text.is_modified());
+    /// text.update(|text| text.push_str("Hello"));
+    /// assert!(text.is_modified());
+    ///
+    /// text.mark_saved();
+    /// assert!(!text.is_modified());
+    /// text.undo();
+    /// assert!(text.is_modified());
+    /// text.redo();
+    /// assert!(!text.is_modified());
+    ///
+    /// text.undo();
+    /// text.update(|text| text.push_str("World")); // discards the saved state
+    /// text.undo();
+    /// assert!(text.is_modified());
+    /// ```
+    #[must_use]
+    pub fn is_modified(&self) -> bool {
+        self.saved_position != Some(self.nb_updates)
+    }
+
+    /// Returns the number of changes of the current state, incremented by every update, undo and redo,
+    /// including the ones made with [`Undo::without_undo`] or while recording is paused.
+    ///
+    /// Unlike [`Undo::position`], it never goes back, so two different states never have the same version.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.undo();
+    /// assert_eq!(counter.version(), 2);
+    /// ```
+    #[must_use]
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns a token identifying the current version of the state, to invalidate values computed from it.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::from("Hello"));
+    /// let (mut word_count, mut token) = (text.split_whitespace().count(), text.change_token());
+    ///
+    /// text.update(|text| text.push_str(" world"));
+    /// if text.has_changed_since(token) {
+    ///     (word_count, token) = (text.split_whitespace().count(), text.change_token());
+    /// }
+    /// assert_eq!(word_count, 2);
+    /// ```
+    #[must_use]
+    pub const fn change_token(&self) -> ChangeToken {
+        ChangeToken(self.version)
+    }
+
+    /// Returns `true` if the state changed since the given token was returned by [`Undo::change_token`].
+    #[must_use]
+    pub const fn has_changed_since(&self, token: ChangeToken) -> bool {
+        self.version != token.0
+    }
+
+    /// Sets a named savepoint at the current position, which can later be rolled back to
+    /// with [`Undo::rollback_to_savepoint`].
+    ///
+    /// Savepoints don't store any state. Updates made after a savepoint are never merged with the ones before it.
+    /// A savepoint is discarded once its position is no longer in the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1]);
+    /// list.savepoint("before risky operation");
+    /// list.update(|list| list.clear());
+    /// list.update(|list| list.push(2));
+    ///
+    /// assert!(list.rollback_to_savepoint("before risky operation"));
+    /// assert_eq!(*list, [1]);
+    /// ```
+    pub fn savepoint(&mut self, name: impl Into<String>) {
+        self.savepoints.push((name.into(), self.nb_updates));
+    }
+
+    /// Moves back to the most recent savepoint with the given name, releasing the savepoints set after it.
+    ///
+    /// The updates recorded since the savepoint are undone, and can still be redone.
+    /// Returns `false` if there is no savepoint with this name, or if a middleware vetoed the rollback.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// counter.savepoint("outer");
+    /// counter.update(|value| *value += 1);
+    /// counter.savepoint("inner");
+    /// counter.update(|value| *value += 2);
+    ///
+    /// assert!(counter.rollback_to_savepoint("inner"));
+    /// assert_eq!(*counter, 1);
+    /// assert!(counter.rollback_to_savepoint("outer"));
+    /// assert_eq!(*counter, 0);
+    /// assert!(!counter.rollback_to_savepoint("inner")); // released
+    /// ```
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> bool {
+        let Some(index) = self.savepoint_index(name) else {
+            return false;
+        };
+        let position = self.savepoints[index].1;
+        // Savepoints beyond the history are discarded, so the position is always in range.
+        let _ = self.jump_to(position);
+        if self.nb_updates != position {
+            return false;
+        }
+        self.savepoints.truncate(index + 1);
+        true
+    }
+
+    /// Removes the most recent savepoint with the given name, along with the savepoints set after it.
+    ///
+    /// Returns `false` if there is no savepoint with this name.
+    pub fn release_savepoint(&mut self, name: &str) -> bool {
+        let Some(index) = self.savepoint_index(name) else {
+            return false;
+        };
+        self.savepoints.truncate(index);
+        true
+    }
+
+    /// Iterates over the names and positions of the savepoints, from the oldest to the most recent.
+    pub fn savepoints(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.savepoints
+            .iter()
+            .map(|(name, position)| (name.as_str(), *position))
+    }
+
+    /// Returns the index of the most recent savepoint with the given name.
+    fn savepoint_index(&self, name: &str) -> Option<usize> {
+        self.savepoints
+            .iter()
+            .rposition(|(savepoint, _)| savepoint == name)
+    }
+
+    /// Returns `true` if the next update can be merged into the last recorded one.
+    fn can_merge(&self) -> bool {
+        let position = self.nb_updates;
+        self.is_recording
+            && !self.can_redo()
+            && !self
+                .savepoints
+                .iter()
+                .any(|(_, savepoint)| *savepoint == position)
+    }
+
+    /// Moves back to the given position, applying the inverses of the undone updates
+    /// if they all have one, or regenerating the state otherwise.
+    ///
+    /// Returns `false` if a middleware vetoed it.
+    fn rewind_to(&mut self, position: usize) -> bool {
+        let count = self.nb_updates - position;
+        if !self.intercept(&mut Action::Undo { count }) {
+            return false;
+        }
+        self.apply_undo(position);
+        true
+    }
+
+    /// Moves back to the given position like [`Undo::rewind_to`], once accepted by the middlewares.
+    fn apply_undo(&mut self, position: usize) {
+        #[cfg(feature = "tracing")]
+        let count = self.nb_updates - position;
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("undo", count, position);
+        #[cfg(feature = "tracing")]
+        let _guard = span.enter();
+        let undone = &self.entries[position..self.nb_updates];
+        self.nb_updates = position;
+        if undone.iter().all(|entry| entry.revert_fn.is_some()) {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(count, "reverting the updates with their inverses");
+            for revert in undone
+                .iter()
+                .rev()
+                .filter_map(|entry| entry.revert_fn.as_ref())
+            {
+                revert.apply(&mut self.current_state);
+            }
+        } else {
+            self.regenerate();
+        }
+        self.notify(ChangeKind::Undo);
+    }
+
+    /// Redo the next `count` undone updates, once accepted by the middlewares.
+    fn apply_redo(&mut self, count: usize) {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("redo", count);
+        #[cfg(feature = "tracing")]
+        let _guard = span.enter();
+        let redone = self.nb_updates..self.nb_updates + count;
+        self.unwind_safe(|undo| {
+            for entry in &undo.entries[redone] {
+                entry.command.apply(&mut undo.current_state);
+            }
+        });
+        self.nb_updates += count;
+        self.notify(ChangeKind::Redo);
+    }
+
+    /// Regenerates the current state by re-applying the first `nb_updates` updates to the initial state,
+    /// starting from the nearest checkpoint.
+    fn regenerate(&mut self) {
+        // The initial state at position 0 is always found.
+        let index = self
+            .checkpoints
+            .partition_point(|(position, _)| *position <= self.nb_updates)
+            - 1;
+        let (start, snapshot) = &self.checkpoints[index];
+        let start = *start;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            checkpoint = start,
+            replayed = self.nb_updates - start,
+            "regenerating the state"
+        );
+        #[cfg(feature = "time")]
+        let started = self.replay_budget.map(|_| self.clock.now());
+        self.current_state.restore(snapshot);
+        for entry in &self.entries[start..self.nb_updates] {
+            entry.command.apply(&mut self.current_state);
+        }
+        self.stats.replays += 1;
+        self.stats.replayed_updates += (self.nb_updates - start) as u64;
+        #[cfg(feature = "time")]
+        if let Some(started) = started {
+            self.replay_cost = self.clock.now().duration_since(started);
+            if self.is_over_replay_budget() && start < self.nb_updates {
+                self.checkpoints
+                    .insert(index + 1, (self.nb_updates, self.current_state.snapshot()));
+                self.replay_cost = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Panics if replaying the history from the initial state doesn't produce the current state,
+    /// when enabled with [`Undo::set_determinism_check`].
+    #[cfg(debug_assertions)]
+    fn check_determinism(&self) {
+        let Some(is_replayed) = self.is_replayed else {
+            return;
+        };
+        let mut replayed = TState::from_snapshot(self.current_state.snapshot());
+        replayed.restore(&self.checkpoints[0].1);
+        for entry in &self.entries[..self.nb_updates] {
+            entry.command.apply(&mut replayed);
+        }
+        assert!(
+            is_replayed(&replayed, &self.current_state),
+            "replaying the history doesn't produce the current state, the update at position {} is not deterministic",
+            self.nb_updates
+        );
+    }
+
+    /// Returns an error if the position is past the end of the history.
+    const fn check_position(&self, position: usize) -> Result<(), OutOfRange> {
+        if position > self.entries.len() {
+            return Err(OutOfRange {
+                position,
+                history_len: self.entries.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns a view over the entry at the given index.
+    fn history_entry(&self, index: usize) -> HistoryEntry<'_, TMeta> {
+        self.entries[index].view(index < self.nb_updates)
+    }
+
+    /// Rebuilds the state after the first `position` updates, starting from the nearest checkpoint.
+    fn state_at(&self, position: usize) -> TState
+    where
+        TState::Snapshot: Clone,
+    {
+        // The initial state at position 0 is always found.
+        let index = self
+            .checkpoints
+            .partition_point(|(checkpoint, _)| *checkpoint <= position)
+            - 1;
+        let (start, snapshot) = &self.checkpoints[index];
+        let mut state = TState::from_snapshot(snapshot.clone());
+        for entry in &self.entries[*start..position] {
+            entry.command.apply(&mut state);
+        }
+        state
+    }
+
+    /// Runs the given function updating the current state, regenerating the state if it panics.
+    ///
+    /// This leaves the `Undo` in its previous state when an update panics, as long as the recorded updates don't.
+    /// If one of them panics while regenerating the state, it's left at the nearest checkpoint instead.
+    fn unwind_safe<TResult>(&mut self, update: impl FnOnce(&mut Self) -> TResult) -> TResult {
+        let guard = RegenerateOnUnwind(self);
+        let result = update(&mut *guard.0);
+        mem::forget(guard);
+        result
+    }
+
+    /// Creates an entry without inverse nor metadata, timestamped by the clock until it's recorded.
+    #[cfg_attr(
+        not(feature = "time"),
+        allow(clippy::missing_const_for_fn, clippy::unused_self)
+    )]
+    fn new_entry(&self, label: Option<String>, command: TCommand) -> Entry<TCommand, TMeta> {
+        Entry {
+            id: UpdateId(0),
+            command,
+            revert_fn: None,
+            label,
+            meta: None,
+            coalesce_key: None,
+            focus_key: None,
+            restores_snapshot: false,
+            #[cfg(feature = "time")]
+            timestamp: self.clock.now(),
+        }
+    }
+
+    /// Returns a new identifier, greater than the ones of all recorded updates.
+    const fn next_id(&mut self) -> UpdateId {
+        let id = UpdateId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Applies the given entry like [`Undo::try_apply_entry`], ignoring the validation error.
+    fn apply_entry(&mut self, entry: Entry<TCommand, TMeta>) -> UpdateId {
+        self.try_apply_entry(entry)
+            .unwrap_or_else(|_| self.next_id())
+    }
+
+    /// Applies the command of the given entry to the current state, then records it
+    /// unless the state is unchanged according to [`Undo::set_skip_unchanged`],
+    /// or redone according to [`Undo::set_redo_resurrection`].
+    ///
+    /// The update is rolled back if the validator rejects it.
+    fn try_apply_entry(&mut self, entry: Entry<TCommand, TMeta>) -> Result<UpdateId, InvalidState> {
+        let pending = self.prepare_update();
+        #[cfg(feature = "time")]
+        let started = self.replay_budget.map(|_| self.clock.now());
+        self.unwind_safe(|undo| entry.command.apply(&mut undo.current_state));
+        #[cfg(feature = "time")]
+        if let Some(started) = started {
+            self.replay_cost += self.clock.now().duration_since(started);
+        }
+        self.finish_update(pending, entry)
+    }
+
+    /// Captures the states needed by [`Undo::finish_update`], before applying an update to the current state.
+    fn prepare_update(&self) -> PendingUpdate<TState> {
+        let before = (self.is_unchanged.is_some() && self.is_recording)
+            .then(|| TState::from_snapshot(self.current_state.snapshot()));
+        let redone =
+            (self.is_redone.is_some() && self.is_recording && self.can_redo()).then(|| {
+                let mut redone = TState::from_snapshot(self.current_state.snapshot());
+                self.entries[self.nb_updates].command.apply(&mut redone);
+                redone
+            });
+        PendingUpdate { before, redone }
+    }
+
+    /// Checks an update applied to the current state since [`Undo::prepare_update`],
+    /// then records the given entry unless the update is skipped or redone.
+    ///
+    /// The update is rolled back if the validator rejects it.
+    fn finish_update(
+        &mut self,
+        pending: PendingUpdate<TState>,
+        entry: Entry<TCommand, TMeta>,
+    ) -> Result<UpdateId, InvalidState> {
+        let PendingUpdate { before, redone } = pending;
+        self.validate()?;
+        if let Some((before, is_unchanged)) = before.zip(self.is_unchanged) {
+            if is_unchanged(&before, &self.current_state) {
+                return Ok(self.next_id());
+            }
+        }
+        if let Some((redone, is_redone)) = redone.zip(self.is_redone) {
+            if is_redone(&redone, &self.current_state)
+                && self.intercept(&mut Action::Redo { count: 1 })
+            {
+                self.nb_updates += 1;
+                self.notify(ChangeKind::Redo);
+                return Ok(self.entries[self.nb_updates - 1].id);
+            }
+        }
+        Ok(self.record(entry))
+    }
+
+    /// Checks the current state with the validator, regenerating the previous state if it's invalid.
+    fn validate(&mut self) -> Result<(), InvalidState> {
+        let Some(validator) = self.validator else {
+            return Ok(());
+        };
+        validator(&self.current_state).map_err(|reason| {
+            self.regenerate();
+            InvalidState { reason }
+        })
+    }
+
+    /// Records an update already applied to the current state in the history,
+    /// or folds it into the checkpoints if recording is paused.
+    fn record(&mut self, mut entry: Entry<TCommand, TMeta>) -> UpdateId {
+        let id = self.next_id();
+        if !self.intercept(&mut Action::Update {
+            label: &mut entry.label,
+        }) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(label = entry.label, "update vetoed by a middleware");
+            self.regenerate();
+            return id;
+        }
+        if !self.is_recording {
+            if entry.restores_snapshot {
+                // The changes can't be applied to the earlier states, so the current state becomes the initial one.
+                self.truncate(self.nb_updates);
+                self.fold(self.nb_updates);
+                self.checkpoints = vec![(0, self.current_state.snapshot())];
+            } else {
+                self.apply_to_checkpoints(|state| entry.command.apply(state));
+            }
+            self.notify(ChangeKind::Unrecorded);
+            return id;
+        }
+        if self.nb_updates != self.entries.len() {
+            // Discard previous updates when updating after an undo, keeping them aside if requested.
+            if self.redo_policy == RedoPolicy::Keep {
+                let parent = self
+                    .nb_updates
+                    .checked_sub(1)
+                    .map(|index| self.entries[index].id);
+                let entries = self.entries.split_off(self.nb_updates);
+                self.discarded.push(Branch { parent, entries });
+            }
+            self.truncate(self.nb_updates);
+            self.stats.discarded_branches += 1;
+        }
+        entry.id = id;
+        #[cfg(feature = "time")]
+        {
+            entry.timestamp = self.clock.now();
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            position = self.nb_updates + 1,
+            label = entry.label,
+            "update recorded"
+        );
+        self.entries.push(entry);
+        self.nb_updates += 1;
+        #[cfg(debug_assertions)]
+        self.check_determinism();
+        self.take_checkpoint();
+        self.enforce_limit();
+        self.compact();
+        self.enforce_memory_budget();
+        self.notify(ChangeKind::Update);
+        id
+    }
+
+    /// Applies the given mutation to the initial state and every checkpoint.
+    fn apply_to_checkpoints(&mut self, mut mutate_fn: impl FnMut(&mut TState)) {
+        let checkpoints = mem::take(&mut self.checkpoints);
+        self.checkpoints = checkpoints
+            .into_iter()
+            .map(|(position, snapshot)| {
+                let mut state = TState::from_snapshot(snapshot);
+                mutate_fn(&mut state);
+                (position, state.into_snapshot())
+            })
+            .collect();
+    }
+
+    /// Calls the middlewares with the given action, until one of them vetoes it by returning `false`.
+    fn intercept(&mut self, action: &mut Action<'_>) -> bool {
+        let state = &self.current_state;
+        self.middlewares
+            .iter_mut()
+            .all(|middleware| middleware(state, action))
+    }
+
+    /// Calls all observers registered with [`Undo::on_change`].
+    fn notify(&mut self, kind: ChangeKind) {
+        self.version += 1;
+        match kind {
+            ChangeKind::Update => self.stats.updates += 1,
+            ChangeKind::Undo => self.stats.undos += 1,
+            ChangeKind::Redo => self.stats.redos += 1,
+            ChangeKind::Unrecorded => {}
+        }
+        for observer in &mut self.observers {
+            observer(&self.current_state, kind);
+        }
+    }
+
+    /// Discards the recorded updates after the given position, along with their checkpoints.
+    fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+        self.checkpoints.retain(|(position, _)| *position <= len);
+        if self.saved_position > Some(len) {
+            self.saved_position = None;
+        }
+        self.savepoints.retain(|(_, position)| *position <= len);
+    }
+
+    /// Clones the current state and the first `len` entries of the history, without the observers and middlewares.
+    fn clone_prefix(&self, len: usize) -> Self
+    where
+        TState::Snapshot: Clone,
+        TCommand: Clone,
+        TMeta: Clone,
+    {
+        Self {
+            current_state: TState::from_snapshot(self.current_state.snapshot()),
+            entries: self.entries[..len].to_vec(),
+            nb_updates: self.nb_updates,
+            limit: self.limit,
+            compaction_policy: self.compaction_policy,
+            memory_budget: self.memory_budget,
+            checkpoint_interval: self.checkpoint_interval,
+            checkpoints: self
+                .checkpoints
+                .iter()
+                .filter(|(position, _)| *position <= len)
+                .cloned()
+                .collect(),
+            saved_position: self.saved_position.filter(|position| *position <= len),
+            savepoints: self
+                .savepoints
+                .iter()
+                .filter(|(_, position)| *position <= len)
+                .cloned()
+                .collect(),
+            redo_policy: self.redo_policy,
+            discarded: Vec::new(),
+            is_recording: self.is_recording,
+            is_unchanged: self.is_unchanged,
+            is_redone: self.is_redone,
+            is_replayed: self.is_replayed,
+            validator: self.validator,
+            next_id: self.next_id,
+            version: self.version,
+            stats: self.stats,
+            observers: Vec::new(),
+            middlewares: Vec::new(),
+            #[cfg(feature = "time")]
+            grouping_window: self.grouping_window,
+            #[cfg(feature = "time")]
+            replay_budget: self.replay_budget,
+            #[cfg(feature = "time")]
+            replay_cost: self.replay_cost,
+            #[cfg(feature = "time")]
+            clock: Arc::clone(&self.clock),
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Snapshots the current state if enough updates have been recorded since the last checkpoint,
+    /// or if replaying them exceeds the replay budget.
+    fn take_checkpoint(&mut self) {
+        let last_position = self.checkpoints.last().map_or(0, |(position, _)| *position);
+        let is_due = self
+            .checkpoint_interval
+            .is_some_and(|interval| self.nb_updates - last_position >= interval.get());
+        #[cfg(feature = "time")]
+        let is_due = is_due || self.is_over_replay_budget();
+        if is_due && last_position < self.nb_updates {
+            self.checkpoints
+                .push((self.nb_updates, self.current_state.snapshot()));
+            #[cfg(feature = "time")]
+            {
+                self.replay_cost = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Discards the oldest updates exceeding the limit by folding them into the initial state.
+    fn enforce_limit(&mut self) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+        let nb_exceeding = self.entries.len().saturating_sub(limit);
+        self.fold(nb_exceeding.min(self.nb_updates));
+        self.truncate(limit);
+    }
+
+    /// Folds the oldest updates, then discards the most recent undone ones, until the history fits in the memory budget.
+    fn enforce_memory_budget(&mut self) {
+        let Some(budget) = self.memory_budget else {
+            return;
+        };
+        let entry_bytes = |entry: &Entry<TCommand, TMeta>| {
+            mem::size_of::<Entry<TCommand, TMeta>>()
+                + [&entry.label, &entry.coalesce_key]
+                    .into_iter()
+                    .flatten()
+                    .map(String::capacity)
+                    .sum::<usize>()
+                + (budget.command_size)(&entry.command)
+        };
+        let snapshot_bytes = |snapshot: &TState::Snapshot| {
+            mem::size_of::<(usize, TState::Snapshot)>() + (budget.snapshot_size)(snapshot)
+        };
+        let mut bytes = self.entries.iter().map(entry_bytes).sum::<usize>()
+            + self
+                .checkpoints
+                .iter()
+                .map(|(_, snapshot)| snapshot_bytes(snapshot))
+                .sum::<usize>();
+        if bytes <= budget.max_bytes {
+            return;
+        }
+        // The nearest checkpoint replaces the initial state when folding, so its size is an estimate of the new one.
+        let mut checkpoints = self.checkpoints.iter().peekable();
+        let mut initial_bytes = checkpoints
+            .next()
+            .map_or(0, |(_, snapshot)| snapshot_bytes(snapshot));
+        let mut nb_folded = 0;
+        while bytes > budget.max_bytes && nb_folded < self.nb_updates {
+            bytes -= entry_bytes(&self.entries[nb_folded]);
+            nb_folded += 1;
+            if let Some((_, snapshot)) = checkpoints.next_if(|(position, _)| *position <= nb_folded)
+            {
+                bytes -= initial_bytes;
+                initial_bytes = snapshot_bytes(snapshot);
+            }
+        }
+        let mut len = self.entries.len();
+        let mut checkpoints = self.checkpoints.iter().rev().peekable();
+        while bytes > budget.max_bytes && len > self.nb_updates {
+            len -= 1;
+            bytes -= entry_bytes(&self.entries[len]);
+            while let Some((_, snapshot)) = checkpoints.next_if(|(position, _)| *position > len) {
+                bytes -= snapshot_bytes(snapshot);
+            }
+        }
+        self.fold(nb_folded);
+        self.truncate(len - nb_folded);
+    }
+
+    /// Folds the updates exceeding the compaction policy into the initial state.
+    fn compact(&mut self) {
+        let policy = self.compaction_policy;
+        let nb_folded = policy
+            .keep_last
+            .map_or(0, |keep_last| self.nb_updates.saturating_sub(keep_last));
+        #[cfg(feature = "time")]
+        let nb_folded = policy.max_age.map_or(nb_folded, |max_age| {
+            // The timestamps are not sorted once entries are moved by a focus, so only the leading run is expired.
+            let now = self.clock.now();
+            let applied = &self.entries[..self.nb_updates];
+            let nb_expired = applied
+                .iter()
+                .position(|entry| now.duration_since(entry.timestamp) <= max_age)
+                .unwrap_or(applied.len());
+            nb_folded.max(nb_expired)
+        });
+        self.fold(nb_folded);
+    }
+
+    /// Folds the given number of oldest applied updates into the initial state.
+    fn fold(&mut self, nb_folded: usize) {
+        if nb_folded > 0 {
+            // Start folding from the nearest checkpoint, which becomes the initial state.
+            let nb_outdated = self
+                .checkpoints
+                .partition_point(|(position, _)| *position <= nb_folded);
+            let nearest = self.checkpoints.drain(..nb_outdated).last();
+            for (position, _) in &mut self.checkpoints {
+                *position -= nb_folded;
+            }
+            if let Some((start, snapshot)) = nearest {
+                let mut state = TState::from_snapshot(snapshot);
+                for entry in &self.entries[start..nb_folded] {
+                    entry.command.apply(&mut state);
+                }
+                self.checkpoints.insert(0, (0, state.into_snapshot()));
+            }
+            // The branches discarded from the folded states can't be restored anymore.
+            let folded = &self.entries[..nb_folded];
+            self.discarded.retain(|branch| {
+                branch
+                    .parent
+                    .is_some_and(|parent| folded.iter().all(|entry| entry.id != parent))
+            });
+            self.entries.drain(..nb_folded);
+            self.saved_position = self
+                .saved_position
+                .and_then(|position| position.checked_sub(nb_folded));
+            self.savepoints.retain_mut(|(_, position)| {
+                position
+                    .checked_sub(nb_folded)
+                    .map(|shifted| *position = shifted)
+                    .is_some()
+            });
+            self.nb_updates -= nb_folded;
+        }
+    }
+}
+
+impl<TState: Snapshot, TCommand: Command<TState>> Undo<'_, TState, TCommand> {
+    /// Wraps the given state in an `Undo` recording custom [`Command`] types instead of closures.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut wrapper: Undo<i32, fn(&mut i32)> = Undo::with_commands(5);
+    /// ```
+    pub fn with_commands(state: TState) -> Self {
+        Self::wrap(state)
+    }
+}
+
+impl<'state, TState, TCommand, TMeta>
+    Undo<'state, TState, TCommand, Observer<'state, TState>, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+{
+    /// Wraps the given state in an `Undo` attaching metadata of type `TMeta` to the recorded updates,
+    /// with [`Undo::update_with_meta`] or [`Undo::apply_with_meta`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// struct Author(&'static str);
+    ///
+    /// let mut text = Undo::with_meta(String::new());
+    /// text.update_with_meta(Author("alice"), |text| text.push_str("Hello"));
+    /// text.update(|text| text.push('!'));
+    ///
+    /// let authors: Vec<_> = text.history().map(|entry| entry.meta.map(|author| author.0)).collect();
+    /// assert_eq!(authors, [Some("alice"), None]);
+    /// ```
+    pub fn with_meta(state: TState) -> Self {
+        Self::wrap(state)
+    }
+
+    /// Wraps the given state with a [`History`] taken from [`Undo::into_parts`].
+    ///
+    /// The state should be the one the history is at, as returned by `into_parts`
+    /// or rebuilt with [`History::replay`]. Otherwise, undoing and redoing regenerates the states of the history,
+    /// but the updates applied with their inverse are applied to the given state.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::new());
+    /// text.update(|text| text.push_str("draft"));
+    /// let (_, history) = text.into_parts();
+    ///
+    /// let mut copy = String::from("unrelated");
+    /// history.replay(&mut copy);
+    /// let mut text = Undo::from_parts(copy, history);
+    /// assert_eq!(*text, "draft");
+    /// text.undo();
+    /// assert_eq!(*text, "");
+    /// ```
+    pub fn from_parts(state: TState, history: History<'state, TState, TCommand, TMeta>) -> Self {
+        Self {
+            current_state: state,
+            entries: history.entries,
+            nb_updates: history.nb_updates,
+            limit: history.limit,
+            compaction_policy: history.compaction_policy,
+            memory_budget: history.memory_budget,
+            checkpoint_interval: history.checkpoint_interval,
+            checkpoints: history.checkpoints,
+            saved_position: history.saved_position,
+            savepoints: history.savepoints,
+            redo_policy: history.redo_policy,
+            discarded: history.discarded,
+            is_recording: history.is_recording,
+            is_unchanged: history.is_unchanged,
+            is_redone: history.is_redone,
+            is_replayed: history.is_replayed,
+            validator: history.validator,
+            next_id: history.next_id,
+            version: history.version,
+            stats: history.stats,
+            observers: Vec::new(),
+            middlewares: Vec::new(),
+            #[cfg(feature = "time")]
+            grouping_window: history.grouping_window,
+            #[cfg(feature = "time")]
+            replay_budget: history.replay_budget,
+            #[cfg(feature = "time")]
+            replay_cost: history.replay_cost,
+            #[cfg(feature = "time")]
+            clock: history.clock,
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Registers a callback called with the new state after each update, undo or redo,
+    /// and after each change made without recording an update.
+    ///
+    /// Moving several steps at once, with [`Undo::undo_n`] or [`Undo::jump_to`] for example, calls it only once.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{ChangeKind, Undo};
+    /// # use std::cell::RefCell;
+    /// let changes = RefCell::new(Vec::new());
+    /// let mut counter = Undo::new(0);
+    /// counter.on_change(|value, kind| changes.borrow_mut().push((*value, kind)));
+    ///
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.undo_all();
+    /// counter.redo();
+    /// counter.redo(); // notifies after each redo
+    /// counter.redo(); // does nothing
+    ///
+    /// assert_eq!(
+    ///     *changes.borrow(),
+    ///     [
+    ///         (1, ChangeKind::Update),
+    ///         (3, ChangeKind::Update),
+    ///         (0, ChangeKind::Undo),
+    ///         (1, ChangeKind::Redo),
+    ///         (3, ChangeKind::Redo),
+    ///     ]
+    /// );
+    /// ```
+    pub fn on_change(&mut self, observer: impl FnMut(&TState, ChangeKind) + 'state) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Registers `save_fn` to persist the state as it changes, following the given policy,
+    /// so that a crash doesn't lose the changes made since the last save.
+    ///
+    /// The state is only saved when it changes: the history itself can be persisted by serializing
+    /// the whole `Undo` with the `serde` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::{AutosavePolicy, Undo};
+    /// # use std::cell::RefCell;
+    /// # use std::num::NonZeroUsize;
+    /// let saved = RefCell::new(Vec::new());
+    /// let mut counter = Undo::new(0);
+    /// let policy = AutosavePolicy::new().with_every(NonZeroUsize::new(2).unwrap());
+    /// counter.autosave(policy, |value| saved.borrow_mut().push(*value)); // or write to a file
+    ///
+    /// counter.update(|value| *value += 1);
+    /// counter.update(|value| *value += 2);
+    /// counter.update(|value| *value += 3);
+    /// counter.undo();
+    /// assert_eq!(*saved.borrow(), [3, 3]);
+    /// ```
+    pub fn autosave(&mut self, policy: AutosavePolicy, save_fn: impl FnMut(&TState) + 'state)
+    where
+        TState: 'state,
+    {
+        #[cfg(feature = "time")]
+        let observer = policy.observer(Arc::clone(&self.clock), save_fn);
+        #[cfg(not(feature = "time"))]
+        let observer = policy.observer(save_fn);
+        self.on_change(observer);
+    }
+
+    /// Returns a receiver of a [`tokio::sync::watch`] channel publishing the new state after each update,
+    /// undo or redo, so that async tasks can follow the state. Clone it to subscribe more tasks.
+    ///
+    /// The `Undo` owns the sender, closing the channel when dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut counter = Undo::new(0);
+    /// let mut receiver = counter.watch();
+    ///
+    /// counter.update(|value| *value += 1);
+    /// assert!(receiver.has_changed().unwrap());
+    /// assert_eq!(*receiver.borrow_and_update(), 1);
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn watch(&mut self) -> tokio::sync::watch::Receiver<TState>
+    where
+        TState: Clone + 'state,
+    {
+        let (sender, receiver) = tokio::sync::watch::channel(self.current_state.clone());
+        self.on_change(move |state, _| {
+            sender.send_replace(state.clone());
+        });
+        receiver
+    }
+
+    /// Returns the current state published through an [`ArcSwap`](arc_swap::ArcSwap), replaced with the new state
+    /// after each update, undo or redo, so that many threads can read a consistent state without waiting for the updates.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::thread;
+    /// let mut counter = Undo::new(0);
+    /// let published = counter.publish();
+    /// counter.update(|value| *value += 1);
+    ///
+    /// let reader = thread::spawn(move || **published.load());
+    /// assert_eq!(reader.join().unwrap(), 1);
+    /// ```
+    #[cfg(feature = "arc-swap")]
+    pub fn publish(&mut self) -> Arc<arc_swap::ArcSwap<TState>>
+    where
+        TState: Clone + 'state,
+    {
+        let published = Arc::new(arc_swap::ArcSwap::from_pointee(self.current_state.clone()));
+        let current_state = Arc::clone(&published);
+        self.on_change(move |state, _| current_state.store(Arc::new(state.clone())));
+        published
+    }
+}
+
+impl<'state, TState: Snapshot + 'state> SendUndo<'state, TState> {
+    /// Wraps the given state like [`Undo::new`], only accepting updates and observers that are `Send + Sync`
+    /// so that the whole `Undo` can be moved to another thread.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// # use std::thread;
+    /// let mut counter = Undo::new_send(0);
+    /// counter.update(|value| *value += 1);
+    ///
+    /// let mut counter = thread::spawn(move || {
+    ///     counter.update(|value| *value += 2);
+    ///     counter
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// assert_eq!(*counter, 3);
+    /// counter.undo();
+    /// assert_eq!(*counter, 1);
+    /// ```
+    pub fn new_send(state: TState) -> Self {
+        Self::wrap(state)
+    }
+
+    /// Updates the current state like [`Undo::update`].
+    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + Send + Sync + 'state) -> UpdateId {
+        self.apply(Box::new(update_fn))
+    }
+
+    /// Updates the current state like [`Undo::update_labeled`].
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+    ) -> UpdateId {
+        self.apply_labeled(label, Box::new(update_fn))
+    }
+
+    /// Awaits `value`, then updates the current state with it like [`Undo::update_async`].
+    pub async fn update_async<TValue: Send + Sync + 'state>(
+        &mut self,
+        value: impl Future<Output = TValue>,
+        update_fn: impl Fn(&mut TState, &TValue) + Send + Sync + 'state,
+    ) -> UpdateId {
+        let value = value.await;
+        self.update(move |state| update_fn(state, &value))
+    }
+
+    /// Updates the current state like [`Undo::update_with_inverse`].
+    pub fn update_with_inverse(
+        &mut self,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+        revert_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+    ) -> UpdateId {
+        let mut entry = self.new_entry(None, Box::new(update_fn) as SendUpdate<'state, TState>);
+        entry.revert_fn = Some(Box::new(revert_fn));
+        self.apply_entry(entry)
+    }
+
+    /// Registers a callback like [`Undo::on_change`].
+    pub fn on_change(&mut self, observer: impl FnMut(&TState, ChangeKind) + Send + Sync + 'state) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Registers a callback saving the state like [`Undo::autosave`].
+    pub fn autosave(
+        &mut self,
+        policy: AutosavePolicy,
+        save_fn: impl FnMut(&TState) + Send + Sync + 'state,
+    ) {
+        #[cfg(feature = "time")]
+        let observer = policy.observer(Arc::clone(&self.clock), save_fn);
+        #[cfg(not(feature = "time"))]
+        let observer = policy.observer(save_fn);
+        self.on_change(observer);
+    }
+
+    /// Returns a receiver of a channel publishing the new state like [`Undo::watch`].
+    #[cfg(feature = "tokio")]
+    pub fn watch(&mut self) -> tokio::sync::watch::Receiver<TState>
+    where
+        TState: Clone + Send + Sync,
+    {
+        let (sender, receiver) = tokio::sync::watch::channel(self.current_state.clone());
+        self.on_change(move |state, _| {
+            sender.send_replace(state.clone());
+        });
+        receiver
+    }
+
+    /// Returns the current state published through an [`ArcSwap`](arc_swap::ArcSwap) like [`Undo::publish`].
+    #[cfg(feature = "arc-swap")]
+    pub fn publish(&mut self) -> Arc<arc_swap::ArcSwap<TState>>
+    where
+        TState: Clone + Send + Sync,
+    {
+        let published = Arc::new(arc_swap::ArcSwap::from_pointee(self.current_state.clone()));
+        let current_state = Arc::clone(&published);
+        self.on_change(move |state, _| current_state.store(Arc::new(state.clone())));
+        published
+    }
+}
+
+impl<'state, TState: Snapshot + 'state> CloneUndo<'state, TState> {
+    /// Wraps the given state like [`Undo::new`], recording the updates in an [`Arc`] so that the whole `Undo`
+    /// can be cloned, for example to fork an editing session.
+    ///
+    /// The updates must be `Send + Sync`, like the ones of a [`SendUndo`].
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new_cloneable(String::from("v1"));
+    /// text.update(|text| text.push_str(", edited"));
+    ///
+    /// let mut previous = text.clone();
+    /// previous.undo();
+    /// assert_eq!(*previous, "v1");
+    /// assert_eq!(*text, "v1, edited");
+    /// ```
+    pub fn new_cloneable(state: TState) -> Self {
+        Self::with_commands(state)
+    }
+
+    /// Updates the current state like [`Undo::update`].
+    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + Send + Sync + 'state) -> UpdateId {
+        self.apply(SharedUpdate(Arc::new(update_fn)))
+    }
+
+    /// Updates the current state like [`Undo::update_labeled`].
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+    ) -> UpdateId {
+        self.apply_labeled(label, SharedUpdate(Arc::new(update_fn)))
+    }
+
+    /// Updates the current state like [`Undo::update_with_inverse`].
+    pub fn update_with_inverse(
+        &mut self,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+        revert_fn: impl Fn(&mut TState) + Send + Sync + 'state,
+    ) -> UpdateId {
+        let mut entry = self.new_entry(None, SharedUpdate(Arc::new(update_fn)));
+        entry.revert_fn = Some(SharedUpdate(Arc::new(revert_fn)));
+        self.apply_entry(entry)
+    }
+}
+
+/// Regenerates the state of the `Undo` when dropped, which only happens if an update panics.
+struct RegenerateOnUnwind<'undo, 'state, TState, TCommand, TObserver, TMeta>(
+    &'undo mut Undo<'state, TState, TCommand, TObserver, TMeta>,
+)
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind);
+
+impl<TState, TCommand, TObserver, TMeta> Drop
+    for RegenerateOnUnwind<'_, '_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    fn drop(&mut self) {
+        #[cfg(any(feature = "std", test))]
+        {
+            let undo = &mut *self.0;
+            let replay =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| undo.regenerate()));
+            if replay.is_err() {
+                // A recorded update panicked again, so the state is left at the nearest checkpoint,
+                // undoing the updates after it instead of aborting on a panic while panicking.
+                let index = undo
+                    .checkpoints
+                    .partition_point(|(position, _)| *position <= undo.nb_updates)
+                    - 1;
+                let (position, snapshot) = &undo.checkpoints[index];
+                undo.current_state.restore(snapshot);
+                undo.nb_updates = *position;
+            }
+        }
+        #[cfg(not(any(feature = "std", test)))]
+        self.0.regenerate();
+    }
+}
+
+/// A group of updates applied with [`Undo::transact`], recorded as a single entry of the history.
+///
+/// It derefs to the state being updated.
+pub struct Transaction<'undo, 'state, TState> {
+    /// The current state of the [`Undo`], updated in place.
+    state: &'undo mut TState,
+    /// All updates applied during the transaction.
+    updates: Vec<Update<'state, TState>>,
+    /// The label of the resulting entry.
+    label: Option<String>,
+}
+
+impl<'state, TState> Transaction<'_, 'state, TState> {
+    /// Updates the state with the given mutating function, as part of the transaction.
+    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + 'state) {
+        update_fn(self.state);
+        self.updates.push(Box::new(update_fn));
+    }
+
+    /// Sets the label of the entry recorded for the whole transaction.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+}
+
+impl<TState> Deref for Transaction<'_, '_, TState> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        self.state
+    }
+}
+
+/// A guard returned by [`Undo::modify`], recording the changes made to the state as a single update when dropped.
+///
+/// It derefs mutably to the current state. If a panic happens while the guard is alive, or if the validator set with
+/// [`Undo::set_validator`] rejects the changes, the previous state is restored and nothing is recorded.
+pub struct ModifyGuard<'undo, 'state, TState: Snapshot + 'state, TMeta = ()>
+where
+    TState::Snapshot: 'state,
+{
+    /// The `Undo` whose state is modified.
+    undo: &'undo mut Undo<'state, TState, Update<'state, TState>, Observer<'state, TState>, TMeta>,
+    /// A snapshot of the state before the changes, taken out when dropped.
+    before: Option<TState::Snapshot>,
+    /// The label of the recorded update.
+    label: Option<String>,
+    /// The metadata of the recorded update.
+    meta: Option<TMeta>,
+}
+
+impl<TState: Snapshot, TMeta> ModifyGuard<'_, '_, TState, TMeta> {
+    /// Sets the label of the update recorded for the changes.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
+    /// Sets the metadata of the update recorded for the changes.
+    pub fn set_meta(&mut self, meta: TMeta) {
+        self.meta = Some(meta);
+    }
+}
+
+impl<TState: Snapshot, TMeta> Deref for ModifyGuard<'_, '_, TState, TMeta> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.undo.current_state
+    }
+}
+
+impl<TState: Snapshot, TMeta> DerefMut for ModifyGuard<'_, '_, TState, TMeta> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.undo.current_state
+    }
+}
+
+impl<'state, TState: Snapshot + 'state, TMeta> Drop for ModifyGuard<'_, 'state, TState, TMeta>
+where
+    TState::Snapshot: 'state,
+{
+    fn drop(&mut self) {
+        let Some(before) = self.before.take() else {
+            return;
+        };
+        let is_invalid = self
+            .undo
+            .validator
+            .is_some_and(|validator| validator(&self.undo.current_state).is_err());
+        if is_panicking() || is_invalid {
+            self.undo.current_state.restore(&before);
+            return;
+        }
+        let after = self.undo.current_state.snapshot();
+        let update_fn = move |state: &mut TState| state.restore(&after);
+        let mut entry = self.undo.new_entry(
+            self.label.take(),
+            Box::new(update_fn) as Update<'state, TState>,
+        );
+        entry.revert_fn = Some(Box::new(move |state: &mut TState| state.restore(&before)));
+        entry.meta = self.meta.take();
+        entry.restores_snapshot = true;
+        self.undo.record(entry);
+    }
+}
+
+/// Returns `true` if the current thread is unwinding, which never happens without `std`.
+#[cfg_attr(not(any(feature = "std", test)), allow(clippy::missing_const_for_fn))]
+fn is_panicking() -> bool {
+    #[cfg(any(feature = "std", test))]
+    return std::thread::panicking();
+    #[cfg(not(any(feature = "std", test)))]
+    false
+}
+
+impl<TState: Snapshot, TCommand, TObserver, TMeta> Deref
+    for Undo<'_, TState, TCommand, TObserver, TMeta>
+{
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_state
+    }
+}
+
+impl<TState: Snapshot, TCommand, TObserver, TMeta> AsRef<TState>
+    for Undo<'_, TState, TCommand, TObserver, TMeta>
+{
+    fn as_ref(&self) -> &TState {
+        &self.current_state
+    }
+}
+
+/// Clones the current state and the history, sharing the recorded commands if they are [`SharedUpdate`]s.
+///
+/// The observers and the middlewares are not cloned.
+impl<TState, TCommand, TObserver, TMeta> Clone for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TState::Snapshot: Clone,
+    TCommand: Command<TState> + Clone,
+    TObserver: FnMut(&TState, ChangeKind),
+    TMeta: Clone,
+{
+    fn clone(&self) -> Self {
+        let mut undo = self.clone_prefix(self.entries.len());
+        undo.discarded.clone_from(&self.discarded);
+        undo
+    }
+}
+
+/// Compares the current state, ignoring the history.
+///
+/// # Example
+/// ```
+/// # use simple_undo::Undo;
+/// let mut list = Undo::from(vec![1]);
+/// list.update(|list| list.push(2));
+/// assert_eq!(list, vec![1, 2]);
+/// ```
+impl<TState, TCommand, TObserver, TMeta> PartialEq<TState>
+    for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot + PartialEq,
+{
+    fn eq(&self, other: &TState) -> bool {
+        self.current_state == *other
+    }
+}
+
+impl<'state, TState: Snapshot + Default + 'state> Default for Undo<'state, TState> {
+    /// Wraps the default state in an `Undo`, see [`Undo::new`].
+    fn default() -> Self {
+        Self::new(TState::default())
+    }
+}
+
+impl<'state, TState: Snapshot + 'state> From<TState> for Undo<'state, TState> {
+    /// Wraps the given state in an `Undo`, see [`Undo::new`].
+    fn from(state: TState) -> Self {
+        Self::new(state)
+    }
+}
+
+/// Summarizes the history along with the current state, as the recorded updates can't be printed.
+///
+/// # Example
+/// ```
+/// # use simple_undo::Undo;
+/// let mut counter = Undo::new(0);
+/// counter.update_labeled("add 1", |value| *value += 1);
+/// counter.update(|value| *value += 2);
+/// counter.undo();
+/// assert_eq!(
+///     format!("{counter:?}"),
+///     r#"Undo { state: 1, history_len: 2, position: 1, labels: [Some("add 1"), None], .. }"#,
+/// );
+/// ```
+impl<TState, TCommand, TObserver, TMeta> fmt::Debug for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Undo");
+        debug
+            .field("state", &self.current_state)
+            .field("history_len", &self.entries.len())
+            .field("position", &self.nb_updates);
+        if self.entries.iter().any(|entry| entry.label.is_some()) {
+            let labels: Vec<_> = self
+                .entries
+                .iter()
+                .map(|entry| entry.label.as_deref())
+                .collect();
+            debug.field("labels", &labels);
+        }
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// An object-safe view over an [`Undo`], whatever its state and command types.
+///
+/// It allows managing several histories together, as done by [`UndoGroup`](group::UndoGroup).
+pub trait AnyUndo {
+    /// See [`Undo::undo`].
+    fn undo(&mut self) -> bool;
+    /// See [`Undo::redo`].
+    fn redo(&mut self) -> bool;
+    /// See [`Undo::can_undo`].
+    fn can_undo(&self) -> bool;
+    /// See [`Undo::can_redo`].
+    fn can_redo(&self) -> bool;
+}
+
+impl<TState, TCommand, TObserver, TMeta> AnyUndo for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    struct Counter {
+        count: u64,
+    }
+
+    #[test]
+    fn it_can_undo_and_redo_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        assert_eq!(counter.count, 0);
+        counter.update(|c| c.count = 5);
+        assert_eq!(counter.count, 5);
+        counter.update(|c| c.count += 3);
+        assert_eq!(counter.count, 8);
+
+        counter.undo();
+        assert_eq!(counter.count, 5);
+        counter.undo();
+        assert_eq!(counter.count, 0);
+
+        counter.redo();
+        assert_eq!(counter.count, 5);
+        counter.redo();
+        assert_eq!(counter.count, 8);
+    }
+
+    #[test]
+    fn it_does_nothing_on_too_many_undo_or_redo() {
+        let mut counter = Undo::new(Counter { count: 3 });
+        counter.undo();
+        assert_eq!(counter.count, 3);
+        counter.redo();
+        assert_eq!(counter.count, 3);
+        counter.update(|c| c.count = 8);
+        assert_eq!(counter.count, 8);
+        counter.undo();
+        counter.undo();
+        counter.undo();
+        assert_eq!(counter.count, 3);
+        counter.redo();
+        counter.redo();
+        counter.redo();
+        counter.redo();
+        assert_eq!(counter.count, 8);
+    }
+
+    #[test]
+    fn it_reports_whether_undo_or_redo_happened() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        assert!(!counter.undo());
+        assert!(!counter.redo());
+        counter.update(|c| c.count += 1);
+        assert!(!counter.redo());
+        assert!(counter.undo());
+        assert!(!counter.undo());
+        assert!(counter.redo());
+        assert!(!counter.redo());
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_several_updates_at_once() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        assert_eq!(counter.undo_n(0), 0);
+        assert_eq!(counter.count, 5);
+        assert_eq!(counter.undo_n(3), 3);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.redo_n(1), 1);
+        assert_eq!(counter.count, 3);
+        assert_eq!(counter.undo_all(), 3);
+        assert_eq!(counter.count, 0);
+        assert_eq!(counter.undo_all(), 0);
+        assert_eq!(counter.redo_all(), 5);
+        assert_eq!(counter.count, 5);
+        assert_eq!(counter.redo_n(2), 0);
+        assert_eq!(counter.position(), 5);
+    }
+
+    #[test]
+    fn it_jumps_to_any_history_position() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        assert_eq!(counter.jump_to(2), Ok(()));
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.jump_to(4), Ok(()));
+        assert_eq!(counter.count, 4);
+        assert_eq!(counter.jump_to(0), Ok(()));
+        assert_eq!(counter.count, 0);
+        assert_eq!(
+            counter.jump_to(6),
+            Err(OutOfRange {
+                position: 6,
+                history_len: 5
+            })
+        );
+        assert_eq!(counter.count, 0);
+        assert_eq!(counter.history_len(), 5);
+    }
+
+    #[test]
+    fn it_lists_labeled_updates_in_history() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update_labeled("first", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.update_labeled(String::from("third"), |c| c.count += 1);
+        counter.undo();
+        assert_eq!(counter.count, 2);
+
+        let labels: Vec<_> = counter.history().map(|entry| entry.label).collect();
+        assert_eq!(labels, [Some("first"), None, Some("third")]);
+        let applied: Vec<_> = counter.history().map(|entry| entry.is_applied).collect();
+        assert_eq!(applied, [true, true, false]);
+
+        counter.update_labeled("replaced", |c| c.count += 10);
+        let labels: Vec<_> = counter.history().map(|entry| entry.label).collect();
+        assert_eq!(labels, [Some("first"), None, Some("replaced")]);
+    }
+
+    #[test]
+    fn it_undoes_a_transaction_as_a_single_update() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.transact(|tx| {
+            tx.update(|c| c.count += 2);
+            tx.update(|c| c.count *= 10);
+            assert_eq!(tx.count, 30);
+        });
+        assert_eq!(counter.count, 30);
+        assert_eq!(counter.history_len(), 2);
+
+        counter.undo();
+        assert_eq!(counter.count, 1);
+        counter.redo();
+        assert_eq!(counter.count, 30);
+    }
+
+    #[test]
+    fn it_records_nothing_for_an_empty_transaction() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.undo();
+        counter.transact(|_| {});
+        assert!(counter.can_redo());
+        assert_eq!(counter.history_len(), 1);
+    }
+
+    #[test]
+    fn it_folds_the_oldest_updates_beyond_the_limit() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 3);
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        assert_eq!(counter.count, 5);
+        assert_eq!(counter.history_len(), 3);
+        assert_eq!(counter.undo_all(), 3);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.redo_all(), 3);
+        assert_eq!(counter.count, 5);
+
+        counter.set_limit(None);
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        assert_eq!(counter.history_len(), 8);
+    }
+
+    #[test]
+    fn it_discards_undone_updates_when_the_limit_is_lowered() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.undo_n(3); // 2
+        counter.set_limit(Some(1));
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 1);
+        assert_eq!(counter.position(), 0);
+        assert!(counter.redo());
+        assert_eq!(counter.count, 3);
+    }
+
+    #[test]
+    fn it_undoes_from_checkpoints() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        let positions: Vec<_> = counter
+            .checkpoints
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        assert_eq!(positions, [0, 2, 4]);
+
+        for expected in (0..5).rev() {
+            counter.undo();
+            assert_eq!(counter.count, expected);
+        }
+        counter.redo_n(3);
+        counter.update(|c| c.count += 10); // discard the checkpoint at 4
+        let positions: Vec<_> = counter
+            .checkpoints
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        assert_eq!(positions, [0, 2, 4]);
+        assert_eq!(counter.checkpoints[2].1.count, 13);
+        counter.undo();
+        assert_eq!(counter.count, 3);
+    }
+
+    #[test]
+    fn it_uses_custom_snapshots() {
+        /// A state that can't be cloned, snapshotted as its count.
+        struct Resource {
+            count: u64,
+        }
+
+        impl Snapshot for Resource {
+            type Snapshot = u64;
+
+            fn snapshot(&self) -> u64 {
+                self.count
+            }
+
+            fn restore(&mut self, snapshot: &u64) {
+                self.count = *snapshot;
+            }
+
+            fn from_snapshot(snapshot: u64) -> Self {
+                Self { count: snapshot }
+            }
+        }
+
+        let mut resource = Undo::with_limit(Resource { count: 0 }, 3);
+        resource.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..7 {
+            resource.update(|r| r.count += 1);
+        }
+        assert_eq!(resource.checkpoints, [(0, 4), (2, 6)]);
+        resource.undo();
+        assert_eq!(resource.count, 6);
+        resource.undo_all();
+        assert_eq!(resource.count, 4);
+        resource.redo_all();
+        assert_eq!(resource.unwrap().count, 7);
+    }
+
+    #[test]
+    fn it_folds_checkpoints_beyond_the_limit() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 3);
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..7 {
+            counter.update(|c| c.count += 1);
+        }
+        let positions: Vec<_> = counter
+            .checkpoints
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        assert_eq!(positions, [0, 2]);
+        assert_eq!(counter.checkpoints[1].1.count, 6);
+        assert_eq!(counter.checkpoints[0].1.count, 4);
+        counter.undo_n(2);
+        assert_eq!(counter.count, 5);
+        counter.undo_all();
+        assert_eq!(counter.count, 4);
+    }
+
+    #[test]
+    fn it_undoes_with_inverses_when_available() {
+        use std::cell::Cell;
+
+        let nb_replays = Cell::new(0);
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| {
+            nb_replays.set(nb_replays.get() + 1);
+            c.count += 1;
+        });
+        counter.update_with_inverse(|c| c.count *= 10, |c| c.count /= 10);
+        counter.update_with_inverse(|c| c.count += 5, |c| c.count -= 5);
+        assert_eq!(counter.count, 15);
+        assert_eq!(nb_replays.get(), 1);
+
+        counter.undo_n(2);
+        assert_eq!(counter.count, 1);
+        assert_eq!(nb_replays.get(), 1); // only inverses were applied
+        counter.redo_all();
+        assert_eq!(counter.count, 15);
+
+        counter.undo_all(); // regenerates because the first update has no inverse
+        assert_eq!(counter.count, 0);
+        assert_eq!(nb_replays.get(), 1);
+        counter.redo_all();
+        assert_eq!(counter.count, 15);
+        assert_eq!(nb_replays.get(), 2);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum CounterCommand {
+        Set(u64),
+        Add(u64),
+    }
+
+    impl Command<Counter> for CounterCommand {
+        fn apply(&self, counter: &mut Counter) {
+            match self {
+                Self::Set(count) => counter.count = *count,
+                Self::Add(count) => counter.count += count,
+            }
+        }
+    }
+
+    #[test]
+    fn it_records_typed_commands() {
+        let mut counter = Undo::with_commands(Counter { count: 0 });
+        counter.apply(CounterCommand::Set(5));
+        counter.apply_labeled("add", CounterCommand::Add(3));
+        counter.apply(CounterCommand::Add(1));
+        assert_eq!(counter.count, 9);
+
+        counter.undo_n(2);
+        assert_eq!(counter.count, 5);
+        counter.redo();
+        assert_eq!(counter.count, 8);
+        counter.apply(CounterCommand::Add(10));
+        assert_eq!(counter.count, 18);
+
+        let commands: Vec<_> = counter.commands().collect();
+        assert_eq!(
+            commands,
+            [
+                &CounterCommand::Set(5),
+                &CounterCommand::Add(3),
+                &CounterCommand::Add(10)
+            ]
+        );
+        let labels: Vec<_> = counter.history().map(|entry| entry.label).collect();
+        assert_eq!(labels, [None, Some("add"), None]);
+    }
+
+    #[test]
+    fn it_rolls_back_failed_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.undo();
+
+        let result = counter.try_update(|c| {
+            c.count = 100;
+            Err(c.count)
+        });
+        assert_eq!(result, Err(100));
+        assert_eq!(counter.count, 1);
+        assert!(counter.can_redo());
+
+        let result = counter.try_update(|c| {
+            c.count += 10;
+            Ok::<(), u64>(())
+        });
+        assert_eq!(result.map(|id| counter.position_of(id)), Ok(Some(2)));
+        assert_eq!(counter.count, 11);
+        assert!(!counter.can_redo());
+        counter.undo();
+        counter.redo();
+        assert_eq!(counter.count, 11);
+    }
+
+    #[test]
+    fn it_restores_the_state_when_an_update_panics() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.undo();
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            counter.update(|c| {
+                c.count = 100;
+                panic!("update failed");
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(counter.count, 1);
+        assert_eq!(counter.history_len(), 2);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            counter.transact(|tx| {
+                tx.update(|c| c.count = 50);
+                panic!("transaction failed");
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(counter.count, 1);
+        assert_eq!(counter.history_len(), 2);
+
+        counter.redo();
+        assert_eq!(counter.count, 2);
+    }
+
+    #[test]
+    fn it_tracks_the_saved_position() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 3);
+        assert!(!counter.is_modified());
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.mark_saved();
+        assert_eq!(counter.saved_position(), Some(2));
+
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1); // folds the first update
+        assert_eq!(counter.saved_position(), Some(1));
+        assert!(counter.is_modified());
+        counter.undo_n(2);
+        assert!(!counter.is_modified());
+
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.saved_position(), Some(0));
+        counter.update(|c| c.count += 1); // folds the saved state
+        assert_eq!(counter.saved_position(), None);
+        counter.undo_all();
+        assert!(counter.is_modified());
+    }
+
+    #[test]
+    fn it_moves_send_undo_across_threads() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut counter = Undo::new_send(Counter { count: 0 });
+        let log = Arc::clone(&changes);
+        counter.on_change(move |c, kind| log.lock().unwrap().push((c.count, kind)));
+        counter.update_labeled("add", |c| c.count += 1);
+        assert_send_sync(&counter);
+
+        let mut counter = thread::spawn(move || {
+            counter.update_with_inverse(|c| c.count *= 10, |c| c.count /= 10);
+            counter
+        })
+        .join()
+        .unwrap();
+        counter.undo();
+        assert_eq!(counter.count, 1);
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [
+                (1, ChangeKind::Update),
+                (10, ChangeKind::Update),
+                (1, ChangeKind::Undo)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_replays_awaited_updates_with_the_resolved_value() {
+        fn assert_send<T: Send>(value: T) -> T {
+            value
+        }
+
+        let mut counter = Undo::new_send(Counter { count: 0 });
+        let resolved = assert_send(counter.update_async(async { 5 }, |c, value| c.count += value));
+        pollster::block_on(resolved);
+        counter.update(|c| c.count *= 2);
+        assert_eq!(counter.count, 10);
+        counter.undo();
+        assert_eq!(counter.count, 5);
+    }
+
+    #[test]
+    fn it_peeks_adjacent_states_without_moving() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        assert!(counter.peek_undo().is_none());
+        for _ in 0..3 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.update_with_inverse(|c| c.count *= 10, |c| c.count /= 10);
+        assert_eq!(counter.peek_undo().map(|c| c.count), Some(3));
+        counter.undo();
+        assert_eq!(counter.peek_undo().map(|c| c.count), Some(2));
+        assert_eq!(counter.peek_redo().map(|c| c.count), Some(30));
+        assert_eq!(counter.count, 3);
+        counter.redo();
+        assert!(counter.peek_redo().is_none());
+    }
+
+    #[test]
+    fn it_iterates_over_all_states() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 3);
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.undo();
+        let counts: Vec<_> = counter.states().map(|c| c.count).collect();
+        assert_eq!(counts, [2, 3, 4, 5]);
+        assert_eq!(counter.count, 4);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn it_rebuilds_the_states_in_parallel() {
+        let mut counter = Undo::new_send(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(7));
+        for i in 0..100 {
+            counter.update(move |c| c.count += i);
+        }
+        counter.undo_n(10);
+        let states: Vec<_> = counter.states().map(|c| c.count).collect();
+        let par_states: Vec<_> = counter.par_states().iter().map(|c| c.count).collect();
+        assert_eq!(par_states.len(), 101);
+        assert_eq!(par_states, states);
+    }
+
+    #[test]
+    fn it_clears_or_resets_the_history() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 2);
+        counter.set_checkpoint_interval(NonZeroUsize::new(1));
+        for _ in 0..3 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.mark_saved();
+        counter.clear();
+        assert_eq!(
+            (counter.history_len(), counter.saved_position()),
+            (0, Some(0))
+        );
+        counter.update(|c| c.count += 10);
+        counter.undo();
+        assert_eq!(counter.count, 3);
+
+        counter.redo();
+        counter.update(|c| c.count += 1);
+        counter.reset();
+        assert_eq!(counter.count, 3);
+        assert_eq!(
+            (counter.history_len(), counter.saved_position()),
+            (0, Some(0))
+        );
+        assert_eq!(counter.checkpoints.len(), 1);
+    }
+
+    #[test]
+    fn it_squashes_a_range_of_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            counter.update_with_inverse(|c| c.count += 1, |c| c.count -= 1);
+        }
+        counter.update(|c| c.count *= 10);
+        counter.mark_saved();
+        counter.undo_n(4); // 2
+
+        assert!(counter.squash_labeled(1..6, "Import").is_ok());
+        assert_eq!(counter.count, 50);
+        assert_eq!(counter.history_len(), 2);
+        assert_eq!(counter.position(), 2);
+        assert_eq!(counter.saved_position(), Some(2));
+        let positions: Vec<_> = counter.checkpoints.iter().map(|(p, _)| *p).collect();
+        assert_eq!(positions, [0, 2]);
+        assert_eq!(counter.history().nth(1).unwrap().label, Some("Import"));
+
+        counter.undo();
+        assert_eq!(counter.count, 1);
+        counter.redo();
+        assert_eq!(counter.count, 50);
+        assert!(counter.squash(1..3).is_err());
+
+        // Squashing updates with inverses keeps a composite inverse.
+        counter.update_with_inverse(|c| c.count += 1, |c| c.count -= 1);
+        counter.update_with_inverse(|c| c.count *= 2, |c| c.count /= 2);
+        assert!(counter.squash(2..4).is_ok());
+        assert!(counter.entries[2].revert_fn.is_some());
+        counter.undo();
+        assert_eq!(counter.count, 50);
+    }
+
+    #[test]
+    fn it_mutates_without_recording() {
+        let mut counter = Undo::new((Counter { count: 0 }, 0));
+        counter.set_checkpoint_interval(NonZeroUsize::new(1));
+        counter.update(|(c, _)| c.count += 1);
+        counter.update(|(c, _)| c.count += 1);
+        counter.undo();
+        counter.without_undo(|(_, cursor)| *cursor = 7);
+        assert_eq!(counter.history_len(), 2);
+
+        counter.redo();
+        counter.undo_all();
+        assert_eq!((counter.0.count, counter.1), (0, 7));
+        assert!(counter
+            .checkpoints
+            .iter()
+            .all(|(_, (_, cursor))| *cursor == 7));
+    }
+
+    #[test]
+    fn it_folds_updates_made_while_paused() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.pause_recording();
+        assert!(!counter.is_recording());
+        counter.update_coalesced("add", |c| c.count += 10);
+        counter.transact(|tx| tx.update(|c| c.count += 100));
+        counter.resume_recording();
+        counter.update_coalesced("add", |c| c.count += 1000);
+
+        assert_eq!(counter.count, 1111);
+        assert_eq!(counter.history_len(), 1);
+        counter.undo();
+        assert_eq!(counter.count, 110);
+        counter.redo();
+        assert_eq!(counter.count, 1111);
+    }
+
+    #[test]
+    fn it_notifies_the_unrecorded_changes() {
+        use std::cell::RefCell;
+
+        let changes = RefCell::new(Vec::new());
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.on_change(|c, kind| changes.borrow_mut().push((c.count, kind)));
+        counter.pause_recording();
+        counter.update(|c| c.count += 1);
+        counter.resume_recording();
+        counter.without_undo(|c| c.count += 10);
+        counter.update(|c| c.count += 100);
+
+        assert_eq!(
+            *changes.borrow(),
+            [
+                (1, ChangeKind::Unrecorded),
+                (11, ChangeKind::Unrecorded),
+                (111, ChangeKind::Update)
+            ]
+        );
+        assert_eq!(counter.stats().updates, 1);
+    }
+
+    #[test]
+    fn it_identifies_recorded_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        let ids: Vec<_> = (0..4).map(|_| counter.update(|c| c.count += 1)).collect();
+        assert_eq!(counter.position_of(ids[1]), Some(2));
+        assert_eq!(counter.undo_to_id(ids[1]), 2);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.undo_to_id(ids[3]), 0);
+
+        let next = counter.update(|c| c.count *= 10); // discards the last two updates
+        assert!(!counter.is_in_history(ids[3]));
+        assert!(ids.iter().all(|id| *id != next));
+        assert!(counter.squash(0..3).is_ok());
+        assert_eq!(counter.position_of(next), Some(1));
+        assert!(!counter.is_in_history(ids[0]));
+        assert_eq!(counter.history().next().unwrap().id, next);
+    }
+
+    #[test]
+    fn it_undoes_until_a_matching_update() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update_labeled("tag", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("tag", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.undo();
+
+        assert_eq!(counter.undo_to_label("tag"), 0);
+        assert_eq!(counter.undo_to_label("missing"), 0);
+        assert_eq!(counter.count, 3);
+        let mut nb_checked = 0;
+        let nb_undone = counter.undo_until(|entry| {
+            nb_checked += 1;
+            entry.is_applied && entry.label.is_none()
+        });
+        assert_eq!((nb_undone, nb_checked), (1, 2));
+        assert_eq!(counter.count, 2);
+        assert!(counter.can_redo());
+    }
+
+    #[test]
+    fn it_rolls_back_to_savepoints() {
+        let mut counter = Undo::with_limit(Counter { count: 0 }, 3);
+        counter.savepoint("start");
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.savepoint("one");
+        counter.update_coalesced("add", |c| c.count += 1); // not merged across a savepoint
+        counter.savepoint("two");
+        counter.savepoint("two");
+        assert_eq!(counter.history_len(), 2);
+
+        assert!(counter.release_savepoint("two"));
+        let savepoints: Vec<_> = counter.savepoints().collect();
+        assert_eq!(savepoints, [("start", 0), ("one", 1), ("two", 2)]);
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1); // folds the first update, discarding "start"
+        let savepoints: Vec<_> = counter.savepoints().collect();
+        assert_eq!(savepoints, [("one", 0), ("two", 1)]);
+
+        assert!(counter.rollback_to_savepoint("one"));
+        assert_eq!(counter.count, 1);
+        assert!(counter.can_redo());
+        assert!(!counter.rollback_to_savepoint("two")); // released by the rollback
+        counter.update(|c| c.count *= 10);
+        assert!(counter.rollback_to_savepoint("one"));
+        assert_eq!(counter.count, 1);
+        assert!(!counter.rollback_to_savepoint("start"));
+    }
+
+    #[test]
+    fn it_debugs_the_history_summary() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        assert_eq!(
+            format!("{counter:?}"),
+            "Undo { state: Counter { count: 0 }, history_len: 0, position: 0, .. }"
+        );
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("add", |c| c.count += 1);
+        assert_eq!(
+            format!("{counter:?}"),
+            r#"Undo { state: Counter { count: 2 }, history_len: 2, position: 2, labels: [None, Some("add")], .. }"#
+        );
+    }
+
+    #[test]
+    fn it_converts_from_and_compares_with_the_state() {
+        let mut list: Undo<Vec<u32>> = Undo::default();
+        assert!(list.as_ref().is_empty());
+        list.update(|list| list.push(1));
+        assert_eq!(list, vec![1]);
+        assert!(list != Vec::new());
+
+        let counter = Undo::from(Counter { count: 3 });
+        assert_eq!(counter.as_ref().count, 3);
+        assert!(!counter.can_undo());
+    }
+
+    #[test]
+    fn it_splits_into_parts_and_back() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.undo();
+        counter.mark_saved();
+        counter.savepoint("four");
+        let (state, history) = counter.into_parts();
+        assert_eq!((state.count, history.len(), history.position()), (4, 5, 4));
+
+        let mut replayed = Counter { count: 100 };
+        history.replay(&mut replayed);
+        assert_eq!(replayed.count, 4);
+
+        let mut counter = Undo::from_parts(replayed, history);
+        assert!(!counter.is_modified());
+        assert!(counter.redo());
+        assert_eq!(counter.count, 5);
+        assert!(counter.rollback_to_savepoint("four"));
+        counter.undo_all();
+        assert_eq!(counter.count, 0);
+    }
+
+    #[test]
+    fn it_records_modifications_through_a_guard() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(1));
+        counter.modify().count = 5;
+        counter.update(|c| c.count += 1);
+        counter.modify().count *= 2;
+        assert_eq!(counter.count, 12);
+        assert_eq!(counter.history_len(), 3);
+
+        counter.undo_all();
+        assert_eq!(counter.count, 0);
+        counter.redo_all();
+        assert_eq!(counter.count, 12);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = counter.modify();
+            guard.count = 0;
+            panic!("modification failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(counter.count, 12);
+        assert_eq!(counter.history_len(), 3);
+    }
+
+    #[test]
+    fn it_folds_the_history_when_modifying_while_paused() {
+        let mut list = Undo::new(vec![1]);
+        list.update(|list| list.push(2));
+        list.update(|list| list.push(3));
+        list.undo();
+        list.pause_recording();
+        list.modify().insert(0, 0);
+        list.resume_recording();
+        assert_eq!(*list, [0, 1, 2]);
+        assert!(!list.can_undo());
+        assert!(!list.can_redo());
+
+        list.update(|list| list.push(4));
+        list.undo();
+        assert_eq!(*list, [0, 1, 2]);
+    }
+
+    #[test]
+    fn it_keeps_the_updates_before_a_modification_when_undoing_an_entry() {
+        let mut list = Undo::new(Vec::new());
+        list.update(|list| list.push(1));
+        list.modify().push(2);
+        assert_eq!(
+            list.undo_entry(0),
+            Err(UndoEntryError::RestoresSnapshot { index: 1 })
+        );
+        assert_eq!(*list, [1, 2]);
+        assert_eq!(list.history_len(), 2);
+        assert!(list.undo_entry(1).is_ok());
+        assert_eq!(*list, [1]);
+    }
+
+    #[test]
+    fn it_reads_the_current_state() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 2);
+        assert_eq!(counter.get().count, 2);
+        assert_eq!(counter.read(|c| c.count * 10), 20);
+        counter.undo();
+        assert_eq!(counter.get().count, 0);
+    }
+
+    #[test]
+    fn it_skips_unchanged_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_skip_unchanged(Some(|before, after| before.count == after.count));
+        let first = counter.update(|c| c.count = 1);
+        let skipped = counter.update_labeled("same", |c| c.count = 1);
+        assert!(skipped > first);
+        assert!(!counter.is_in_history(skipped));
+        counter.update_with_inverse(|c| c.count += 0, |c| c.count -= 0);
+        assert_eq!(counter.history_len(), 1);
+
+        counter.set_skip_unchanged(None);
+        counter.update(|c| c.count = 1);
+        assert_eq!(counter.history_len(), 2);
+    }
+
+    #[test]
+    fn it_rolls_back_invalid_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_validator(Some(|c| {
+            if c.count % 2 == 0 {
+                Ok(())
+            } else {
+                Err(format!("{} is odd", c.count))
+            }
+        }));
+        counter.update(|c| c.count += 2);
+        let rejected = counter.update(|c| c.count += 1);
+        assert!(!counter.is_in_history(rejected));
+        counter.modify().count = 3;
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 1);
+
+        let error = counter.try_apply(Box::new(|c: &mut Counter| c.count = 7));
+        assert_eq!(error.unwrap_err().to_string(), "invalid state: 7 is odd");
+        assert_eq!(counter.count, 2);
+        assert!(counter
+            .try_apply(Box::new(|c: &mut Counter| c.count = 8))
+            .is_ok());
+        assert_eq!(counter.history_len(), 2);
     }
 
     #[test]
-    fn it_can_undo_and_redo_updates() {
+    fn it_intercepts_actions_with_middlewares() {
+        let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
         let mut counter = Undo::new(Counter { count: 0 });
-        assert_eq!(counter.count, 0);
-        counter.update(|c| c.count = 5);
+        counter.add_middleware({
+            let log = std::sync::Arc::clone(&log);
+            move |c, action| {
+                log.lock()
+                    .unwrap()
+                    .push(format!("{action:?} at {}", c.count));
+                true
+            }
+        });
+        counter.add_middleware(|c, action| match action {
+            Action::Update { .. } => c.count < 10,
+            Action::Undo { count } => *count < 2,
+            Action::Redo { .. } => true,
+        });
+
+        counter.update_labeled("add", |c| c.count += 1);
+        counter.update(|c| c.count += 20);
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.update_coalesced("add", |c| c.count += 20);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 2);
+
+        assert_eq!(counter.undo_n(2), 0);
+        assert!(counter.undo());
+        assert_eq!(counter.redo_n(3), 1);
+        assert_eq!(counter.count, 2);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                r#"Update { label: Some("add") } at 1"#,
+                "Update { label: None } at 21",
+                "Update { label: None } at 2",
+                "Update { label: None } at 22",
+                "Undo { count: 2 } at 2",
+                "Undo { count: 1 } at 2",
+                "Redo { count: 1 } at 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_previews_updates_with_a_dry_run() {
+        let mut counter = Undo::new(Counter { count: 1 });
+        counter.update(|c| c.count += 1);
+        let preview = counter.dry_run(|c| c.count *= 10);
+        assert_eq!(preview.count, 20);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 1);
+    }
+
+    #[test]
+    fn it_attaches_metadata_to_entries() {
+        #[derive(Debug, PartialEq)]
+        struct Selection(usize, usize);
+
+        let mut counter = Undo::with_meta(Counter { count: 0 });
+        counter.update_with_meta(Selection(0, 1), |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.modify().set_meta(Selection(2, 3));
+        counter.update_with_meta(Selection(4, 5), |c| c.count += 1);
+        let metas: Vec<_> = counter.history().map(|entry| entry.meta).collect();
+        assert_eq!(
+            metas,
+            [
+                Some(&Selection(0, 1)),
+                None,
+                Some(&Selection(2, 3)),
+                Some(&Selection(4, 5))
+            ]
+        );
+
+        counter.squash(1..4).unwrap();
+        let metas: Vec<_> = counter.history().map(|entry| entry.meta).collect();
+        assert_eq!(metas, [Some(&Selection(0, 1)), Some(&Selection(4, 5))]);
+        assert_eq!(
+            counter.undo_until(|entry| entry.meta == Some(&Selection(0, 1))),
+            1
+        );
+        assert_eq!(counter.count, 1);
+    }
+
+    #[test]
+    fn it_returns_the_view_state_when_undoing_and_redoing() {
+        let mut counter = Undo::with_meta(Counter { count: 0 });
+        counter.update_with_meta("first", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.undo_with_view(), None);
+        assert_eq!(counter.count, 1);
+        assert_eq!(counter.undo_with_view(), Some(&"first"));
+        assert_eq!(counter.undo_with_view(), None);
+        assert_eq!(counter.redo_with_view(), Some(&"first"));
+        assert_eq!(counter.redo_with_view(), None);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.redo_with_view(), None);
+    }
+
+    #[test]
+    fn it_describes_the_next_undo_and_redo() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        assert_eq!(
+            (counter.undo_description(), counter.redo_description()),
+            (None, None)
+        );
+        counter.update_labeled("first", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("third", |c| c.count += 1);
+        counter.undo_n(2);
+        assert_eq!(counter.undo_description(), Some("first"));
+        assert_eq!(counter.redo_description(), None);
+        counter.redo();
+        assert_eq!(counter.redo_description(), Some("third"));
+    }
+
+    #[test]
+    fn it_reports_the_memory_usage() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        let empty = counter.memory_usage();
+        assert_eq!((empty.entries, empty.snapshots), (0, 1));
+
+        for _ in 0..10 {
+            counter.update_labeled("add", |c| c.count += 1);
+        }
+        let full = counter.memory_usage();
+        assert_eq!((full.entries, full.snapshots), (10, 6));
+        assert!(full.approximate_bytes > empty.approximate_bytes);
+
+        counter.clear();
+        let cleared = counter.memory_usage();
+        assert_eq!((cleared.entries, cleared.snapshots), (0, 1));
+        counter.shrink_to_fit();
+        assert!(counter.memory_usage().approximate_bytes < cleared.approximate_bytes);
+    }
+
+    #[test]
+    fn it_compacts_the_history_with_a_policy() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.undo_n(2);
+        counter.set_compaction_policy(CompactionPolicy::new().with_keep_last(1));
+        assert_eq!((counter.position(), counter.history_len()), (1, 3));
+        assert!(counter.undo());
+        assert_eq!(counter.count, 2);
+        counter.redo_all();
         assert_eq!(counter.count, 5);
-        counter.update(|c| c.count += 3);
-        assert_eq!(counter.count, 8);
 
+        counter.update(|c| c.count += 1);
+        assert_eq!((counter.position(), counter.history_len()), (1, 1));
+        assert_eq!(counter.compaction_policy().keep_last, Some(1));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_compacts_the_updates_older_than_the_max_age() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        std::thread::sleep(Duration::from_millis(20));
+        counter.update(|c| c.count += 1);
+        counter
+            .set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_millis(10)));
+        assert_eq!(counter.history_len(), 1);
         counter.undo();
-        assert_eq!(counter.count, 5);
+        assert_eq!(counter.count, 2);
+    }
+
+    #[test]
+    fn it_clones_the_history() {
+        let mut counter = Undo::new_cloneable(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("add", |c| c.count += 1);
+        counter.update_with_inverse(|c| c.count *= 3, |c| c.count /= 3);
         counter.undo();
-        assert_eq!(counter.count, 0);
 
-        counter.redo();
-        assert_eq!(counter.count, 5);
-        counter.redo();
-        assert_eq!(counter.count, 8);
+        let mut clone = counter.clone();
+        assert_eq!(
+            (clone.count, clone.position(), clone.history_len()),
+            (2, 2, 3)
+        );
+        clone.redo();
+        assert_eq!(clone.count, 6);
+        clone.undo_all();
+        assert_eq!(clone.count, 0);
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.undo_description(), Some("add"));
     }
 
     #[test]
-    fn it_does_nothing_on_too_many_undo_or_redo() {
-        let mut counter = Undo::new(Counter { count: 3 });
+    fn it_forks_the_applied_history() {
+        let mut counter = Undo::new_cloneable(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(1));
+        counter.update(|c| c.count += 1);
+        counter.savepoint("one");
+        counter.update(|c| c.count += 1);
+        counter.mark_saved();
+        counter.savepoint("two");
         counter.undo();
-        assert_eq!(counter.count, 3);
-        counter.redo();
-        assert_eq!(counter.count, 3);
-        counter.update(|c| c.count = 8);
-        assert_eq!(counter.count, 8);
+
+        let mut fork = counter.fork();
+        assert_eq!((fork.position(), fork.history_len()), (1, 1));
+        assert_eq!(fork.saved_position(), None);
+        assert_eq!(fork.savepoints().collect::<Vec<_>>(), [("one", 1)]);
+        fork.update(|c| c.count += 10);
+        assert!(fork.undo());
+        assert!(fork.undo());
+        assert_eq!(fork.count, 0);
+        assert!(counter.redo());
+        assert_eq!(counter.count, 2);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_autosaves_after_an_interval() {
+        use std::cell::RefCell;
+
+        let saved = RefCell::new(Vec::new());
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        let policy = AutosavePolicy::new()
+            .with_every(NonZeroUsize::new(10).unwrap())
+            .with_interval(Duration::from_mins(1));
+        counter.autosave(policy, |c| saved.borrow_mut().push(c.count));
+
+        counter.update(|c| c.count += 1);
+        clock.advance(Duration::from_secs(59));
+        counter.update(|c| c.count += 1);
+        assert!(saved.borrow().is_empty());
+        clock.advance(Duration::from_secs(1));
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        assert_eq!(*saved.borrow(), [3]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn it_publishes_the_state_to_async_tasks() {
+        let mut counter = Undo::new_send(Counter { count: 0 });
+        let mut receiver = counter.watch();
+        let task = std::thread::spawn(move || {
+            let mut counts = Vec::new();
+            while pollster::block_on(receiver.changed()).is_ok() {
+                counts.push(receiver.borrow_and_update().count);
+            }
+            counts
+        });
+
+        counter.update(|c| c.count += 1);
         counter.undo();
+        drop(counter);
+        let counts = task.join().unwrap();
+        assert_eq!(counts.last(), Some(&0));
+    }
+
+    #[cfg(feature = "arc-swap")]
+    #[test]
+    fn it_publishes_the_state_to_readers() {
+        let mut counter = Undo::new_send(Counter { count: 0 });
+        let published = counter.publish();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let published = Arc::clone(&published);
+                std::thread::spawn(move || published.load().count)
+            })
+            .collect();
+        counter.update(|c| c.count += 1);
+        for reader in readers {
+            assert!(reader.join().unwrap() <= 1);
+        }
+
+        let snapshot = published.load_full();
         counter.undo();
+        assert_eq!(snapshot.count, 1);
+        assert_eq!(published.load().count, 0);
+    }
+
+    #[test]
+    fn it_notifies_observers_of_changes() {
+        use std::cell::RefCell;
+
+        let changes = RefCell::new(Vec::new());
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.on_change(|c, kind| changes.borrow_mut().push((c.count, kind)));
+
+        counter.update(|c| c.count += 1);
+        counter.transact(|tx| {
+            tx.update(|c| c.count += 1);
+            tx.update(|c| c.count += 1);
+        });
+        counter.transact(|_| {});
+        counter.jump_to(1).unwrap();
+        counter.jump_to(1).unwrap();
+        counter.jump_to(2).unwrap();
+        counter.undo_n(0);
+        let _ = counter.try_update(|_| Err(()));
+
+        assert_eq!(
+            *changes.borrow(),
+            [
+                (1, ChangeKind::Update),
+                (3, ChangeKind::Update),
+                (1, ChangeKind::Undo),
+                (3, ChangeKind::Redo)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_coalesces_consecutive_updates_with_the_same_key() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(1));
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.mark_saved();
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.update_coalesced("add", |c| c.count += 1);
+        assert_eq!(counter.history_len(), 1);
+        assert_eq!(counter.saved_position(), None);
+        assert_eq!(counter.checkpoints[1].1.count, 3);
+
+        counter.update_coalesced("multiply", |c| c.count *= 2);
+        counter.update_coalesced("add", |c| c.count += 1);
+        assert_eq!(counter.history_len(), 3);
         counter.undo();
+        counter.update_coalesced("multiply", |c| c.count *= 2); // not merged after an undo
+        assert_eq!(counter.history_len(), 3);
+        assert_eq!(counter.count, 12);
+
+        counter.undo_n(2);
         assert_eq!(counter.count, 3);
-        counter.redo();
-        counter.redo();
-        counter.redo();
-        counter.redo();
+        counter.undo();
+        assert_eq!(counter.count, 0);
+        counter.redo_all();
+        assert_eq!(counter.count, 12);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_takes_checkpoints_within_the_replay_budget() {
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        counter.set_replay_budget(Some(Duration::from_millis(3)));
+        let slow_update = |clock: &clock::ManualClock| {
+            let clock = clock.clone();
+            move |counter: &mut Counter| {
+                clock.advance(Duration::from_millis(2));
+                counter.count += 1;
+            }
+        };
+        for _ in 0..6 {
+            counter.update(slow_update(&clock));
+        }
+        let positions: Vec<_> = counter.checkpoints.iter().map(|(p, _)| *p).collect();
+        assert!(positions.windows(2).all(|pair| pair[1] - pair[0] <= 2));
+        assert_eq!(positions.last(), Some(&6));
+
+        counter.set_replay_budget(None);
+        for _ in 0..3 {
+            counter.update(slow_update(&clock));
+        }
+        assert_eq!(counter.checkpoints.len(), positions.len());
+        counter.set_replay_budget(Some(Duration::from_millis(1)));
+        counter.undo();
         assert_eq!(counter.count, 8);
+        assert!(counter.checkpoints.iter().any(|(p, _)| *p == 8));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_timestamps_the_updates_with_the_clock() {
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        counter.set_grouping_window(Some(Duration::from_secs(1)));
+        counter.set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_mins(1)));
+        counter.update(|c| c.count += 1);
+        clock.advance(Duration::from_millis(500));
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.history_len(), 1);
+        clock.advance(Duration::from_secs(30));
+        counter.update(|c| c.count += 1);
+        let time = clock.now();
+        clock.advance(Duration::from_secs(10));
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.history_len(), 3);
+
+        clock.advance(Duration::from_secs(58));
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.history_len(), 2);
+        assert_eq!(counter.undo_to(time), 2);
+        assert_eq!(counter.count, 3);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_groups_updates_within_the_window() {
+        use std::time::Duration;
+
+        let start = Instant::now();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.set_grouping_window(Some(Duration::from_secs(1)));
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("labeled", |c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.history_len(), 2);
+
+        counter.undo();
+        counter.update(|c| c.count += 10); // not merged after an undo
+        counter.update(|c| c.count += 10);
+        assert_eq!(counter.history_len(), 2);
+        assert_eq!(counter.count, 22);
+
+        counter.set_grouping_window(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(1));
+        counter.update(|c| c.count += 100);
+        assert_eq!(counter.history_len(), 3);
+        assert_eq!(counter.undo_to(start), 3);
+        assert_eq!(counter.count, 0);
     }
 
     #[test]
@@ -215,6 +6001,560 @@ mod tests {
         assert_eq!(counter.count, 16);
     }
 
+    #[test]
+    fn it_tracks_the_history_position() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        assert!(!counter.can_undo());
+        assert!(!counter.can_redo());
+        assert_eq!((counter.position(), counter.history_len()), (0, 0));
+
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 1);
+        assert!(counter.can_undo());
+        assert!(!counter.can_redo());
+        assert_eq!((counter.position(), counter.history_len()), (3, 3));
+
+        counter.undo();
+        counter.undo();
+        assert!(counter.can_undo());
+        assert!(counter.can_redo());
+        assert_eq!((counter.position(), counter.history_len()), (1, 3));
+
+        counter.update(|c| c.count += 1); // discard previous updates
+        assert!(!counter.can_redo());
+        assert_eq!((counter.position(), counter.history_len()), (2, 2));
+    }
+
+    #[test]
+    fn it_undoes_an_entry_in_the_middle_of_the_history() {
+        let mut list = Undo::new(vec![1]);
+        list.set_checkpoint_interval(NonZeroUsize::new(1));
+        list.update(|list| list.push(2));
+        list.savepoint("two");
+        list.update(|list| list.push(3));
+        list.update(|list| list.retain(|value| *value != 1));
+        list.update(|list| list.push(4));
+        list.undo();
+        assert_eq!(
+            list.undo_entry(3),
+            Err(UndoEntryError::NotApplied {
+                index: 3,
+                position: 3
+            })
+        );
+
+        list.set_validator(Some(|list| {
+            if list.is_empty() {
+                Err(String::from("empty"))
+            } else {
+                Ok(())
+            }
+        }));
+        assert_eq!(list.undo_entry(0), Ok(()));
+        assert_eq!(*list, [3]);
+        assert_eq!(list.savepoints().count(), 0);
+        assert!(matches!(
+            list.undo_entry(0),
+            Err(UndoEntryError::Conflict(_))
+        ));
+        assert_eq!(*list, [3]);
+
+        assert!(list.redo());
+        assert_eq!(*list, [3, 4]);
+        list.undo_all();
+        assert_eq!(*list, [1]);
+        list.redo_all();
+        assert_eq!(*list, [3, 4]);
+    }
+
+    #[test]
+    fn it_keeps_the_discarded_redo_branches() {
+        let mut list = Undo::new(Vec::new());
+        list.set_redo_policy(RedoPolicy::Keep);
+        list.update(|list| list.push(1));
+        let first = list.update(|list| list.push(2));
+        list.update(|list| list.push(3));
+        list.undo_n(2);
+        let second = list.update(|list| list.push(20));
+        list.undo();
+        list.update(|list| list.push(200));
+        assert_eq!(*list, [1, 200]);
+        assert_eq!(
+            list.discarded_branches()
+                .map(|branch| (branch.position, branch.entries.len()))
+                .collect::<Vec<_>>(),
+            [(1, 2), (1, 1)]
+        );
+
+        assert!(list.restore_branch(first));
+        assert!(!list.restore_branch(first));
+        assert_eq!(*list, [1]);
+        list.redo_all();
+        assert_eq!(*list, [1, 2, 3]);
+        assert_eq!(list.discarded_branches().count(), 2);
+        assert!(list.restore_branch(second));
+        assert_eq!(list.redo_all(), 1);
+        assert_eq!(*list, [1, 20]);
+
+        list.set_limit(Some(1));
+        assert_eq!(list.discarded_branches().count(), 0);
+        list.set_redo_policy(RedoPolicy::Discard);
+        list.undo();
+        list.update(|list| list.push(30));
+        assert!(list.discarded.is_empty());
+    }
+
+    #[test]
+    fn it_redoes_an_update_repeated_after_an_undo() {
+        use std::cell::RefCell;
+
+        let changes = RefCell::new(Vec::new());
+        let mut list = Undo::new(Vec::new());
+        list.set_redo_resurrection(Some(PartialEq::eq));
+        list.on_change(|_, kind| changes.borrow_mut().push(kind));
+        let first = list.update(|list| list.push(1));
+        list.update(|list| list.push(2));
+        list.undo_all();
+
+        assert_eq!(list.update(|list| list.insert(0, 1)), first);
+        assert_eq!(list.position(), 1);
+        assert_eq!(list.history_len(), 2);
+        assert_eq!(changes.borrow().last(), Some(&ChangeKind::Redo));
+        list.update(|list| list.push(3));
+        assert!(!list.can_redo());
+        assert_eq!(*list, [1, 3]);
+    }
+
+    #[test]
+    fn it_increments_the_version_on_every_change() {
+        let mut counter = Undo::new(0);
+        let token = counter.change_token();
+        counter.update(|value| *value += 1);
+        counter.undo();
+        counter.redo();
+        counter.without_undo(|value| *value *= 10);
+        assert_eq!(counter.version(), 4);
+        assert!(counter.has_changed_since(token));
+
+        let token = counter.change_token();
+        counter.set_validator(Some(|value| {
+            if *value < 100 {
+                Ok(())
+            } else {
+                Err(String::from("too large"))
+            }
+        }));
+        counter.update(|value| *value *= 10); // rolled back
+        assert!(!counter.redo());
+        assert!(!counter.has_changed_since(token));
+    }
+
+    #[test]
+    fn it_yields_the_states_around_each_update() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update_labeled("Double", |c| c.count *= 2);
+        counter.undo();
+        let diffs: Vec<_> = counter
+            .diffs()
+            .map(|(before, after)| (before.count, after.count))
+            .collect();
+        assert_eq!(diffs, [(0, 1), (1, 2)]);
+
+        let mut diffs = Vec::new();
+        counter.for_each_diff(|entry, before, after| {
+            diffs.push((entry.label, entry.is_applied, before.count, after.count));
+        });
+        assert_eq!(diffs, [(None, true, 0, 1), (Some("Double"), false, 1, 2)]);
+    }
+
+    #[test]
+    fn it_counts_the_usage() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            counter.update(|c| c.count += 1);
+        }
+        counter.undo_n(2); // replays 1 update from the checkpoint at 2
+        counter.undo(); // restores the checkpoint at 2
+        counter.redo_all();
+        let stats = counter.stats();
+        assert_eq!((stats.updates, stats.undos, stats.redos), (5, 2, 1));
+        assert_eq!((stats.replays, stats.replayed_updates), (2, 1));
+        assert_eq!(stats.average_replay_len(), Some(0.5));
+
+        counter.reset_stats();
+        assert_eq!(counter.stats(), UsageStats::default());
+        assert_eq!(counter.stats().average_replay_len(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the update at position 2 is not deterministic")]
+    fn it_checks_that_the_updates_are_deterministic() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let mut counter = Undo::new(0);
+        counter.set_determinism_check(Some(PartialEq::eq));
+        counter.update(|value| *value += 1);
+        counter.update(|value| {
+            calls.set(calls.get() + 1);
+            *value += calls.get();
+        });
+    }
+
+    #[test]
+    fn it_restores_the_state_in_place() {
+        let mut list = Undo::new(Vec::with_capacity(8));
+        list.update(|list| list.extend([1, 2, 3]));
+        list.update(|list| list.push(4));
+        let buffer = list.as_ptr();
+        list.undo();
+        list.undo();
+        list.redo();
+        assert_eq!(*list, [1, 2, 3]);
+        assert_eq!(list.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn it_reports_the_progress_of_a_jump_until_cancelled() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_checkpoint_interval(NonZeroUsize::new(4));
+        for _ in 0..10 {
+            counter.update(|counter| counter.count += 1);
+        }
+        let token = CancellationToken::new();
+        let mut progresses = Vec::new();
+        let is_done =
+            counter.jump_to_with_progress(7, |progress| progresses.push(progress), &token);
+        assert_eq!(is_done, Ok(true));
+        assert_eq!(
+            progresses,
+            [
+                ReplayProgress {
+                    replayed: 1,
+                    total: 3
+                },
+                ReplayProgress {
+                    replayed: 2,
+                    total: 3
+                },
+                ReplayProgress {
+                    replayed: 3,
+                    total: 3
+                },
+            ]
+        );
+        assert_eq!(counter.count, 7);
+
+        let is_done = counter.jump_to_with_progress(
+            10,
+            |progress| {
+                if progress.replayed == 2 {
+                    token.cancel();
+                }
+            },
+            &token,
+        );
+        assert_eq!(is_done, Ok(false));
+        assert_eq!((counter.position(), counter.count), (9, 9));
+        assert_eq!(counter.jump_to_with_progress(1, |_| {}, &token), Ok(false));
+        assert_eq!((counter.position(), counter.count), (0, 0));
+        assert!(counter.jump_to_with_progress(11, |_| {}, &token).is_err());
+    }
+
+    #[test]
+    fn it_keeps_the_history_within_the_memory_budget() {
+        let mut list = Undo::new(Vec::<u8>::new());
+        list.set_checkpoint_interval(NonZeroUsize::new(1));
+        for _ in 0..10 {
+            list.update(|list| list.extend([0; 1000]));
+        }
+        let budget = MemoryBudget {
+            max_bytes: 20_000,
+            snapshot_size: Vec::capacity,
+            command_size: |_| 0,
+        };
+        list.set_memory_budget(Some(budget));
+        assert!(list.history_len() < 10);
+        assert!(
+            list.checkpoints
+                .iter()
+                .map(|(_, s)| s.capacity())
+                .sum::<usize>()
+                <= 20_000
+        );
+        assert_eq!(list.len(), 10_000);
+
+        list.undo_all();
+        assert!(!list.is_empty());
+        list.set_memory_budget(Some(MemoryBudget {
+            max_bytes: 0,
+            ..budget
+        }));
+        assert_eq!(list.history_len(), 0);
+        assert!(list.memory_budget().is_some());
+    }
+
+    #[test]
+    fn it_returns_the_result_of_the_first_application() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        let previous = counter.update_ret(|c| {
+            c.count *= 10;
+            c.count / 10
+        });
+        assert_eq!(previous, 1);
+        let count = counter.update_ret(|c| {
+            c.count += 5;
+            c.count
+        });
+        assert_eq!(count, 15);
+
+        counter.undo();
+        counter.undo();
+        assert_eq!(counter.count, 1);
+        counter.redo_all();
+        assert_eq!(counter.count, 15);
+    }
+
+    fn reject_odd(counter: &Counter) -> Result<(), String> {
+        if counter.count.is_multiple_of(2) {
+            Ok(())
+        } else {
+            Err(format!("{} is odd", counter.count))
+        }
+    }
+
+    #[test]
+    fn it_rolls_back_invalid_fallible_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_validator(Some(reject_odd));
+        let rejected = counter.try_update(|c| {
+            c.count += 1;
+            Ok::<(), ()>(())
+        });
+        assert!(!counter.is_in_history(rejected.unwrap()));
+        assert_eq!(counter.count, 0);
+        assert!(!counter.can_undo());
+    }
+
+    #[test]
+    fn it_rolls_back_invalid_coalesced_updates() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_validator(Some(reject_odd));
+        counter.update_coalesced("add", |c| c.count += 1);
+        assert_eq!(counter.count, 0);
+        assert!(!counter.can_undo());
+
+        counter.update_coalesced("add", |c| c.count += 2);
+        counter.update_coalesced("add", |c| c.count += 1); // merged
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 1);
+        counter.undo();
+        counter.redo();
+        assert_eq!(counter.count, 2);
+    }
+
+    #[test]
+    fn it_rolls_back_invalid_transactions() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_validator(Some(reject_odd));
+        counter.transact(|tx| {
+            tx.update(|c| c.count += 2);
+            tx.update(|c| c.count += 1);
+        });
+        assert_eq!(counter.count, 0);
+        assert!(!counter.can_undo());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_rolls_back_invalid_grouped_updates() {
+        use std::time::Duration;
+
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_validator(Some(reject_odd));
+        counter.set_grouping_window(Some(Duration::from_secs(1)));
+        counter.update(|c| c.count += 2);
+        let rejected = counter.update(|c| c.count += 1); // merged
+        assert!(!counter.is_in_history(rejected));
+        assert_eq!(counter.count, 2);
+        counter.undo();
+        counter.redo();
+        assert_eq!(counter.count, 2);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_only_groups_updates_without_keys() {
+        use std::time::Duration;
+
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_grouping_window(Some(Duration::from_secs(1)));
+        counter.update_coalesced("add", |c| c.count += 1);
+        counter.update(|c| c.count += 1); // not merged into the coalesced update
+        counter.update_coalesced("add", |c| c.count += 1);
+        assert_eq!(counter.history_len(), 3);
+
+        counter.set_skip_unchanged(Some(|before, after| before.count == after.count));
+        counter.update(|c| c.count += 1);
+        counter.update(|c| c.count += 0); // skipped instead of being merged
+        assert_eq!(counter.history_len(), 4);
+        assert_eq!(counter.count, 4);
+        counter.undo();
+        assert_eq!(counter.count, 3);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_undoes_to_a_time_with_unordered_timestamps() {
+        fn first(pair: &mut (u32, u32)) -> &mut u32 {
+            &mut pair.0
+        }
+
+        let clock = clock::ManualClock::new();
+        let mut pair = Undo::new((0, 0));
+        pair.set_clock(clock.clone());
+        pair.focus("first", first).update(|first| *first += 1);
+        let time = clock.now();
+        clock.advance(Duration::from_secs(1));
+        pair.update(|pair| pair.1 += 1);
+        pair.focus("first", first).undo();
+        pair.focus("first", first).redo(); // moved after the more recent update
+
+        assert_eq!(pair.undo_to(time), 0);
+        pair.undo();
+        assert_eq!(pair.undo_to(time), 1);
+        assert_eq!(*pair, (0, 0));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_folds_the_updates_older_than_the_max_age_into_the_initial_state() {
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        counter.set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_mins(1)));
+        counter.update(|c| c.count += 1);
+        clock.advance(Duration::from_secs(30));
+        counter.update(|c| c.count += 10);
+        clock.advance(Duration::from_secs(40));
+        counter.update(|c| c.count += 100);
+        assert_eq!(counter.history_len(), 2);
+
+        assert_eq!(counter.undo_all(), 2);
+        assert_eq!(counter.count, 1);
+        assert_eq!(counter.redo_all(), 2);
+        assert_eq!(counter.count, 111);
+        counter.undo();
+        assert_eq!(counter.count, 11);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_only_folds_the_leading_expired_updates_of_a_reordered_history() {
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        counter
+            .focus("count", |c: &mut Counter| &mut c.count)
+            .update(|count| *count += 1);
+        counter.update(|c| c.count += 10);
+        clock.advance(Duration::from_secs(100));
+        counter.update(|c| c.count += 100);
+        let mut focus = counter.focus("count", |c: &mut Counter| &mut c.count);
+        assert!(focus.undo());
+        assert!(focus.redo()); // moved after the update made 100 seconds later
+
+        counter
+            .set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_secs(50)));
+        counter.update(|c| c.count += 1000);
+        assert_eq!(counter.history_len(), 3);
+        assert_eq!(counter.undo_all(), 3);
+        assert_eq!(counter.count, 10);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_keeps_the_last_hour_of_a_weeks_long_history() {
+        let clock = clock::ManualClock::new();
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.set_clock(clock.clone());
+        counter
+            .set_compaction_policy(CompactionPolicy::new().with_max_age(Duration::from_hours(1)));
+        // An update every 10 minutes for two weeks, every other one through a focus.
+        for step in 0..2016_u32 {
+            clock.advance(Duration::from_mins(10));
+            if step.is_multiple_of(2) {
+                counter
+                    .focus("count", |c: &mut Counter| &mut c.count)
+                    .update(|count| *count += 1);
+            } else {
+                counter.update(|c| c.count += 1);
+            }
+        }
+        assert_eq!(counter.history_len(), 7);
+
+        clock.advance(Duration::from_mins(5));
+        let mut focus = counter.focus("count", |c: &mut Counter| &mut c.count);
+        assert!(focus.undo());
+        assert!(focus.redo()); // moves the update made 15 minutes ago to the top
+        counter.update(|c| c.count += 1);
+        assert_eq!(counter.history_len(), 7);
+        assert_eq!(counter.undo_all(), 7);
+        assert_eq!(counter.count, 2010);
+    }
+
+    #[test]
+    fn it_keeps_the_history_when_undoing_an_entry_fails() {
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update(|c| {
+            assert!(c.count > 0, "depends on the first update");
+            c.count += 1;
+        });
+        assert!(matches!(
+            counter.undo_entry(0),
+            Err(UndoEntryError::Conflict(_))
+        ));
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 2);
+        counter.undo_all();
+        assert_eq!(counter.count, 0);
+        counter.redo_all();
+
+        counter.add_middleware(|_, action| !matches!(action, Action::Undo { .. }));
+        assert_eq!(counter.undo_entry(1), Err(UndoEntryError::Vetoed));
+        assert_eq!(counter.count, 2);
+        assert_eq!(counter.history_len(), 2);
+    }
+
+    #[test]
+    fn it_restores_a_checkpoint_when_a_recorded_update_panics_while_regenerating() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let applications = Cell::new(0);
+        let mut counter = Undo::new(Counter { count: 0 });
+        counter.update(|c| c.count += 1);
+        counter.update(|c| {
+            applications.set(applications.get() + 1);
+            assert_eq!(applications.get(), 1, "cannot be replayed");
+            c.count += 1;
+        });
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            counter.update(|c| {
+                c.count = 100;
+                panic!("update failed");
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(counter.count, 0);
+        assert_eq!((counter.position(), counter.history_len()), (0, 2));
+    }
+
     #[test]
     fn it_unwraps_the_inner_value() {
         let mut counter = Undo::new(Counter { count: 0 });
@@ -0,0 +1,176 @@
+//! Reconciliation of two diverging histories, by rebasing the updates of one on top of the other.
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::{ChangeKind, Command, Snapshot, Undo};
+
+/// A [`Command`] that can be adjusted to apply after another command made concurrently,
+/// as needed by [`Undo::merge`].
+///
+/// # Example
+/// ```
+/// use simple_undo::merge::Rebase;
+/// use simple_undo::{Command, Undo};
+///
+/// #[derive(Clone, PartialEq)]
+/// struct Insert(usize, char);
+///
+/// impl Command<String> for Insert {
+///     fn apply(&self, text: &mut String) {
+///         text.insert(self.0, self.1);
+///     }
+/// }
+///
+/// impl Rebase<String> for Insert {
+///     fn rebase(&self, other: &Self) -> Option<Self> {
+///         let index = if other.0 <= self.0 { self.0 + 1 } else { self.0 };
+///         Some(Insert(index, self.1))
+///     }
+/// }
+///
+/// let mut local = Undo::with_commands(String::from("ac"));
+/// let mut remote = local.clone();
+/// local.apply(Insert(0, '>'));
+/// remote.apply(Insert(1, 'b'));
+///
+/// local.merge(&remote)?;
+/// assert_eq!(*local, ">abc");
+/// # Ok::<(), simple_undo::merge::MergeConflict>(())
+/// ```
+pub trait Rebase<TState>: Command<TState> + Sized {
+    /// Returns this command adjusted to apply after `other`, both having been made on the same state,
+    /// or `None` if they conflict.
+    fn rebase(&self, other: &Self) -> Option<Self>;
+}
+
+/// Error returned by [`Undo::merge`] when two updates can't be rebased on each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// The position of the conflicting update in the merged history.
+    pub position: usize,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the update at position {} conflicts", self.position)
+    }
+}
+
+impl Error for MergeConflict {}
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Rebase<TState> + Clone + PartialEq,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Rebases the applied updates of `other` made since both histories diverged on top of the current state,
+    /// recording them as new updates with their label.
+    ///
+    /// Both histories diverge after their longest common sequence of applied commands, like the ones of
+    /// an `Undo` and its [clone](Clone::clone). Each update of `other` is [rebased](Rebase::rebase)
+    /// on the updates of this history made since, which are themselves rebased on it for the next ones.
+    ///
+    /// # Errors
+    /// Returns [`MergeConflict`] if two updates can't be rebased on each other, in which case nothing is changed.
+    pub fn merge(&mut self, other: &Self) -> Result<(), MergeConflict> {
+        let local = &self.entries[..self.nb_updates];
+        let remote = &other.entries[..other.nb_updates];
+        let common_len = local
+            .iter()
+            .zip(remote)
+            .take_while(|(local, remote)| local.command == remote.command)
+            .count();
+
+        let mut local: Vec<_> = local[common_len..]
+            .iter()
+            .map(|entry| entry.command.clone())
+            .collect();
+        let mut rebased = Vec::with_capacity(remote.len() - common_len);
+        for (position, entry) in remote.iter().enumerate().skip(common_len) {
+            let mut command = entry.command.clone();
+            for local_command in &mut local {
+                let conflict = MergeConflict {
+                    position: position + 1,
+                };
+                let rebased_command = command.rebase(local_command).ok_or(conflict)?;
+                *local_command = local_command.rebase(&command).ok_or(conflict)?;
+                command = rebased_command;
+            }
+            rebased.push(self.new_entry(entry.label.clone(), command));
+        }
+
+        for entry in rebased {
+            self.apply_entry(entry);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A command setting or adding to a counter, two sets conflicting with each other.
+    #[derive(Debug, Clone, PartialEq)]
+    enum Edit {
+        Add(i32),
+        Set(i32),
+    }
+
+    impl Command<i32> for Edit {
+        fn apply(&self, value: &mut i32) {
+            match self {
+                Self::Add(operand) => *value += operand,
+                Self::Set(new_value) => *value = *new_value,
+            }
+        }
+    }
+
+    impl Rebase<i32> for Edit {
+        fn rebase(&self, other: &Self) -> Option<Self> {
+            match (self, other) {
+                (Self::Set(_), Self::Set(_)) => None,
+                (Self::Add(_), Self::Set(_)) => Some(Self::Add(0)),
+                _ => Some(self.clone()),
+            }
+        }
+    }
+
+    #[test]
+    fn it_merges_diverging_histories() {
+        let mut local = Undo::with_commands(0);
+        local.apply(Edit::Add(1));
+        let mut remote = local.clone();
+        local.apply(Edit::Add(10));
+        remote.apply_labeled("remote add", Edit::Add(100));
+        remote.apply(Edit::Add(1000));
+        remote.apply(Edit::Add(1));
+        remote.undo();
+
+        local.merge(&remote).unwrap();
+        assert_eq!(*local, 1111);
+        assert_eq!(local.history_len(), 4);
+        assert_eq!(local.undo_description(), None);
+        local.undo();
+        assert_eq!(local.undo_description(), Some("remote add"));
+        local.merge(&local.clone()).unwrap();
+        assert!(local.can_redo());
+    }
+
+    #[test]
+    fn it_leaves_the_history_unchanged_on_conflict() {
+        let mut local = Undo::with_commands(0);
+        let mut remote = local.clone();
+        local.apply(Edit::Set(5));
+        remote.apply(Edit::Add(1));
+        remote.apply(Edit::Set(7));
+
+        let conflict = local.merge(&remote).unwrap_err();
+        assert_eq!(conflict, MergeConflict { position: 2 });
+        assert_eq!(conflict.to_string(), "the update at position 2 conflicts");
+        assert_eq!((*local, local.history_len()), (5, 1));
+    }
+}
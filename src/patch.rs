@@ -0,0 +1,131 @@
+//! Export of the changes between two positions of the history as a JSON Patch (RFC 6902),
+//! enabled by the `json-patch` feature.
+
+use core::fmt;
+use std::error::Error;
+
+use json_patch::Patch;
+use serde::Serialize;
+
+use crate::{ChangeKind, Command, OutOfRange, Snapshot, Undo};
+
+/// Error returned by [`Undo::patch_between`].
+#[derive(Debug)]
+pub enum PatchError {
+    /// One of the positions is past the end of the history.
+    OutOfRange(OutOfRange),
+    /// A state could not be converted to JSON.
+    Serialization(serde_json::Error),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange(error) => error.fmt(f),
+            Self::Serialization(error) => write!(f, "failed to convert the state to JSON: {error}"),
+        }
+    }
+}
+
+impl Error for PatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::OutOfRange(error) => Some(error),
+            Self::Serialization(error) => Some(error),
+        }
+    }
+}
+
+impl From<OutOfRange> for PatchError {
+    fn from(error: OutOfRange) -> Self {
+        Self::OutOfRange(error)
+    }
+}
+
+impl From<serde_json::Error> for PatchError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serialization(error)
+    }
+}
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot + Serialize,
+    TState::Snapshot: Clone,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Returns the JSON Patch turning the state at position `from` into the state at position `to`,
+    /// both between `0` and [`Undo::history_len`], without changing the current state.
+    ///
+    /// The states are converted to JSON with their `Serialize` implementation, then compared.
+    ///
+    /// # Errors
+    /// Returns [`PatchError::OutOfRange`] if a position is past the end of the history,
+    /// or [`PatchError::Serialization`] if a state can't be converted to JSON.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut list = Undo::new(vec![1]);
+    /// let saved = list.position();
+    /// list.update(|list| list.push(2));
+    /// list.update(|list| list[0] = 10);
+    ///
+    /// let patch = list.patch_between(saved, list.position())?;
+    /// assert_eq!(
+    ///     serde_json::to_value(patch)?,
+    ///     serde_json::json!([
+    ///         { "op": "replace", "path": "/0", "value": 10 },
+    ///         { "op": "add", "path": "/1", "value": 2 },
+    ///     ])
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn patch_between(&self, from: usize, to: usize) -> Result<Patch, PatchError> {
+        self.check_position(from)?;
+        self.check_position(to)?;
+        let before = serde_json::to_value(self.state_at(from))?;
+        let after = serde_json::to_value(self.state_at(to))?;
+        Ok(json_patch::diff(&before, &after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Clone, Serialize)]
+    struct Document {
+        title: String,
+        tags: Vec<&'static str>,
+    }
+
+    #[test]
+    fn it_produces_a_patch_between_two_positions() {
+        let mut document = Undo::new(Document {
+            title: String::from("Draft"),
+            tags: Vec::new(),
+        });
+        document.update(|document| document.title.push_str(" 1"));
+        document.update(|document| document.tags.push("rust"));
+        document.undo();
+
+        let mut state = serde_json::to_value(document.state_at(0)).unwrap();
+        let patch = document.patch_between(0, 2).unwrap();
+        json_patch::patch(&mut state, &patch).unwrap();
+        assert_eq!(state, json!({ "title": "Draft 1", "tags": ["rust"] }));
+
+        let patch = document.patch_between(2, 1).unwrap();
+        assert_eq!(
+            serde_json::to_value(patch).unwrap(),
+            json!([{ "op": "remove", "path": "/tags/0" }])
+        );
+        assert_eq!(document.patch_between(1, 1).unwrap().0.len(), 0);
+        assert!(matches!(
+            document.patch_between(0, 3),
+            Err(PatchError::OutOfRange(OutOfRange { position: 3, .. }))
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+//! States made of persistent collections of `im`, enabled by the `im` feature, whose clones share their structure.
+
+use core::hash::{BuildHasher, Hash};
+use core::num::NonZeroUsize;
+
+use crate::Undo;
+
+/// A state whose clones share their structure with the original, like the collections of `im`,
+/// so that cloning it is nearly free whatever its size.
+///
+/// It's implemented for the collections of `im`, and can be implemented for a state made of them.
+pub trait Persistent: Clone {}
+
+impl<A: Clone> Persistent for im::Vector<A> {}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> Persistent for im::HashMap<K, V, S> {}
+
+impl<A: Hash + Eq + Clone, S: BuildHasher> Persistent for im::HashSet<A, S> {}
+
+impl<K: Ord + Clone, V: Clone> Persistent for im::OrdMap<K, V> {}
+
+impl<A: Ord + Clone> Persistent for im::OrdSet<A> {}
+
+impl<'state, TState: Persistent + 'state> Undo<'state, TState> {
+    /// Wraps the given persistent state like [`Undo::new`], snapshotting it after every update.
+    ///
+    /// Undoing then restores the snapshot of the previous state instead of re-applying any update,
+    /// the snapshots sharing most of their structure with each other.
+    ///
+    /// # Example
+    /// ```
+    /// use im::{HashMap, Vector};
+    /// use simple_undo::persistent::Persistent;
+    /// use simple_undo::Undo;
+    ///
+    /// #[derive(Clone)]
+    /// struct Library {
+    ///     books: Vector<String>,
+    ///     loans: HashMap<String, u32>,
+    /// }
+    ///
+    /// impl Persistent for Library {}
+    ///
+    /// let mut library = Undo::persistent(Library {
+    ///     books: Vector::new(),
+    ///     loans: HashMap::new(),
+    /// });
+    /// library.update(|library| library.books.push_back("Dune".into()));
+    /// library.update(|library| {
+    ///     library.loans.insert("Dune".into(), 7);
+    /// });
+    ///
+    /// library.undo(); // restores the previous snapshot
+    /// assert!(library.loans.is_empty());
+    /// ```
+    pub fn persistent(state: TState) -> Self {
+        let mut undo = Self::new(state);
+        undo.checkpoint_interval = Some(NonZeroUsize::MIN);
+        undo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    #[test]
+    fn it_never_replays_the_updates() {
+        let nb_calls = Rc::new(Cell::new(0));
+        let mut list = Undo::persistent(im::Vector::new());
+        for value in 0..10 {
+            let nb_calls = Rc::clone(&nb_calls);
+            list.update(move |list| {
+                nb_calls.set(nb_calls.get() + 1);
+                list.push_back(value);
+            });
+        }
+        list.undo_n(4);
+        list.undo();
+        assert_eq!(*list, (0..5).collect());
+        assert_eq!(nb_calls.get(), 10);
+        assert_eq!(list.stats().replayed_updates, 0);
+    }
+}
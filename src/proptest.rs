@@ -0,0 +1,133 @@
+//! Property-testing helpers checking an [`Undo`] against a reference model, enabled by the `proptest` feature.
+//!
+//! [`operations`] generates random sequences of updates, undos and redos, which [`check_model`] applies
+//! to an `Undo` and to a model keeping a copy of every state, checking that both agree after each operation.
+
+use core::fmt;
+
+use ::proptest::collection::vec;
+use ::proptest::prelude::{prop_oneof, Just, Strategy};
+use ::proptest::test_runner::TestCaseError;
+use ::proptest::{prop_assert, prop_assert_eq};
+
+use crate::Undo;
+
+/// An operation applied to an [`Undo`] by [`check_model`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation<TUpdate> {
+    /// Updates the state with the given value, as interpreted by the update function of [`check_model`].
+    Update(TUpdate),
+    /// Calls [`Undo::undo`].
+    Undo,
+    /// Calls [`Undo::redo`].
+    Redo,
+}
+
+/// Returns a strategy generating up to `max_len` operations, the updates being generated by `update`.
+///
+/// Updates are generated three times more often than undos and redos.
+pub fn operations<TStrategy>(
+    update: TStrategy,
+    max_len: usize,
+) -> impl Strategy<Value = Vec<Operation<TStrategy::Value>>>
+where
+    TStrategy: Strategy + 'static,
+    TStrategy::Value: Clone,
+{
+    let operation = prop_oneof![
+        3 => update.prop_map(Operation::Update),
+        1 => Just(Operation::Undo),
+        1 => Just(Operation::Redo),
+    ];
+    vec(operation, 0..=max_len)
+}
+
+/// Applies the operations to an `Undo` wrapping `initial_state`, checking it against a reference model.
+///
+/// Each update is recorded as a closure calling `update_fn` with its value. After each operation,
+/// the current state must be the one of the model, and the position must be within the history.
+/// At the end, the states replayed with [`Undo::states`] must be the ones of the model.
+///
+/// # Errors
+/// Returns a [`TestCaseError`] describing the first difference with the model.
+///
+/// # Example
+/// It's usually called within the `proptest!` macro, or with a test runner:
+/// ```
+/// use proptest::prelude::any;
+/// use proptest::test_runner::TestRunner;
+/// use simple_undo::proptest::{check_model, operations};
+///
+/// let mut runner = TestRunner::default();
+/// let result = runner.run(&operations(any::<u8>(), 20), |operations| {
+///     check_model(Vec::new(), &operations, |list, value| list.push(*value))
+/// });
+/// assert!(result.is_ok());
+/// ```
+pub fn check_model<TState, TUpdate>(
+    initial_state: TState,
+    operations: &[Operation<TUpdate>],
+    update_fn: impl Fn(&mut TState, &TUpdate) + Clone + 'static,
+) -> Result<(), TestCaseError>
+where
+    TState: Clone + PartialEq + fmt::Debug + 'static,
+    TUpdate: Clone + 'static,
+{
+    let mut undo = Undo::new(initial_state.clone());
+    let mut states = vec![initial_state];
+    let mut position = 0;
+    for operation in operations {
+        match operation {
+            Operation::Update(value) => {
+                let mut state = states[position].clone();
+                update_fn(&mut state, value);
+                states.truncate(position + 1);
+                states.push(state);
+                position += 1;
+
+                let (update_fn, value) = (update_fn.clone(), value.clone());
+                undo.update(move |state| update_fn(state, &value));
+            }
+            Operation::Undo => {
+                prop_assert_eq!(undo.undo(), position > 0, "undo at position {}", position);
+                position = position.saturating_sub(1);
+            }
+            Operation::Redo => {
+                let can_redo = position + 1 < states.len();
+                prop_assert_eq!(undo.redo(), can_redo, "redo at position {}", position);
+                position += usize::from(can_redo);
+            }
+        }
+        prop_assert_eq!(undo.position(), position);
+        prop_assert_eq!(undo.history_len(), states.len() - 1);
+        prop_assert!(undo.position() <= undo.history_len());
+        prop_assert_eq!(&*undo, &states[position], "state at position {}", position);
+    }
+    prop_assert_eq!(undo.states().collect::<Vec<_>>(), states);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::proptest::prelude::any;
+    use ::proptest::proptest;
+
+    proptest! {
+        #[test]
+        fn it_agrees_with_the_model(operations in operations(any::<i8>(), 30)) {
+            check_model(0_i64, &operations, |value, delta| *value = *value * 2 + i64::from(*delta))?;
+        }
+    }
+
+    #[test]
+    fn it_reports_a_difference_with_the_model() {
+        let operations = [Operation::Update(1), Operation::Update(2), Operation::Undo];
+        let result = check_model(0, &operations, |value, delta| {
+            // Not deterministic, so the replayed state differs.
+            static CALLS: core::sync::atomic::AtomicI32 = core::sync::atomic::AtomicI32::new(0);
+            *value += delta + CALLS.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! A history widget for terminal user interfaces built with `ratatui`, enabled by the `ratatui` feature.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use ratatui_core::buffer::Buffer;
+use ratatui_core::layout::Rect;
+use ratatui_core::style::{Modifier, Style};
+use ratatui_core::text::Line;
+use ratatui_core::widgets::Widget;
+
+use crate::{ChangeKind, Command, Snapshot, Undo};
+
+/// A widget listing the history of an [`Undo`], starting with the initial state.
+///
+/// The current position is highlighted, the undone updates are dimmed and the saved position is marked with `*`.
+/// The list scrolls to keep the current position visible.
+///
+/// # Example
+/// ```
+/// use ratatui_core::buffer::Buffer;
+/// use ratatui_core::layout::Rect;
+/// use ratatui_core::widgets::Widget;
+/// use simple_undo::ratatui::HistoryList;
+/// use simple_undo::Undo;
+///
+/// let mut text = Undo::new(String::new());
+/// text.update_labeled("Type", |text| text.push('a'));
+/// text.mark_saved();
+/// text.update_labeled("Delete", |text| text.clear());
+/// text.undo();
+///
+/// let mut buffer = Buffer::empty(Rect::new(0, 0, 20, 3));
+/// HistoryList::new(&text).render(buffer.area, &mut buffer);
+/// // Renders:
+/// //   Initial state
+/// // > Type *
+/// //   Delete
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistoryList<'a> {
+    /// The labels of the initial state and the recorded updates, and whether they are applied.
+    items: Vec<(Line<'a>, bool)>,
+    /// The index of the current position in the items.
+    position: usize,
+    /// The style of the current position.
+    highlight_style: Style,
+}
+
+impl<'a> HistoryList<'a> {
+    /// Creates a widget listing the history of the given `Undo`.
+    #[must_use]
+    pub fn new<TState, TCommand, TObserver, TMeta>(
+        undo: &'a Undo<'_, TState, TCommand, TObserver, TMeta>,
+    ) -> Self
+    where
+        TState: Snapshot,
+        TCommand: Command<TState>,
+        TObserver: FnMut(&TState, ChangeKind),
+    {
+        let saved_position = undo.saved_position();
+        let labels = core::iter::once((None, true)).chain(
+            undo.history()
+                .map(|entry| (Some(entry.label.unwrap_or("Update")), entry.is_applied)),
+        );
+        let items = labels
+            .enumerate()
+            .map(|(index, (label, is_applied))| {
+                let label = label.unwrap_or("Initial state");
+                let label = if saved_position == Some(index) {
+                    format!("{label} *")
+                } else {
+                    label.into()
+                };
+                (Line::from(label), is_applied)
+            })
+            .collect();
+        Self {
+            items,
+            position: undo.position(),
+            highlight_style: Style::new().add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// Sets the style of the current position, bold by default.
+    #[must_use]
+    pub const fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+}
+
+impl Widget for HistoryList<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let height = usize::from(area.height);
+        let offset = (self.position + 1).saturating_sub(height);
+        for (row, (index, (label, is_applied))) in
+            (area.y..).zip(self.items.into_iter().enumerate().skip(offset).take(height))
+        {
+            let is_current = index == self.position;
+            let marker = if is_current { "> " } else { "  " };
+            let style = if is_current {
+                self.highlight_style
+            } else if is_applied {
+                Style::new()
+            } else {
+                Style::new().add_modifier(Modifier::DIM)
+            };
+            let mut line = Line::from(marker);
+            line.spans.extend(label.spans);
+            line.style(style)
+                .render(Rect::new(area.x, row, area.width, 1), buf);
+        }
+    }
+}
+
+/// Handles the `j` and `k` keys to move down or up a [`HistoryList`], jumping to the next or previous position.
+///
+/// Returns `true` if the key moved the history.
+pub fn handle_key<TState, TCommand, TObserver, TMeta>(
+    undo: &mut Undo<'_, TState, TCommand, TObserver, TMeta>,
+    key: char,
+) -> bool
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    let position = match key {
+        'j' => undo.position() + 1,
+        'k' if undo.can_undo() => undo.position() - 1,
+        _ => return false,
+    };
+    undo.jump_to(position).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use ratatui_core::buffer::Cell;
+
+    #[test]
+    fn it_scrolls_to_the_current_position() {
+        let mut counter = Undo::new(0);
+        for i in 1..=5 {
+            counter.update_labeled(format!("Add {i}"), move |value| *value += i);
+        }
+        assert!(handle_key(&mut counter, 'k'));
+        assert!(!handle_key(&mut counter, 'x'));
+
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 8, 2));
+        HistoryList::new(&counter).render(buffer.area, &mut buffer);
+        let text: String = buffer.content.iter().map(Cell::symbol).collect();
+        assert_eq!(text, "  Add 3 > Add 4 ");
+        assert_eq!(buffer[(2, 1)].modifier, Modifier::BOLD);
+
+        assert!(handle_key(&mut counter, 'j'));
+        assert!(!handle_key(&mut counter, 'j'));
+        assert_eq!(*counter, 15);
+    }
+}
@@ -0,0 +1,289 @@
+//! Serialization of an [`Undo`] recording typed [`Command`]s, enabled by the `serde` feature.
+//!
+//! The [`Snapshot`] of the initial state and all recorded commands are serialized with their label and metadata,
+//! along with the history position,
+//! the saved position and the configuration. The current state and checkpoints are regenerated on deserialization.
+//!
+//! The applied updates alone can also be exported as an [`Event`] log, to be replayed on another `Undo`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{ChangeKind, Command, Snapshot, Undo};
+
+/// The serialized form of an entry of the history.
+#[derive(Serialize, Deserialize)]
+struct SerializedEntry<TCommand, TMeta> {
+    command: TCommand,
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<TMeta>,
+}
+
+/// An applied update of an event log, as exported by [`Undo::export_events`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event<TCommand, TMeta = ()> {
+    /// The recorded command.
+    pub command: TCommand,
+    /// The label of the update, if any.
+    pub label: Option<String>,
+    /// The metadata attached to the update, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<TMeta>,
+}
+
+/// The serialized form of an [`Undo`].
+#[derive(Serialize, Deserialize)]
+struct SerializedUndo<TState, TCommand, TMeta> {
+    initial_state: TState,
+    entries: Vec<SerializedEntry<TCommand, TMeta>>,
+    position: usize,
+    saved_position: Option<usize>,
+    limit: Option<usize>,
+    checkpoint_interval: Option<NonZeroUsize>,
+}
+
+impl<TState, TCommand, TObserver, TMeta> Serialize for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TState::Snapshot: Serialize,
+    TCommand: Serialize,
+    TMeta: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| SerializedEntry {
+                command: &entry.command,
+                label: entry.label.clone(),
+                meta: entry.meta.as_ref(),
+            })
+            .collect();
+        SerializedUndo {
+            initial_state: &self.checkpoints[0].1,
+            entries,
+            position: self.nb_updates,
+            saved_position: self.saved_position,
+            limit: self.limit,
+            checkpoint_interval: self.checkpoint_interval,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, TState, TCommand, TObserver, TMeta> Deserialize<'de>
+    for Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TState::Snapshot: Deserialize<'de>,
+    TCommand: Command<TState> + Deserialize<'de>,
+    TMeta: Deserialize<'de>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized =
+            SerializedUndo::<TState::Snapshot, TCommand, TMeta>::deserialize(deserializer)?;
+        let history_len = serialized.entries.len();
+        if serialized.position > history_len {
+            return Err(D::Error::custom(format_args!(
+                "position {} is out of range for a history of {history_len} updates",
+                serialized.position
+            )));
+        }
+
+        let mut undo = Self::wrap(TState::from_snapshot(serialized.initial_state));
+        undo.checkpoint_interval = serialized.checkpoint_interval;
+        for SerializedEntry {
+            command,
+            label,
+            meta,
+        } in serialized.entries
+        {
+            command.apply(&mut undo.current_state);
+            let mut entry = undo.new_entry(label, command);
+            entry.meta = meta;
+            undo.record(entry);
+        }
+        if serialized.position < history_len {
+            undo.nb_updates = serialized.position;
+            undo.regenerate();
+        }
+        undo.saved_position = serialized
+            .saved_position
+            .filter(|position| *position <= history_len);
+        // Applied last so that the positions above match the serialized history.
+        undo.set_limit(serialized.limit);
+        Ok(undo)
+    }
+}
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Returns the log of the applied updates, from the oldest to the most recent, to be serialized.
+    ///
+    /// Unlike serializing the whole `Undo`, the log doesn't include the initial state nor the undone updates:
+    /// replaying it with [`Undo::import_events`] on the same initial state reconstructs the current state.
+    ///
+    /// # Example
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use simple_undo::{Command, Event, Undo};
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Add(i32);
+    ///
+    /// impl Command<i32> for Add {
+    ///     fn apply(&self, value: &mut i32) {
+    ///         *value += self.0;
+    ///     }
+    /// }
+    ///
+    /// let mut counter = Undo::with_commands(0);
+    /// counter.apply_labeled("add 5", Add(5));
+    /// counter.apply(Add(2));
+    /// let log = serde_json::to_string(&counter.export_events())?;
+    ///
+    /// let mut replica = Undo::with_commands(0);
+    /// replica.import_events(serde_json::from_str::<Vec<Event<Add>>>(&log)?);
+    /// assert_eq!(*replica, 7);
+    /// assert_eq!(replica.undo_description(), None);
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    #[must_use]
+    pub fn export_events(&self) -> Vec<Event<&TCommand, &TMeta>> {
+        self.entries[..self.nb_updates]
+            .iter()
+            .map(|entry| Event {
+                command: &entry.command,
+                label: entry.label.clone(),
+                meta: entry.meta.as_ref(),
+            })
+            .collect()
+    }
+
+    /// Applies the commands of an event log exported by [`Undo::export_events`],
+    /// recording each of them as a new update with its label and metadata.
+    pub fn import_events(&mut self, events: impl IntoIterator<Item = Event<TCommand, TMeta>>) {
+        for Event {
+            command,
+            label,
+            meta,
+        } in events
+        {
+            let mut entry = self.new_entry(label, command);
+            entry.meta = meta;
+            self.apply_entry(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Command, Event, Observer, Undo};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Edit {
+        Push(char),
+        Pop,
+    }
+
+    impl Command<String> for Edit {
+        fn apply(&self, text: &mut String) {
+            match self {
+                Self::Push(c) => text.push(*c),
+                Self::Pop => {
+                    text.pop();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_restores_the_serialized_history() {
+        let mut text = Undo::with_commands(String::from("a"));
+        text.apply(Edit::Push('b'));
+        text.apply_labeled("pop", Edit::Pop);
+        text.apply(Edit::Push('c'));
+        text.mark_saved();
+        text.undo();
+
+        let json = serde_json::to_string(&text).unwrap();
+        let mut restored: Undo<String, Edit> = serde_json::from_str(&json).unwrap();
+        assert_eq!(*restored, "a");
+        assert_eq!(restored.position(), 2);
+        assert!(restored.is_modified());
+        assert_eq!(
+            restored
+                .history()
+                .map(|entry| entry.label)
+                .collect::<Vec<_>>(),
+            [None, Some("pop"), None]
+        );
+
+        restored.redo();
+        assert_eq!(*restored, "ac");
+        assert!(!restored.is_modified());
+        restored.undo_all();
+        assert_eq!(*restored, "a");
+    }
+
+    #[test]
+    fn it_serializes_the_metadata() {
+        let mut text = Undo::with_meta(String::new());
+        text.apply_with_meta(7_u32, Edit::Push('a'));
+        text.apply(Edit::Push('b'));
+
+        let json = serde_json::to_string(&text).unwrap();
+        assert!(json.contains(r#""meta":7"#));
+        let restored: Undo<String, Edit, Observer<String>, u32> =
+            serde_json::from_str(&json).unwrap();
+        let metas: Vec<_> = restored.history().map(|entry| entry.meta).collect();
+        assert_eq!(metas, [Some(&7), None]);
+    }
+
+    #[test]
+    fn it_replays_the_exported_events() {
+        let mut text = Undo::with_meta(String::from("a"));
+        text.apply_with_meta(1_u8, Edit::Push('b'));
+        text.apply_labeled("pop", Edit::Pop);
+        text.apply(Edit::Push('c'));
+        text.undo();
+
+        let json = serde_json::to_string(&text.export_events()).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"command":{"Push":"b"},"label":null,"meta":1},{"command":"Pop","label":"pop"}]"#
+        );
+        let events: Vec<Event<Edit, u8>> = serde_json::from_str(&json).unwrap();
+        let mut replica = Undo::with_meta(String::from("a"));
+        replica.import_events(events);
+        assert_eq!(*replica, *text);
+        assert_eq!(replica.history_len(), 2);
+        assert_eq!(replica.undo_description(), Some("pop"));
+    }
+
+    #[test]
+    fn it_rejects_an_out_of_range_position() {
+        let json = r#"{
+            "initial_state": "",
+            "entries": [{ "command": "Pop", "label": null }],
+            "position": 2,
+            "saved_position": null,
+            "limit": null,
+            "checkpoint_interval": null
+        }"#;
+        let result = serde_json::from_str::<Undo<String, Edit>>(json);
+        assert!(result.is_err());
+    }
+}
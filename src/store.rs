@@ -0,0 +1,321 @@
+//! Storage of the snapshots of an [`Undo`](crate::Undo) outside of it, such as in files or a database.
+
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::Snapshot;
+
+/// A storage backend for the snapshots of a state, used through a [`Stored`] state.
+///
+/// The snapshots of a huge document don't have to live in memory: implementing this trait over a
+/// memory-mapped file, `sled` or `SQLite` only keeps their keys in the history.
+/// Storing can't fail from the point of view of the [`Undo`](crate::Undo), so the backend should panic on I/O errors.
+///
+/// # Example
+/// ```
+/// use std::fs;
+/// use std::path::PathBuf;
+/// use simple_undo::store::{HistoryStore, Stored};
+/// use simple_undo::Undo;
+///
+/// /// Stores each snapshot of a text in its own file.
+/// struct FileStore {
+///     directory: PathBuf,
+///     next_key: u64,
+/// }
+///
+/// impl HistoryStore<String> for FileStore {
+///     type Key = PathBuf;
+///
+///     fn insert(&mut self, value: &String) -> PathBuf {
+///         self.next_key += 1;
+///         let path = self.directory.join(self.next_key.to_string());
+///         fs::write(&path, value).expect("failed to write the snapshot");
+///         path
+///     }
+///
+///     fn get(&self, key: &PathBuf) -> String {
+///         fs::read_to_string(key).expect("failed to read the snapshot")
+///     }
+///
+///     fn remove(&mut self, key: &PathBuf) {
+///         let _ = fs::remove_file(key);
+///     }
+/// }
+///
+/// let directory = std::env::temp_dir().join(format!("simple-undo-{}", std::process::id()));
+/// fs::create_dir_all(&directory)?;
+/// let store = FileStore { directory: directory.clone(), next_key: 0 };
+///
+/// let mut text = Undo::new(Stored::new(String::from("Hello"), store));
+/// text.update(|text| text.push_str(" world"));
+/// text.undo();
+/// assert_eq!(**text, "Hello");
+///
+/// drop(text);
+/// assert_eq!(fs::read_dir(&directory)?.count(), 0);
+/// fs::remove_dir(&directory)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait HistoryStore<TValue> {
+    /// The key identifying a stored value, kept in memory by the history.
+    type Key;
+
+    /// Stores a copy of the given value, returning its key.
+    fn insert(&mut self, value: &TValue) -> Self::Key;
+
+    /// Returns a copy of the value stored with the given key.
+    fn get(&self, key: &Self::Key) -> TValue;
+
+    /// Removes the value stored with the given key, once the history no longer needs it.
+    fn remove(&mut self, key: &Self::Key);
+}
+
+/// The default [`HistoryStore`], keeping the values in memory.
+#[derive(Debug)]
+pub struct MemoryStore<TValue> {
+    /// The stored values, by key.
+    values: HashMap<u64, TValue>,
+    /// The key of the next stored value.
+    next_key: u64,
+}
+
+impl<TValue> MemoryStore<TValue> {
+    /// Returns the number of stored values.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if there is no stored value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<TValue> Default for MemoryStore<TValue> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            next_key: 0,
+        }
+    }
+}
+
+impl<TValue: Clone> HistoryStore<TValue> for MemoryStore<TValue> {
+    type Key = u64;
+
+    fn insert(&mut self, value: &TValue) -> u64 {
+        let key = self.next_key;
+        self.next_key += 1;
+        self.values.insert(key, value.clone());
+        key
+    }
+
+    fn get(&self, key: &u64) -> TValue {
+        self.values[key].clone()
+    }
+
+    fn remove(&mut self, key: &u64) {
+        self.values.remove(key);
+    }
+}
+
+/// A [`HistoryStore`] keeping the values in memory compressed with LZ4, enabled by the `lz4` feature.
+///
+/// The snapshots of a document usually compress very well, at the cost of compressing each of them when taken
+/// and decompressing it when restored. The values are stored as bytes, so the state is typically an
+/// encoded document.
+///
+/// # Example
+/// ```
+/// use simple_undo::store::{CompressedStore, Stored};
+/// use simple_undo::Undo;
+///
+/// let mut document = Undo::new(Stored::new(vec![0_u8; 100_000], CompressedStore::default()));
+/// document.update(|document| document[0] = 1);
+/// assert!(document.store().compressed_size() < 1_000);
+/// document.undo();
+/// assert_eq!(document[0], 0);
+/// ```
+#[cfg(feature = "lz4")]
+#[derive(Debug, Default)]
+pub struct CompressedStore {
+    /// The compressed values, by key.
+    values: HashMap<u64, Vec<u8>>,
+    /// The key of the next stored value.
+    next_key: u64,
+}
+
+#[cfg(feature = "lz4")]
+impl CompressedStore {
+    /// Returns the number of stored values.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if there is no stored value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the total size in bytes of the compressed values.
+    #[must_use]
+    pub fn compressed_size(&self) -> usize {
+        self.values.values().map(Vec::len).sum()
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<TValue: AsRef<[u8]> + From<Vec<u8>>> HistoryStore<TValue> for CompressedStore {
+    type Key = u64;
+
+    fn insert(&mut self, value: &TValue) -> u64 {
+        let key = self.next_key;
+        self.next_key += 1;
+        let compressed = lz4_flex::compress_prepend_size(value.as_ref());
+        self.values.insert(key, compressed);
+        key
+    }
+
+    fn get(&self, key: &u64) -> TValue {
+        let bytes = lz4_flex::decompress_size_prepended(&self.values[key])
+            .expect("stored values are valid LZ4 blocks");
+        TValue::from(bytes)
+    }
+
+    fn remove(&mut self, key: &u64) {
+        self.values.remove(key);
+    }
+}
+
+/// A state whose [snapshots](Snapshot) are kept in a [`HistoryStore`], the history only holding their keys.
+///
+/// It dereferences to the wrapped state, which is updated as usual.
+pub struct Stored<TState, TStore: HistoryStore<TState>> {
+    /// The wrapped state.
+    state: TState,
+    /// The store shared with the snapshots.
+    store: Arc<Mutex<TStore>>,
+}
+
+impl<TState, TStore: HistoryStore<TState>> Stored<TState, TStore> {
+    /// Wraps the given state, to store its snapshots in `store`.
+    pub fn new(state: TState, store: TStore) -> Self {
+        Self {
+            state,
+            store: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Locks the store, recovering it if poisoned.
+    pub fn store(&self) -> MutexGuard<'_, TStore> {
+        lock(&self.store)
+    }
+
+    /// Unwraps the inner state, dropping the store once no snapshot uses it.
+    pub fn into_inner(self) -> TState {
+        self.state
+    }
+}
+
+impl<TState, TStore: HistoryStore<TState>> Deref for Stored<TState, TStore> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl<TState, TStore: HistoryStore<TState>> DerefMut for Stored<TState, TStore> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.state
+    }
+}
+
+/// The snapshot of a [`Stored`] state, removing its value from the store when dropped.
+pub struct StoredSnapshot<TState, TStore: HistoryStore<TState>> {
+    /// The key of the stored value.
+    key: TStore::Key,
+    /// The store holding the value.
+    store: Arc<Mutex<TStore>>,
+}
+
+impl<TState, TStore: HistoryStore<TState>> Drop for StoredSnapshot<TState, TStore> {
+    fn drop(&mut self) {
+        lock(&self.store).remove(&self.key);
+    }
+}
+
+impl<TState, TStore: HistoryStore<TState>> Snapshot for Stored<TState, TStore> {
+    type Snapshot = StoredSnapshot<TState, TStore>;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        StoredSnapshot {
+            key: self.store().insert(&self.state),
+            store: Arc::clone(&self.store),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Self::Snapshot) {
+        self.state = lock(&snapshot.store).get(&snapshot.key);
+    }
+
+    fn from_snapshot(snapshot: Self::Snapshot) -> Self {
+        Self {
+            state: lock(&snapshot.store).get(&snapshot.key),
+            store: Arc::clone(&snapshot.store),
+        }
+    }
+}
+
+/// Locks the given store, recovering it if poisoned.
+fn lock<TStore>(store: &Mutex<TStore>) -> MutexGuard<'_, TStore> {
+    store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Undo;
+
+    #[test]
+    fn it_keeps_the_snapshots_in_the_store() {
+        let mut list = Undo::with_limit(Stored::new(vec![1], MemoryStore::default()), 2);
+        list.set_checkpoint_interval(std::num::NonZeroUsize::new(1));
+        list.update(|list| list.push(2));
+        list.update(|list| list.push(3));
+        assert_eq!(list.store().len(), 3);
+
+        list.update(|list| list.push(4));
+        assert_eq!(list.store().len(), 3);
+        list.undo_all();
+        assert_eq!(**list, [1, 2]);
+        list.update(|list| list.push(5));
+        assert_eq!(list.store().len(), 2);
+
+        let store = Arc::clone(&list.store);
+        drop(list);
+        assert!(lock(&store).is_empty());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn it_compresses_the_snapshots() {
+        let text = b"undo ".repeat(1000);
+        let mut text = Undo::new(Stored::new(text, CompressedStore::default()));
+        text.update(|text| text.extend_from_slice(b"redo"));
+        text.update(|text| text.truncate(10));
+        assert_eq!(text.store().len(), 1);
+
+        text.undo();
+        assert!(text.ends_with(b"redo"));
+        text.undo();
+        assert_eq!(text.len(), 5000);
+        assert!(text.store().compressed_size() < 100);
+    }
+}
@@ -0,0 +1,299 @@
+//! Sharing an [`Undo`] between threads.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{ChangeKind, Command, SendUndo, SendUpdate, Snapshot, Undo, UpdateId};
+
+/// A handle to an [`Undo`] shared between threads, such as a UI thread and a worker.
+///
+/// Cloning the handle shares the same history. Updates must be `Send + Sync` and are applied under a write lock,
+/// while the state is read under a read lock.
+///
+/// As an update panicking leaves the `Undo` in its previous state, a poisoned lock is recovered
+/// instead of propagating the panic to the other threads.
+///
+/// # Example
+/// ```
+/// use simple_undo::sync::SharedUndo;
+/// use std::thread;
+///
+/// let counter = SharedUndo::new(0);
+/// let worker = {
+///     let counter = counter.clone();
+///     thread::spawn(move || counter.update(|value| *value += 10))
+/// };
+/// worker.join().unwrap();
+///
+/// counter.update(|value| *value *= 2);
+/// assert_eq!(counter.read(|value| *value), 20);
+/// counter.undo();
+/// assert_eq!(counter.read(|value| *value), 10);
+/// ```
+pub struct SharedUndo<TState: Snapshot> {
+    /// The shared `Undo`, locked for each operation.
+    inner: Arc<RwLock<SendUndo<'static, TState>>>,
+}
+
+impl<TState> SharedUndo<TState>
+where
+    TState: Snapshot + Send + Sync + 'static,
+    TState::Snapshot: Send + Sync,
+{
+    /// Wraps the given state in a `SharedUndo`, which will track all updates and allows undoing or redoing them.
+    pub fn new(state: TState) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Undo::new_send(state))),
+        }
+    }
+
+    /// Calls `read_fn` with the current state, under a read lock.
+    pub fn read<TResult>(&self, read_fn: impl FnOnce(&TState) -> TResult) -> TResult {
+        read_fn(&self.read_lock())
+    }
+
+    /// Updates the current state with the given mutating function, like [`Undo::update`].
+    pub fn update(&self, update_fn: impl Fn(&mut TState) + Send + Sync + 'static) -> UpdateId {
+        self.write_lock().update(update_fn)
+    }
+
+    /// Updates the current state like [`SharedUndo::update`], attaching a label to the recorded update.
+    pub fn update_labeled(
+        &self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'static,
+    ) -> UpdateId {
+        self.write_lock().update_labeled(label, update_fn)
+    }
+
+    /// Returns `true` if there is an update that can be undone with [`SharedUndo::undo`].
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.read_lock().can_undo()
+    }
+
+    /// Returns `true` if there is an undone update that can be redone with [`SharedUndo::redo`].
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        self.read_lock().can_redo()
+    }
+
+    /// Undo the last update, like [`Undo::undo`].
+    ///
+    /// Returns `false` if there was nothing to undo.
+    #[allow(clippy::must_use_candidate)] // like `Undo::undo`, the result can be ignored
+    pub fn undo(&self) -> bool {
+        self.write_lock().undo()
+    }
+
+    /// Redo the last undone update, like [`Undo::redo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    #[allow(clippy::must_use_candidate)] // like `Undo::redo`, the result can be ignored
+    pub fn redo(&self) -> bool {
+        self.write_lock().redo()
+    }
+
+    /// Locks the shared `Undo` for reading, recovering it if poisoned.
+    fn read_lock(&self) -> RwLockReadGuard<'_, SendUndo<'static, TState>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the shared `Undo` for writing, recovering it if poisoned.
+    fn write_lock(&self) -> RwLockWriteGuard<'_, SendUndo<'static, TState>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<TState: Snapshot> Clone for SharedUndo<TState> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// A handle enqueuing commands from any thread, applied in order by the thread owning the [`Undo`]
+/// with [`Undo::apply_pending`].
+///
+/// Cloning the handle enqueues to the same [`PendingCommands`].
+///
+/// # Example
+/// ```
+/// use simple_undo::sync::CommandQueue;
+/// use simple_undo::Undo;
+/// use std::thread;
+///
+/// let mut counter = Undo::new_send(0);
+/// let (queue, pending) = CommandQueue::new();
+/// let worker = {
+///     let queue = queue.clone();
+///     thread::spawn(move || queue.update_labeled("Add 10", |value| *value += 10))
+/// };
+/// worker.join().unwrap();
+/// queue.update(|value| *value *= 2);
+///
+/// assert_eq!(counter.apply_pending(&pending), 2);
+/// assert_eq!(*counter, 20);
+/// counter.undo();
+/// assert_eq!(*counter, 10);
+/// ```
+#[derive(Debug)]
+pub struct CommandQueue<TCommand> {
+    /// The sending half of the queue, with the label of each command.
+    sender: Sender<(Option<String>, TCommand)>,
+}
+
+/// The commands enqueued by a [`CommandQueue`], waiting to be applied with [`Undo::apply_pending`].
+#[derive(Debug)]
+pub struct PendingCommands<TCommand> {
+    /// The receiving half of the queue.
+    receiver: Receiver<(Option<String>, TCommand)>,
+}
+
+impl<TCommand> CommandQueue<TCommand> {
+    /// Creates a queue, returning its handle and the pending commands to give to the thread owning the `Undo`.
+    #[must_use]
+    pub fn new() -> (Self, PendingCommands<TCommand>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self { sender }, PendingCommands { receiver })
+    }
+
+    /// Enqueues a command, to be applied like [`Undo::apply`].
+    ///
+    /// Does nothing if the pending commands have been dropped.
+    pub fn push(&self, command: TCommand) {
+        self.sender.send((None, command)).ok();
+    }
+
+    /// Enqueues a command like [`CommandQueue::push`], attaching a label to its recorded entry.
+    pub fn push_labeled(&self, label: impl Into<String>, command: TCommand) {
+        self.sender.send((Some(label.into()), command)).ok();
+    }
+}
+
+impl<TState> CommandQueue<SendUpdate<'static, TState>> {
+    /// Enqueues an update of the state, to be applied like [`Undo::update`].
+    pub fn update(&self, update_fn: impl Fn(&mut TState) + Send + Sync + 'static) {
+        self.push(Box::new(update_fn));
+    }
+
+    /// Enqueues an update like [`CommandQueue::update`], attaching a label to its recorded entry.
+    pub fn update_labeled(
+        &self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + Send + Sync + 'static,
+    ) {
+        self.push_labeled(label, Box::new(update_fn));
+    }
+}
+
+impl<TCommand> Clone for CommandQueue<TCommand> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Applies the commands enqueued by the [`CommandQueue`] so far, in order, recording each of them.
+    ///
+    /// Returns the number of applied commands.
+    pub fn apply_pending(&mut self, pending: &PendingCommands<TCommand>) -> usize {
+        pending
+            .receiver
+            .try_iter()
+            .map(|(label, command)| self.apply_entry(self.new_entry(label, command)))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn it_updates_from_several_threads() {
+        let list = SharedUndo::new(Vec::new());
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let list = list.clone();
+                thread::spawn(move || list.update_labeled(format!("push {i}"), move |l| l.push(i)))
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(list.read(Vec::len), 4);
+
+        while list.undo() {}
+        assert!(list.read(Vec::is_empty));
+        assert!(!list.can_undo());
+        assert!(list.redo());
+        assert!(list.can_redo());
+        assert_eq!(list.read(Vec::len), 1);
+    }
+
+    #[test]
+    fn it_recovers_from_a_panicking_update() {
+        let counter = SharedUndo::new(1);
+        let result = {
+            let counter = counter.clone();
+            thread::spawn(move || {
+                counter.update(|value| {
+                    *value = 0;
+                    panic!("update failed");
+                });
+            })
+            .join()
+        };
+        assert!(result.is_err());
+
+        counter.update(|value| *value += 1);
+        assert_eq!(counter.read(|value| *value), 2);
+        assert!(counter.undo());
+        assert!(!counter.undo());
+    }
+
+    #[test]
+    fn it_applies_the_pending_commands_in_order() {
+        let mut list = Undo::new_send(Vec::new());
+        let (queue, pending) = CommandQueue::<SendUpdate<Vec<(u8, u8)>>>::new();
+        let producers: Vec<_> = (0..4)
+            .map(|i| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for j in 0..10 {
+                        queue.update(move |l| l.push((i, j)));
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(list.apply_pending(&pending), 40);
+        assert_eq!(list.apply_pending(&pending), 0);
+        assert_eq!(list.history_len(), 40);
+        for i in 0..4 {
+            let order: Vec<_> = list
+                .iter()
+                .filter(|(k, _)| *k == i)
+                .map(|(_, j)| *j)
+                .collect();
+            assert_eq!(order, (0..10).collect::<Vec<_>>());
+        }
+
+        drop(pending);
+        queue.update(Vec::clear);
+        list.undo();
+        assert_eq!(list.len(), 39);
+    }
+}
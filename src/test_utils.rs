@@ -0,0 +1,143 @@
+//! Utilities to test the undo behavior of an application: the [`assert_history!`](crate::assert_history) macro
+//! and a runner of scripted scenarios.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{ChangeKind, Command, Snapshot, Undo, Update};
+
+/// Asserts that the labels of the history of an [`Undo`] are the given ones, unlabeled updates having an empty
+/// label, and optionally that its position is the given one.
+///
+/// # Example
+/// ```
+/// use simple_undo::{assert_history, Undo};
+///
+/// let mut list = Undo::new(vec![1]);
+/// list.update_labeled("add", |list| list.push(2));
+/// list.update_labeled("remove", |list| {
+///     list.remove(0);
+/// });
+/// list.undo();
+///
+/// assert_history!(list, ["add", "remove"], position = 1);
+/// ```
+#[macro_export]
+macro_rules! assert_history {
+    ($undo:expr, [$($label:expr),* $(,)?] $(, position = $position:expr)? $(,)?) => {{
+        let undo = &$undo;
+        let expected: &[&str] = &[$($label),*];
+        assert_eq!($crate::test_utils::labels(undo), expected, "labels of the history");
+        $(assert_eq!(undo.position(), $position, "position in the history");)?
+    }};
+}
+
+/// Returns the labels of the history of an [`Undo`], unlabeled updates having an empty label.
+///
+/// Used by [`assert_history!`](crate::assert_history).
+pub fn labels<'undo, TState, TCommand, TObserver, TMeta>(
+    undo: &'undo Undo<'_, TState, TCommand, TObserver, TMeta>,
+) -> Vec<&'undo str>
+where
+    TState: Snapshot,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    undo.history()
+        .map(|entry| entry.label.unwrap_or_default())
+        .collect()
+}
+
+/// A step of a scenario run by [`run_scenario`].
+pub enum Step<'state, TState> {
+    /// Updates the state with [`Undo::update_labeled`].
+    Update(String, Update<'state, TState>),
+    /// Undoes the last update, which must be possible.
+    Undo,
+    /// Redoes the last undone update, which must be possible.
+    Redo,
+    /// Checks the current state.
+    Expect(TState),
+}
+
+impl<'state, TState> Step<'state, TState> {
+    /// Returns a step updating the state with the given label.
+    pub fn update(label: impl Into<String>, update_fn: impl Fn(&mut TState) + 'state) -> Self {
+        Self::Update(label.into(), Box::new(update_fn))
+    }
+}
+
+/// Runs the steps of a scenario on the given `Undo`.
+///
+/// # Panics
+/// Panics with the index of the failing step if an expected state differs from the current state,
+/// or if there is nothing to undo or redo.
+///
+/// # Example
+/// ```
+/// use simple_undo::test_utils::{run_scenario, Step};
+/// use simple_undo::Undo;
+///
+/// let mut counter = Undo::new(0);
+/// run_scenario(&mut counter, [
+///     Step::update("add", |value| *value += 1),
+///     Step::update("double", |value| *value *= 2),
+///     Step::Expect(2),
+///     Step::Undo,
+///     Step::Expect(1),
+///     Step::Redo,
+///     Step::Expect(2),
+/// ]);
+/// ```
+pub fn run_scenario<'state, TState>(
+    undo: &mut Undo<'state, TState>,
+    steps: impl IntoIterator<Item = Step<'state, TState>>,
+) where
+    TState: Snapshot + PartialEq + fmt::Debug + 'state,
+{
+    for (index, step) in steps.into_iter().enumerate() {
+        match step {
+            Step::Update(label, update_fn) => {
+                undo.update_labeled(label, update_fn);
+            }
+            Step::Undo => assert!(undo.undo(), "step {index}: nothing to undo"),
+            Step::Redo => assert!(undo.redo(), "step {index}: nothing to redo"),
+            Step::Expect(expected) => {
+                assert_eq!(**undo, expected, "step {index}: unexpected state");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_checks_the_history() {
+        let mut text = Undo::new(String::new());
+        assert_history!(text, []);
+        run_scenario(
+            &mut text,
+            [
+                Step::update("type", |text: &mut String| text.push_str("Hi")),
+                Step::Undo,
+                Step::Expect(String::new()),
+                Step::update("paste", |text: &mut String| text.push_str("Hello")),
+            ],
+        );
+        text.update(|text| text.push('!'));
+        assert_history!(text, ["paste", ""], position = 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "step 1: nothing to redo")]
+    fn it_reports_the_failing_step() {
+        run_scenario(
+            &mut Undo::new(0),
+            [Step::update("add", |value| *value += 1), Step::Redo],
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! An undoable text for large documents, stored in a [`Rope`] and enabled by the `ropey` feature.
+
+use core::ops::{Deref, Range};
+
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::AnyUndo;
+
+/// The maximum number of chars of a grapheme removed by [`UndoText::backspace`].
+const MAX_GRAPHEME_CHARS: usize = 32;
+
+/// An edit recorded by an [`UndoText`], with the inserted or removed text needed to revert it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextOp {
+    /// Inserts the text at the char index.
+    Insert {
+        /// The char index of the inserted text.
+        char_idx: usize,
+        /// The inserted text.
+        text: String,
+    },
+    /// Removes the text at the char index.
+    Remove {
+        /// The char index of the removed text.
+        char_idx: usize,
+        /// The removed text.
+        text: String,
+    },
+}
+
+impl TextOp {
+    /// Applies the edit to the rope.
+    fn apply(&self, rope: &mut Rope) {
+        match self {
+            Self::Insert { char_idx, text } => rope.insert(*char_idx, text),
+            Self::Remove { char_idx, text } => {
+                rope.remove(*char_idx..*char_idx + text.chars().count());
+            }
+        }
+    }
+
+    /// Reverts the edit applied to the rope.
+    fn revert(&self, rope: &mut Rope) {
+        match self {
+            Self::Insert { char_idx, text } => {
+                rope.remove(*char_idx..*char_idx + text.chars().count());
+            }
+            Self::Remove { char_idx, text } => rope.insert(*char_idx, text),
+        }
+    }
+}
+
+/// A text like [`Undo<String>`](crate::Undo) stored in a [`Rope`], recording each edit as a [`TextOp`].
+///
+/// Edits and undos take `O(log n)` in the length of the document, which is never cloned.
+/// Consecutive typing is coalesced into words, and consecutive removals into a single edit,
+/// until [`UndoText::end_step`] is called or the text is undone or redone.
+///
+/// # Example
+/// ```
+/// use ropey::Rope;
+/// use simple_undo::text::UndoText;
+///
+/// let mut text = UndoText::new(Rope::from_str("Hello"));
+/// for (i, c) in " big world".chars().enumerate() {
+///     text.insert(5 + i, c.encode_utf8(&mut [0; 4]));
+/// }
+/// assert_eq!(text.history_len(), 2); // " big ", "world"
+/// text.backspace(15);
+///
+/// text.undo();
+/// assert_eq!(text.to_string(), "Hello big world");
+/// text.undo();
+/// assert_eq!(text.to_string(), "Hello big ");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UndoText {
+    /// The current text to edit.
+    current_text: Rope,
+    /// All recorded edits, applied or not.
+    ops: Vec<TextOp>,
+    /// Number of edits applied to the current text. Undoing reduces this number.
+    nb_updates: usize,
+    /// `false` when the next edit must not be coalesced with the last recorded one.
+    is_coalescing: bool,
+}
+
+impl UndoText {
+    /// Wraps the given text in an `UndoText`, which will track all edits and allows undoing or redoing them.
+    #[must_use]
+    pub const fn new(text: Rope) -> Self {
+        Self {
+            current_text: text,
+            ops: Vec::new(),
+            nb_updates: 0,
+            is_coalescing: false,
+        }
+    }
+
+    /// Unwraps the inner text to an owned value, disabling the undo/redo feature.
+    #[must_use]
+    pub fn unwrap(self) -> Rope {
+        self.current_text
+    }
+
+    /// Returns `true` if there is an edit that can be undone with [`UndoText::undo`].
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        self.nb_updates > 0
+    }
+
+    /// Returns `true` if there is an undone edit that can be redone with [`UndoText::redo`].
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        self.nb_updates < self.ops.len()
+    }
+
+    /// Returns the number of recorded edits, including the undone ones that can still be redone.
+    #[must_use]
+    pub const fn history_len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns the number of edits currently applied to the text.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.nb_updates
+    }
+
+    /// Inserts the text at the char index, like [`Rope::insert`].
+    ///
+    /// It is coalesced with the previous insertion if it follows it, unless it starts a new word
+    /// after the whitespaces typed at the end of a word.
+    /// Note that future [`UndoText::redo`] are reset, as with every edit.
+    ///
+    /// # Panics
+    /// Panics if `char_idx` is out of bounds.
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.current_text.insert(char_idx, text);
+        if let Some(TextOp::Insert {
+            char_idx: previous_idx,
+            text: previous_text,
+        }) = self.coalescing_op()
+        {
+            let is_new_word = previous_text.ends_with(char::is_whitespace)
+                && previous_text.contains(|c: char| !c.is_whitespace())
+                && !text.starts_with(char::is_whitespace);
+            if *previous_idx + previous_text.chars().count() == char_idx && !is_new_word {
+                previous_text.push_str(text);
+                return;
+            }
+        }
+        self.record(TextOp::Insert {
+            char_idx,
+            text: text.into(),
+        });
+    }
+
+    /// Removes the chars in the range, like [`Rope::remove`].
+    ///
+    /// It is coalesced with the previous removal if they are adjacent, as when pressing backspace or delete repeatedly.
+    ///
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    pub fn remove(&mut self, char_range: Range<usize>) {
+        if char_range.is_empty() {
+            return;
+        }
+        let removed = self.current_text.slice(char_range.clone()).to_string();
+        self.current_text.remove(char_range.clone());
+        if let Some(TextOp::Remove { char_idx, text }) = self.coalescing_op() {
+            if char_range.end == *char_idx {
+                text.insert_str(0, &removed);
+                *char_idx = char_range.start;
+                return;
+            }
+            if char_range.start == *char_idx {
+                text.push_str(&removed);
+                return;
+            }
+        }
+        self.record(TextOp::Remove {
+            char_idx: char_range.start,
+            text: removed,
+        });
+    }
+
+    /// Removes the grapheme before the char index, like pressing backspace with the cursor at this index,
+    /// so that a character made of several chars, like an emoji or an accented letter, is removed at once.
+    ///
+    /// Returns the char index of the cursor after the removal.
+    ///
+    /// # Panics
+    /// Panics if `char_idx` is out of bounds.
+    pub fn backspace(&mut self, char_idx: usize) -> usize {
+        let before = self
+            .current_text
+            .slice(char_idx.saturating_sub(MAX_GRAPHEME_CHARS)..char_idx)
+            .to_string();
+        let len = before
+            .graphemes(true)
+            .next_back()
+            .map_or(0, |grapheme| grapheme.chars().count());
+        self.remove(char_idx - len..char_idx);
+        char_idx - len
+    }
+
+    /// Prevents the next edit from being coalesced with the last recorded one.
+    pub const fn end_step(&mut self) {
+        self.is_coalescing = false;
+    }
+
+    /// Returns the recorded edits, from the oldest to the most recent, including the undone ones.
+    pub fn ops(&self) -> impl Iterator<Item = &TextOp> {
+        self.ops.iter()
+    }
+
+    /// Undo the last edit by reverting it.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.nb_updates == 0 {
+            return false;
+        }
+        self.is_coalescing = false;
+        self.nb_updates -= 1;
+        self.ops[self.nb_updates].revert(&mut self.current_text);
+        true
+    }
+
+    /// Redo the last edit that have been undone using [`UndoText::undo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.nb_updates == self.ops.len() {
+            return false;
+        }
+        self.is_coalescing = false;
+        self.ops[self.nb_updates].apply(&mut self.current_text);
+        self.nb_updates += 1;
+        true
+    }
+
+    /// Returns the last recorded edit if the next one can be coalesced with it.
+    fn coalescing_op(&mut self) -> Option<&mut TextOp> {
+        if self.is_coalescing && self.nb_updates == self.ops.len() {
+            self.ops.last_mut()
+        } else {
+            None
+        }
+    }
+
+    /// Records the given edit, already applied to the current text.
+    fn record(&mut self, op: TextOp) {
+        // Discard previous edits when editing after an undo.
+        self.ops.truncate(self.nb_updates);
+        self.ops.push(op);
+        self.nb_updates += 1;
+        self.is_coalescing = true;
+    }
+}
+
+impl Deref for UndoText {
+    type Target = Rope;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_text
+    }
+}
+
+impl AnyUndo for UndoText {
+    fn undo(&mut self) -> bool {
+        Self::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Self::redo(self)
+    }
+
+    fn can_undo(&self) -> bool {
+        Self::can_undo(self)
+    }
+
+    fn can_redo(&self) -> bool {
+        Self::can_redo(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_coalesces_typing_and_removals() {
+        let mut text = UndoText::new(Rope::new());
+        let mut cursor = 0;
+        for word in ["Hi", " there", " you"] {
+            for c in word.chars() {
+                text.insert(cursor, c.encode_utf8(&mut [0; 4]));
+                cursor += 1;
+            }
+        }
+        assert_eq!(text.history_len(), 3);
+
+        cursor = text.backspace(cursor);
+        text.backspace(cursor);
+        text.remove(0..1);
+        text.remove(0..1);
+        assert_eq!(text.to_string(), " there y");
+        assert_eq!(
+            text.ops().skip(3).collect::<Vec<_>>(),
+            [
+                &TextOp::Remove {
+                    char_idx: 10,
+                    text: "ou".into()
+                },
+                &TextOp::Remove {
+                    char_idx: 0,
+                    text: "Hi".into()
+                },
+            ]
+        );
+
+        text.undo();
+        text.undo();
+        assert_eq!(text.to_string(), "Hi there you");
+        text.end_step();
+        text.insert(12, "!");
+        assert!(!text.can_redo());
+        text.undo();
+        text.undo();
+        assert_eq!(text.to_string(), "Hi there ");
+        text.redo();
+        text.insert(12, "?");
+        assert_eq!(text.history_len(), 4);
+    }
+
+    #[test]
+    fn it_removes_whole_graphemes() {
+        let mut text = UndoText::new(Rope::from_str("cafe\u{301} 👍🏽"));
+        let cursor = text.backspace(text.len_chars());
+        assert_eq!(text.to_string(), "cafe\u{301} ");
+        text.backspace(cursor - 1);
+        assert_eq!(text.to_string(), "caf ");
+        assert_eq!(text.history_len(), 2);
+        assert_eq!(text.backspace(0), 0);
+        text.undo();
+        assert_eq!(text.to_string(), "cafe\u{301} ");
+    }
+}
@@ -0,0 +1,108 @@
+//! Diffs of a text state between two positions of the history, enabled by the `similar` feature.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use similar::{ChangeTag, TextDiff};
+
+use crate::{ChangeKind, Command, OutOfRange, Snapshot, Undo};
+
+impl<TState, TCommand, TObserver, TMeta> Undo<'_, TState, TCommand, TObserver, TMeta>
+where
+    TState: Snapshot + AsRef<str>,
+    TState::Snapshot: Clone,
+    TCommand: Command<TState>,
+    TObserver: FnMut(&TState, ChangeKind),
+{
+    /// Renders the changes of the text from position `from` to position `to` as a unified diff of its lines,
+    /// both positions being between `0` and [`Undo::history_len`].
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if a position is past the end of the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// let mut text = Undo::new(String::from("Hello\nworld\n"));
+    /// text.mark_saved();
+    /// text.update(|text| *text = text.replace("world", "there"));
+    ///
+    /// let since_save = text.text_diff(text.saved_position().unwrap(), text.position())?;
+    /// assert_eq!(
+    ///     since_save,
+    ///     "--- position 0\n+++ position 1\n@@ -1,2 +1,2 @@\n Hello\n-world\n+there\n"
+    /// );
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn text_diff(&self, from: usize, to: usize) -> Result<String, OutOfRange> {
+        self.check_position(from)?;
+        self.check_position(to)?;
+        let (before, after) = (self.state_at(from), self.state_at(to));
+        let diff = TextDiff::from_lines(before.as_ref(), after.as_ref());
+        Ok(diff
+            .unified_diff()
+            .header(&format!("position {from}"), &format!("position {to}"))
+            .to_string())
+    }
+
+    /// Returns the changes of the text from position `from` to position `to` word by word,
+    /// to render an inline diff, like [`Undo::text_diff`].
+    ///
+    /// Each change is tagged as deleted, inserted or kept, the whitespaces being separate words.
+    ///
+    /// # Errors
+    /// Returns [`OutOfRange`] if a position is past the end of the history.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::Undo;
+    /// use similar::ChangeTag;
+    ///
+    /// let mut text = Undo::new(String::from("Hello world"));
+    /// text.update(|text| *text = text.replace("world", "there"));
+    ///
+    /// let changes = text.word_diff(0, 1)?;
+    /// assert_eq!(changes[2], (ChangeTag::Delete, String::from("world")));
+    /// assert_eq!(changes[3], (ChangeTag::Insert, String::from("there")));
+    /// # Ok::<(), simple_undo::OutOfRange>(())
+    /// ```
+    pub fn word_diff(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> Result<Vec<(ChangeTag, String)>, OutOfRange> {
+        self.check_position(from)?;
+        self.check_position(to)?;
+        let (before, after) = (self.state_at(from), self.state_at(to));
+        let diff = TextDiff::from_words(before.as_ref(), after.as_ref());
+        Ok(diff
+            .iter_all_changes()
+            .map(|change| (change.tag(), String::from(change.value())))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_the_changes_between_two_positions() {
+        let mut text = Undo::new(String::from("a\nb\n"));
+        text.update(|text| text.push_str("c\n"));
+        text.update(|text| *text = text.replacen('a', "A", 1));
+        text.undo();
+
+        assert_eq!(
+            text.text_diff(2, 0).unwrap(),
+            "--- position 2\n+++ position 0\n@@ -1,3 +1,2 @@\n-A\n+a\n b\n-c\n"
+        );
+        assert_eq!(text.text_diff(1, 1).unwrap(), "");
+        assert!(text
+            .word_diff(0, 1)
+            .unwrap()
+            .contains(&(ChangeTag::Insert, String::from("c"))));
+        assert!(text.text_diff(0, 3).is_err());
+    }
+}
@@ -0,0 +1,573 @@
+//! Branching history where updating after an undo keeps the discarded updates, like the undo tree of vim.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::num::NonZeroUsize;
+use core::ops::Deref;
+
+use crate::{Snapshot, Update};
+
+/// Identifier of a node of an [`UndoTree`], each node being the state after an update.
+///
+/// Identifiers are changed by [`UndoTree::prune`], which returns the new identifier of each kept node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// Error returned by [`UndoTree::jump_to`] when the node doesn't belong to the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownNode {
+    /// The requested node.
+    pub node: NodeId,
+    /// The number of nodes in the tree at the time of the jump.
+    pub node_count: usize,
+}
+
+impl fmt::Display for UnknownNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "node {} doesn't belong to a tree of {} nodes",
+            self.node.0, self.node_count
+        )
+    }
+}
+
+impl Error for UnknownNode {}
+
+/// A node of the tree, the root being the initial state.
+struct Node<'state, TState: Snapshot> {
+    /// The update leading to this node from its parent, `None` for the root.
+    update_fn: Option<Update<'state, TState>>,
+    /// The snapshot of the state of this node, always taken for the root.
+    snapshot: Option<TState::Snapshot>,
+    label: Option<String>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// The child reached by [`UndoTree::redo`], which is the most recently visited one.
+    redo_child: Option<NodeId>,
+}
+
+/// A wrapper like [`Undo`](crate::Undo) whose history is a tree: updating after an undo creates a new branch
+/// instead of discarding the updates that could have been redone.
+///
+/// # Example
+/// ```
+/// use simple_undo::tree::UndoTree;
+///
+/// let mut text = UndoTree::new(String::new());
+/// text.update(|text| text.push_str("Hello"));
+/// text.update(|text| text.push_str(" world"));
+/// text.undo();
+/// text.update(|text| text.push_str(" there")); // creates a new branch
+/// assert_eq!(*text, "Hello there");
+///
+/// let branches = text.siblings().to_vec();
+/// text.jump_to(branches[0])?; // switches back to the first branch
+/// assert_eq!(*text, "Hello world");
+/// # Ok::<(), simple_undo::tree::UnknownNode>(())
+/// ```
+pub struct UndoTree<'state, TState: Snapshot> {
+    /// The current state to update.
+    current_state: TState,
+    /// All nodes of the tree, indexed by [`NodeId`], the first one being the root.
+    nodes: Vec<Node<'state, TState>>,
+    /// The node of the current state.
+    current: NodeId,
+    /// Number of updates between a node with a snapshot and its descendants with one.
+    checkpoint_interval: Option<NonZeroUsize>,
+}
+
+impl<'state, TState: Snapshot> UndoTree<'state, TState> {
+    /// Wraps the given state in an `UndoTree`, which will track all updates in a tree of branches.
+    pub fn new(state: TState) -> Self {
+        Self {
+            nodes: vec![Node {
+                update_fn: None,
+                snapshot: Some(state.snapshot()),
+                label: None,
+                parent: None,
+                children: Vec::new(),
+                redo_child: None,
+            }],
+            current_state: state,
+            current: NodeId(0),
+            checkpoint_interval: None,
+        }
+    }
+
+    /// Unwraps the inner state to an owned value, disabling the undo/redo feature.
+    pub fn unwrap(self) -> TState {
+        self.current_state
+    }
+
+    /// Returns the root node, whose state is the initial state.
+    #[must_use]
+    pub const fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Returns the node of the current state.
+    #[must_use]
+    pub const fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// Returns the number of nodes in the tree, including the root.
+    #[must_use]
+    pub const fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of updates between two snapshots of the state along a branch, if enabled.
+    #[must_use]
+    pub const fn checkpoint_interval(&self) -> Option<NonZeroUsize> {
+        self.checkpoint_interval
+    }
+
+    /// Enables snapshots of the state of new nodes every `interval` updates along a branch,
+    /// or disables them with `None`, like [`Undo::set_checkpoint_interval`](crate::Undo::set_checkpoint_interval).
+    ///
+    /// Undoing and jumping then only re-apply the updates since the nearest ancestor with a snapshot,
+    /// instead of all the updates since the root.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// # use std::num::NonZeroUsize;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.set_checkpoint_interval(NonZeroUsize::new(100));
+    /// for _ in 0..1000 {
+    ///     counter.update(|value| *value += 1);
+    /// }
+    ///
+    /// counter.undo(); // only re-applies 99 updates
+    /// assert_eq!(*counter, 999);
+    /// ```
+    pub fn set_checkpoint_interval(&mut self, interval: Option<NonZeroUsize>) {
+        self.checkpoint_interval = interval;
+        if interval.is_none() {
+            // Only keep the snapshot of the root.
+            for node in &mut self.nodes[1..] {
+                node.snapshot = None;
+            }
+        }
+    }
+
+    /// Returns the parent of the given node, `None` for the root.
+    ///
+    /// # Panics
+    /// Panics if the node doesn't belong to this tree.
+    #[must_use]
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    /// Returns the children of the given node, from the oldest to the most recent branch.
+    ///
+    /// # Panics
+    /// Panics if the node doesn't belong to this tree.
+    #[must_use]
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    /// Returns the label of the update leading to the given node, if any.
+    ///
+    /// # Panics
+    /// Panics if the node doesn't belong to this tree.
+    #[must_use]
+    pub fn label(&self, node: NodeId) -> Option<&str> {
+        self.nodes[node.0].label.as_deref()
+    }
+
+    /// Returns the branches that can be redone from the current node, from the oldest to the most recent.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.undo();
+    /// counter.update(|value| *value += 2);
+    /// counter.undo();
+    /// assert_eq!(counter.branches().len(), 2);
+    /// ```
+    #[must_use]
+    pub fn branches(&self) -> &[NodeId] {
+        self.children(self.current)
+    }
+
+    /// Returns the current node and its siblings, which are the alternative branches of the last update.
+    #[must_use]
+    pub fn siblings(&self) -> &[NodeId] {
+        self.parent(self.current).map_or_else(
+            || core::slice::from_ref(&self.current),
+            |parent| self.children(parent),
+        )
+    }
+
+    /// Updates the current state with the given mutating function, creating a new branch if updates were undone.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.undo();
+    /// counter.update(|value| *value += 2);
+    /// assert_eq!(*counter, 2);
+    /// assert_eq!(counter.node_count(), 3); // the root and both branches
+    /// ```
+    pub fn update(&mut self, update_fn: impl Fn(&mut TState) + 'state) {
+        self.record(None, Box::new(update_fn));
+    }
+
+    /// Updates the current state like [`UndoTree::update`], attaching a label to the new node.
+    pub fn update_labeled(
+        &mut self,
+        label: impl Into<String>,
+        update_fn: impl Fn(&mut TState) + 'state,
+    ) {
+        self.record(Some(label.into()), Box::new(update_fn));
+    }
+
+    /// Undo the last update, moving to the parent node.
+    ///
+    /// Returns `false` if the current node is the root.
+    pub fn undo(&mut self) -> bool {
+        let Some(parent) = self.parent(self.current) else {
+            return false;
+        };
+        let from = self.current;
+        self.current = parent;
+        self.regenerate(from);
+        true
+    }
+
+    /// Redo the most recently visited branch of the current node.
+    ///
+    /// Returns `false` if the current node has no children.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.update(|value| *value += 1);
+    /// counter.undo();
+    /// counter.update(|value| *value += 2);
+    /// counter.undo();
+    ///
+    /// counter.redo(); // the most recent branch
+    /// assert_eq!(*counter, 2);
+    /// assert!(!counter.redo());
+    /// ```
+    pub fn redo(&mut self) -> bool {
+        let Some(child) = self.nodes[self.current.0].redo_child else {
+            return false;
+        };
+        if let Some(update_fn) = &self.nodes[child.0].update_fn {
+            update_fn(&mut self.current_state);
+        }
+        self.current = child;
+        true
+    }
+
+    /// Moves to any node of the tree, such as a sibling branch, regenerating its state
+    /// from its nearest ancestor with a snapshot, or from the current node if it's an ancestor.
+    ///
+    /// The branches leading to this node become the ones followed by [`UndoTree::redo`].
+    ///
+    /// # Errors
+    /// Returns [`UnknownNode`] if the node doesn't belong to this tree, leaving the state untouched.
+    pub fn jump_to(&mut self, node: NodeId) -> Result<(), UnknownNode> {
+        if node.0 >= self.nodes.len() {
+            return Err(UnknownNode {
+                node,
+                node_count: self.nodes.len(),
+            });
+        }
+        let from = self.current;
+        self.current = node;
+        let mut child = node;
+        while let Some(parent) = self.parent(child) {
+            self.nodes[parent.0].redo_child = Some(child);
+            child = parent;
+        }
+        self.regenerate(from);
+        Ok(())
+    }
+
+    /// Removes all branches that are not on the path from the root to the current node
+    /// or on the path followed by [`UndoTree::redo`] from it.
+    ///
+    /// Returns the new identifier of each kept node by its previous one, the other [`NodeId`]s being invalid.
+    ///
+    /// # Example
+    /// ```
+    /// # use simple_undo::tree::UndoTree;
+    /// let mut counter = UndoTree::new(0);
+    /// counter.update(|value| *value += 1);
+    /// let discarded = counter.current();
+    /// counter.undo();
+    /// counter.update(|value| *value += 2);
+    /// let kept = counter.current();
+    /// assert_eq!(counter.node_count(), 3);
+    ///
+    /// let remapped = counter.prune();
+    /// assert_eq!(counter.node_count(), 2);
+    /// assert_eq!(*counter, 2);
+    /// assert_eq!(remapped.get(&kept), Some(&counter.current()));
+    /// assert_eq!(remapped.get(&discarded), None);
+    /// ```
+    pub fn prune(&mut self) -> BTreeMap<NodeId, NodeId> {
+        let mut kept = self.path_to(self.current);
+        let mut node = self.current;
+        while let Some(child) = self.nodes[node.0].redo_child {
+            kept.push(child);
+            node = child;
+        }
+        let current_index = self.path_to(self.current).len() - 1;
+
+        let mut nodes = core::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.nodes = kept
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| {
+                let mut node = nodes[node.0].take()?;
+                node.parent = index.checked_sub(1).map(NodeId);
+                node.redo_child = (index + 1 < kept.len()).then_some(NodeId(index + 1));
+                node.children = node.redo_child.into_iter().collect();
+                Some(node)
+            })
+            .collect();
+        self.current = NodeId(current_index);
+        kept.into_iter()
+            .enumerate()
+            .map(|(index, node)| (node, NodeId(index)))
+            .collect()
+    }
+
+    /// Returns the nodes from the root to the given node, both included.
+    fn path_to(&self, node: NodeId) -> Vec<NodeId> {
+        let mut path = vec![node];
+        let mut node = node;
+        while let Some(parent) = self.parent(node) {
+            path.push(parent);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Returns the number of updates to re-apply to regenerate the state of the given node
+    /// from its nearest ancestor with a snapshot.
+    fn replay_len(&self, node: NodeId) -> usize {
+        let mut len = 0;
+        let mut node = node;
+        while self.nodes[node.0].snapshot.is_none() {
+            let Some(parent) = self.parent(node) else {
+                break;
+            };
+            len += 1;
+            node = parent;
+        }
+        len
+    }
+
+    /// Regenerates the state of the current node, the current state being the one of the node `from`,
+    /// by re-applying the updates since `from` if it's an ancestor, or else since the nearest ancestor
+    /// with a snapshot, the root always having one.
+    fn regenerate(&mut self, from: NodeId) {
+        let mut path = Vec::new();
+        let mut node = self.current;
+        while node != from {
+            if let Some(snapshot) = &self.nodes[node.0].snapshot {
+                self.current_state.restore(snapshot);
+                break;
+            }
+            path.push(node);
+            let Some(parent) = self.parent(node) else {
+                break;
+            };
+            node = parent;
+        }
+        for node in path.into_iter().rev() {
+            if let Some(update_fn) = &self.nodes[node.0].update_fn {
+                update_fn(&mut self.current_state);
+            }
+        }
+    }
+
+    /// Applies the given update and records it as a new child of the current node.
+    fn record(&mut self, label: Option<String>, update_fn: Update<'state, TState>) {
+        update_fn(&mut self.current_state);
+        let is_due = self
+            .checkpoint_interval
+            .is_some_and(|interval| self.replay_len(self.current) + 1 >= interval.get());
+        let node = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            update_fn: Some(update_fn),
+            snapshot: is_due.then(|| self.current_state.snapshot()),
+            label,
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo_child: None,
+        });
+        let parent = &mut self.nodes[self.current.0];
+        parent.children.push(node);
+        parent.redo_child = Some(node);
+        self.current = node;
+    }
+}
+
+impl<TState: Snapshot> Deref for UndoTree<'_, TState> {
+    type Target = TState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_discarded_branches() {
+        let mut counter = UndoTree::new(0);
+        counter.update_labeled("+1", |value| *value += 1);
+        counter.update_labeled("+2", |value| *value += 2);
+        counter.undo();
+        counter.update_labeled("+10", |value| *value += 10);
+        assert_eq!(*counter, 11);
+
+        let siblings = counter.siblings().to_vec();
+        let labels: Vec<_> = siblings.iter().map(|node| counter.label(*node)).collect();
+        assert_eq!(labels, [Some("+2"), Some("+10")]);
+
+        counter.jump_to(siblings[0]).unwrap();
+        assert_eq!(*counter, 3);
+        counter.undo();
+        counter.undo();
+        assert_eq!(*counter, 0);
+        assert!(!counter.undo());
+        counter.redo();
+        counter.redo(); // follows the branch visited last
+        assert_eq!(*counter, 3);
+        assert_eq!(counter.current(), siblings[0]);
+    }
+
+    #[test]
+    fn it_prunes_inactive_branches() {
+        let mut counter = UndoTree::new(0);
+        counter.update(|value| *value += 1);
+        counter.update(|value| *value += 2);
+        counter.undo();
+        counter.update(|value| *value += 10);
+        counter.update(|value| *value += 100);
+        counter.undo();
+        counter.undo();
+        counter.update(|value| *value += 1000);
+        counter.undo();
+        counter.redo();
+        counter.undo();
+        let branch = counter.branches()[1];
+        counter.jump_to(branch).unwrap();
+        assert_eq!(counter.node_count(), 6);
+
+        counter.prune();
+        assert_eq!(counter.node_count(), 4);
+        assert_eq!(*counter, 11);
+        assert_eq!(counter.siblings(), [counter.current()]);
+        counter.redo();
+        assert_eq!(*counter, 111);
+        while counter.undo() {}
+        assert_eq!(*counter, 0);
+    }
+
+    #[test]
+    fn it_remaps_the_kept_nodes_when_pruning() {
+        let mut counter = UndoTree::new(0);
+        counter.update(|value| *value += 1);
+        let first = counter.current();
+        counter.update(|value| *value += 2);
+        let discarded = counter.current();
+        counter.undo();
+        counter.update(|value| *value += 10);
+        let current = counter.current();
+        counter.update(|value| *value += 100);
+        let redone = counter.current();
+        counter.undo();
+
+        let remapped = counter.prune();
+        assert_eq!(remapped.len(), 4);
+        assert_eq!(remapped.get(&counter.root()), Some(&counter.root()));
+        assert_eq!(remapped.get(&discarded), None);
+        assert_eq!(remapped[&current], counter.current());
+        counter.jump_to(remapped[&first]).unwrap();
+        assert_eq!(*counter, 1);
+        counter.jump_to(remapped[&redone]).unwrap();
+        assert_eq!(*counter, 111);
+    }
+
+    #[test]
+    fn it_regenerates_from_the_nearest_snapshot() {
+        use alloc::rc::Rc;
+        use core::cell::Cell;
+
+        let applied = Rc::new(Cell::new(0));
+        let mut counter = UndoTree::new(0);
+        counter.set_checkpoint_interval(NonZeroUsize::new(2));
+        for _ in 0..5 {
+            let applied = Rc::clone(&applied);
+            counter.update(move |value| {
+                applied.set(applied.get() + 1);
+                *value += 1;
+            });
+        }
+        let last = counter.current();
+
+        applied.set(0);
+        counter.undo(); // restores the snapshot after 4 updates
+        assert_eq!((*counter, applied.get()), (4, 0));
+        counter.undo(); // restores the snapshot after 2 updates
+        assert_eq!((*counter, applied.get()), (3, 1));
+        counter.jump_to(last).unwrap(); // restores the snapshot after 4 updates
+        assert_eq!((*counter, applied.get()), (5, 2));
+
+        counter.set_checkpoint_interval(None);
+        applied.set(0);
+        counter.undo(); // restores the root
+        assert_eq!((*counter, applied.get()), (4, 4));
+        while *counter > 1 {
+            counter.undo();
+        }
+        applied.set(0);
+        counter.jump_to(last).unwrap(); // replays from the current node
+        assert_eq!((*counter, applied.get()), (5, 4));
+    }
+
+    #[test]
+    fn it_rejects_unknown_nodes() {
+        let mut counter = UndoTree::new(0);
+        counter.update(|value| *value += 1);
+        assert_eq!(
+            counter.jump_to(NodeId(2)),
+            Err(UnknownNode {
+                node: NodeId(2),
+                node_count: 2
+            })
+        );
+        assert_eq!(*counter, 1);
+        assert!(counter.jump_to(counter.root()).is_ok());
+        assert_eq!(*counter, 0);
+    }
+}
@@ -0,0 +1,206 @@
+//! JavaScript bindings, enabled by the `wasm` feature, to use an [`Undo`] from a web app through `wasm-bindgen`.
+//!
+//! With the `local-storage` feature, the history can be saved to the `localStorage` of the browser
+//! to be restored after a page reload.
+
+use js_sys::{Array, Function};
+#[cfg(feature = "local-storage")]
+use js_sys::{Object, Reflect, JSON};
+use wasm_bindgen::prelude::*;
+#[cfg(feature = "local-storage")]
+use web_sys::Storage;
+
+use crate::{Undo, Update};
+
+/// An [`Undo`] wrapping a JavaScript value, exported to JavaScript as the `UndoJs` class.
+///
+/// Each update is a JavaScript function returning the new state from the current one, which is called once:
+/// the history records the returned value, so the function doesn't need to be deterministic.
+/// It must return a new value without mutating its argument though, as the recorded states are references
+/// to JavaScript values: mutating the current state in place also changes the recorded ones, so undoing
+/// would leave it unchanged.
+/// A serializable Rust state can be converted from and to a `JsValue` with `serde-wasm-bindgen`.
+///
+/// ```js
+/// const counter = new UndoJs(0);
+/// counter.update((value) => value + 10);
+/// counter.update((value) => value * 2);
+/// counter.undo();
+/// console.log(counter.state); // 10
+/// ```
+#[wasm_bindgen]
+pub struct UndoJs {
+    /// The wrapped `Undo`, recording the states returned by the updates.
+    inner: Undo<'static, JsValue>,
+}
+
+#[wasm_bindgen]
+impl UndoJs {
+    /// Wraps the given state, like [`Undo::new`].
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new(state: JsValue) -> Self {
+        Self {
+            inner: Undo::new(state),
+        }
+    }
+
+    /// Returns the current state.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn state(&self) -> JsValue {
+        self.inner.get().clone()
+    }
+
+    /// Updates the current state to the value returned by `update_fn`, called with the current state.
+    ///
+    /// The current state must not be mutated by `update_fn`, like `(state) => ({ ...state, x: 1 })` instead of
+    /// `(state) => { state.x = 1; return state; }`, or the recorded states would be changed as well.
+    ///
+    /// # Errors
+    /// Returns the exception thrown by `update_fn`, in which case nothing is recorded.
+    pub fn update(&mut self, update_fn: &Function) -> Result<(), JsValue> {
+        let new_state = update_fn.call1(&JsValue::NULL, &self.inner)?;
+        self.record(None, new_state);
+        Ok(())
+    }
+
+    /// Updates the current state like [`UndoJs::update`], attaching a label to the recorded update.
+    ///
+    /// # Errors
+    /// Returns the exception thrown by `update_fn`, in which case nothing is recorded.
+    #[wasm_bindgen(js_name = updateLabeled)]
+    pub fn update_labeled(&mut self, label: String, update_fn: &Function) -> Result<(), JsValue> {
+        let new_state = update_fn.call1(&JsValue::NULL, &self.inner)?;
+        self.record(Some(label), new_state);
+        Ok(())
+    }
+
+    /// Undo the last update, like [`Undo::undo`].
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.inner.undo()
+    }
+
+    /// Redo the last undone update, like [`Undo::redo`].
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        self.inner.redo()
+    }
+
+    /// Returns `true` if there is an update that can be undone.
+    #[wasm_bindgen(js_name = canUndo)]
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.inner.can_undo()
+    }
+
+    /// Returns `true` if there is an undone update that can be redone.
+    #[wasm_bindgen(js_name = canRedo)]
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        self.inner.can_redo()
+    }
+
+    /// Returns the number of updates currently applied to the state.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.inner.position()
+    }
+
+    /// Exports the snapshots of the state after each recorded update, like [`Undo::states`],
+    /// as an array starting with the initial state.
+    #[must_use]
+    pub fn snapshots(&self) -> Array {
+        self.inner.states().collect()
+    }
+}
+
+impl UndoJs {
+    /// Records an update setting the state to `new_state`.
+    fn record(&mut self, label: Option<String>, new_state: JsValue) {
+        let update_fn = move |state: &mut JsValue| state.clone_from(&new_state);
+        self.inner.apply_entry(
+            self.inner
+                .new_entry(label, Box::new(update_fn) as Update<'_, JsValue>),
+        );
+    }
+}
+
+#[cfg(feature = "local-storage")]
+#[wasm_bindgen]
+impl UndoJs {
+    /// Saves the whole history, with its snapshots and labels, to the `localStorage` under the given key.
+    ///
+    /// The states must be serializable with `JSON.stringify`.
+    ///
+    /// ```js
+    /// const text = UndoJs.restore("text") ?? new UndoJs("");
+    /// text.update((text) => text + "a");
+    /// text.save("text"); // restored after a page reload
+    /// ```
+    ///
+    /// # Errors
+    /// Returns the exception thrown if the `localStorage` is unavailable, full, or if a state can't be serialized.
+    pub fn save(&self, key: &str) -> Result<(), JsValue> {
+        let labels: Array = self
+            .inner
+            .history()
+            .map(|entry| entry.label.map_or(JsValue::NULL, JsValue::from))
+            .collect();
+        let session = Object::new();
+        Reflect::set(&session, &"states".into(), &self.snapshots())?;
+        Reflect::set(&session, &"labels".into(), &labels)?;
+        Reflect::set(&session, &"position".into(), &self.position().into())?;
+        let json = JSON::stringify(&session)?;
+        local_storage()?.set_item(key, &String::from(json))
+    }
+
+    /// Restores the history saved by [`UndoJs::save`] under the given key, or returns `undefined` if there is none.
+    ///
+    /// # Errors
+    /// Returns the exception thrown if the `localStorage` is unavailable or if the saved history is invalid.
+    pub fn restore(key: &str) -> Result<Option<Self>, JsValue> {
+        let Some(json) = local_storage()?.get_item(key)? else {
+            return Ok(None);
+        };
+        let session = JSON::parse(&json)?;
+        let states: Array = Reflect::get(&session, &"states".into())?.dyn_into()?;
+        let labels: Array = Reflect::get(&session, &"labels".into())?.dyn_into()?;
+        let position = Reflect::get(&session, &"position".into())?
+            .as_f64()
+            .ok_or("invalid position")?;
+
+        let mut states = states.iter();
+        let mut undo = Self::new(states.next().ok_or("missing initial state")?);
+        for (state, label) in states.zip(labels.iter()) {
+            undo.record(label.as_string(), state);
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // saved from a `usize`
+        let position = position as usize;
+        undo.inner
+            .jump_to(position)
+            .map_err(|error| JsError::new(&error.to_string()))?;
+        Ok(Some(undo))
+    }
+
+    /// Removes the history saved under the given key from the `localStorage`.
+    ///
+    /// # Errors
+    /// Returns the exception thrown if the `localStorage` is unavailable.
+    pub fn forget(key: &str) -> Result<(), JsValue> {
+        local_storage()?.remove_item(key)
+    }
+}
+
+/// Returns the `localStorage` of the browser.
+#[cfg(feature = "local-storage")]
+fn local_storage() -> Result<Storage, JsValue> {
+    web_sys::window()
+        .ok_or("no window")?
+        .local_storage()?
+        .ok_or_else(|| "localStorage is unavailable".into())
+}